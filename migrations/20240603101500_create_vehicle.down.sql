@@ -0,0 +1,15 @@
+-- DropForeignKey
+ALTER TABLE "parking_history" DROP CONSTRAINT "parking_history_vehicle_id_fkey";
+ALTER TABLE "vehicle" DROP CONSTRAINT "vehicle_user_id_fkey";
+
+-- DropIndex
+DROP INDEX IF EXISTS "parking_history_plate_number_idx";
+DROP INDEX IF EXISTS "vehicle_user_id_plate_number_key";
+DROP INDEX IF EXISTS "vehicle_id_key";
+
+-- AlterTable
+ALTER TABLE "parking_history" DROP COLUMN "vehicle_id",
+DROP COLUMN "plate_number";
+
+-- DropTable
+DROP TABLE IF EXISTS "vehicle";
@@ -0,0 +1,30 @@
+-- CreateTable
+CREATE TABLE "vehicle" (
+    "id" UUID NOT NULL,
+    "user_id" UUID NOT NULL,
+    "plate_number" TEXT NOT NULL,
+    "vehicle_type" "vehicle_type" NOT NULL,
+    "color" TEXT NOT NULL,
+    "image_url" TEXT,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP,
+    "updated_at" TIMESTAMP(3)
+);
+
+-- AlterTable
+ALTER TABLE "parking_history" ADD COLUMN     "vehicle_id" UUID,
+ADD COLUMN     "plate_number" TEXT;
+
+-- CreateIndex
+CREATE UNIQUE INDEX "vehicle_id_key" ON "vehicle"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "vehicle_user_id_plate_number_key" ON "vehicle"("user_id", "plate_number");
+
+-- CreateIndex
+CREATE INDEX "parking_history_plate_number_idx" ON "parking_history"("plate_number");
+
+-- AddForeignKey
+ALTER TABLE "vehicle" ADD CONSTRAINT "vehicle_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "user"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_history" ADD CONSTRAINT "parking_history_vehicle_id_fkey" FOREIGN KEY ("vehicle_id") REFERENCES "vehicle"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
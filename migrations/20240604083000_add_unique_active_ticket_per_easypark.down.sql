@@ -0,0 +1,2 @@
+-- DropIndex
+DROP INDEX IF EXISTS "parking_history_easypark_id_active_key";
@@ -0,0 +1,15 @@
+-- UpdateData
+UPDATE "parking_history" ph
+SET "ticket_status" = 'not_active', "updated_at" = CURRENT_TIMESTAMP
+FROM (
+    SELECT "id", row_number() OVER (
+        PARTITION BY "easypark_id"
+        ORDER BY "created_at" DESC NULLS LAST, "id" DESC
+    ) AS "position"
+    FROM "parking_history"
+    WHERE "ticket_status" IN ('active', 'default')
+) duplicate
+WHERE ph."id" = duplicate."id" AND duplicate."position" > 1;
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_history_easypark_id_active_key" ON "parking_history"("easypark_id") WHERE "ticket_status" IN ('active', 'default');
@@ -0,0 +1,11 @@
+-- AlterTable
+ALTER TABLE "parking_history" DROP COLUMN "penalty_amount",
+DROP COLUMN "penalty_reason";
+
+-- AlterTable
+ALTER TABLE "parking_lot" DROP COLUMN "lost_ticket_fee",
+DROP COLUMN "overstay_fee",
+DROP COLUMN "max_stay_hours";
+
+-- DropEnum
+DROP TYPE IF EXISTS "penalty_reason";
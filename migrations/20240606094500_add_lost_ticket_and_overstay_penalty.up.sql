@@ -0,0 +1,11 @@
+-- CreateEnum
+CREATE TYPE "penalty_reason" AS ENUM ('default', 'lost_ticket', 'overstay');
+
+-- AlterTable
+ALTER TABLE "parking_lot" ADD COLUMN     "lost_ticket_fee" DOUBLE PRECISION NOT NULL DEFAULT 0,
+ADD COLUMN     "overstay_fee" DOUBLE PRECISION NOT NULL DEFAULT 0,
+ADD COLUMN     "max_stay_hours" INTEGER;
+
+-- AlterTable
+ALTER TABLE "parking_history" ADD COLUMN     "penalty_amount" DOUBLE PRECISION NOT NULL DEFAULT 0,
+ADD COLUMN     "penalty_reason" "penalty_reason";
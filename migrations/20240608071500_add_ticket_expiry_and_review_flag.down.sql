@@ -0,0 +1,8 @@
+-- DropIndex
+DROP INDEX IF EXISTS "parking_history_ticket_status_created_at_idx";
+
+-- AlterTable
+ALTER TABLE "parking_history" DROP COLUMN "flagged_for_review_at";
+
+-- Enum values cannot be dropped in place, expired tickets are closed instead
+UPDATE "parking_history" SET "ticket_status" = 'not_active' WHERE "ticket_status" = 'expired';
@@ -0,0 +1,8 @@
+-- AlterEnum
+ALTER TYPE "ticket_status" ADD VALUE 'expired';
+
+-- AlterTable
+ALTER TABLE "parking_history" ADD COLUMN     "flagged_for_review_at" TIMESTAMP(3);
+
+-- CreateIndex
+CREATE INDEX "parking_history_ticket_status_created_at_idx" ON "parking_history"("ticket_status", "created_at");
@@ -0,0 +1,16 @@
+-- DropForeignKey
+ALTER TABLE "keeper_shift" DROP CONSTRAINT "keeper_shift_parking_lot_id_fkey";
+ALTER TABLE "keeper_shift" DROP CONSTRAINT "keeper_shift_owner_id_fkey";
+ALTER TABLE "keeper_shift" DROP CONSTRAINT "keeper_shift_keeper_id_fkey";
+
+-- DropIndex
+DROP INDEX IF EXISTS "parking_history_keeper_id_check_out_date_idx";
+DROP INDEX IF EXISTS "keeper_shift_owner_id_idx";
+DROP INDEX IF EXISTS "keeper_shift_keeper_id_open_key";
+DROP INDEX IF EXISTS "keeper_shift_id_key";
+
+-- DropTable
+DROP TABLE IF EXISTS "keeper_shift";
+
+-- DropEnum
+DROP TYPE IF EXISTS "shift_status";
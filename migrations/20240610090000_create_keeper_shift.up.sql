@@ -0,0 +1,42 @@
+-- CreateEnum
+CREATE TYPE "shift_status" AS ENUM ('default', 'open', 'closed', 'signed_off');
+
+-- CreateTable
+CREATE TABLE "keeper_shift" (
+    "id" UUID NOT NULL,
+    "keeper_id" UUID NOT NULL,
+    "owner_id" UUID NOT NULL,
+    "parking_lot_id" UUID,
+    "shift_status" "shift_status" NOT NULL DEFAULT 'open',
+    "clock_in_at" TIMESTAMP(3) NOT NULL,
+    "clock_out_at" TIMESTAMP(3),
+    "total_ticket" BIGINT,
+    "expected_cash" DOUBLE PRECISION,
+    "declared_cash" DOUBLE PRECISION,
+    "discrepancy" DOUBLE PRECISION,
+    "signed_off_at" TIMESTAMP(3),
+    "sign_off_note" TEXT,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP,
+    "updated_at" TIMESTAMP(3)
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "keeper_shift_id_key" ON "keeper_shift"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "keeper_shift_keeper_id_open_key" ON "keeper_shift"("keeper_id") WHERE "shift_status" = 'open';
+
+-- CreateIndex
+CREATE INDEX "keeper_shift_owner_id_idx" ON "keeper_shift"("owner_id");
+
+-- CreateIndex
+CREATE INDEX "parking_history_keeper_id_check_out_date_idx" ON "parking_history"("keeper_id", "check_out_date");
+
+-- AddForeignKey
+ALTER TABLE "keeper_shift" ADD CONSTRAINT "keeper_shift_keeper_id_fkey" FOREIGN KEY ("keeper_id") REFERENCES "user"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "keeper_shift" ADD CONSTRAINT "keeper_shift_owner_id_fkey" FOREIGN KEY ("owner_id") REFERENCES "user"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "keeper_shift" ADD CONSTRAINT "keeper_shift_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE SET NULL ON UPDATE CASCADE;
@@ -0,0 +1,8 @@
+-- DropIndex
+DROP INDEX IF EXISTS "parking_history_check_out_date_idx";
+
+-- DropIndex
+DROP INDEX IF EXISTS "parking_history_parking_lot_id_check_out_date_idx";
+
+-- DropTable
+DROP TABLE IF EXISTS "parking_lot_hourly_stat";
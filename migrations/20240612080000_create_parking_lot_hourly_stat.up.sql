@@ -0,0 +1,47 @@
+-- CreateTable
+-- One row per lot, vehicle type and UTC hour in which at least one vehicle was
+-- parked. The scheduler only recomputes the most recent hours, older hours are
+-- final so a refresh stays cheap as parking_history grows.
+CREATE TABLE "parking_lot_hourly_stat" (
+    "parking_lot_id" UUID NOT NULL,
+    "vehicle_type" "vehicle_type" NOT NULL,
+    "hour" TIMESTAMP(3) NOT NULL,
+    "occupied" BIGINT NOT NULL,
+    "arrivals" BIGINT NOT NULL,
+    "departures" BIGINT NOT NULL
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_hourly_stat_key" ON "parking_lot_hourly_stat"("parking_lot_id", "vehicle_type", "hour");
+
+-- CreateIndex
+CREATE INDEX "parking_lot_hourly_stat_hour_idx" ON "parking_lot_hourly_stat"("hour");
+
+-- CreateIndex
+CREATE INDEX "parking_history_parking_lot_id_check_out_date_idx" ON "parking_history"("parking_lot_id", "check_out_date");
+
+-- CreateIndex
+CREATE INDEX "parking_history_check_out_date_idx" ON "parking_history"("check_out_date");
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_hourly_stat" ADD CONSTRAINT "parking_lot_hourly_stat_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- InsertData
+-- Open tickets count up to the moment of the migration.
+INSERT INTO "parking_lot_hourly_stat" ("parking_lot_id", "vehicle_type", "hour", "occupied", "arrivals", "departures")
+SELECT
+    ph."parking_lot_id",
+    ph."vehicle_type",
+    h."hour",
+    COUNT(*),
+    COUNT(*) FILTER (WHERE date_trunc('hour', ph."check_in_date") = h."hour"),
+    COUNT(*) FILTER (WHERE date_trunc('hour', ph."check_out_date") = h."hour")
+FROM "parking_history" ph
+CROSS JOIN LATERAL generate_series(
+    date_trunc('hour', ph."check_in_date"),
+    date_trunc('hour', COALESCE(ph."check_out_date", now() AT TIME ZONE 'UTC')),
+    INTERVAL '1 hour'
+) AS h("hour")
+WHERE ph."check_in_date" IS NOT NULL AND
+    ph."ticket_status" IN ('active', 'not_active')
+GROUP BY ph."parking_lot_id", ph."vehicle_type", h."hour";
@@ -0,0 +1,3 @@
+-- DropIndex
+DROP INDEX IF EXISTS "parking_history_created_at_id_idx";
+DROP INDEX IF EXISTS "user_created_at_id_idx";
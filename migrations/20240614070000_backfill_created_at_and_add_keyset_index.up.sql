@@ -0,0 +1,11 @@
+-- Backfill
+-- Keyset pagination orders by ("created_at", "id"), rows without a timestamp
+-- would never show up on any page.
+UPDATE "user" SET "created_at" = COALESCE("updated_at", CURRENT_TIMESTAMP) WHERE "created_at" IS NULL;
+UPDATE "parking_history" SET "created_at" = COALESCE("check_in_date", "updated_at", CURRENT_TIMESTAMP) WHERE "created_at" IS NULL;
+
+-- CreateIndex
+CREATE INDEX "user_created_at_id_idx" ON "user"("created_at", "id");
+
+-- CreateIndex
+CREATE INDEX "parking_history_created_at_id_idx" ON "parking_history"("created_at", "id");
@@ -0,0 +1,5 @@
+-- DropIndex
+DROP INDEX IF EXISTS "parking_lot_address_trgm_idx";
+DROP INDEX IF EXISTS "parking_lot_area_name_trgm_idx";
+DROP INDEX IF EXISTS "user_phone_number_trgm_idx";
+DROP INDEX IF EXISTS "user_name_trgm_idx";
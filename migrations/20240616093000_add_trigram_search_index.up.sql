@@ -0,0 +1,14 @@
+-- CreateExtension
+CREATE EXTENSION IF NOT EXISTS "pg_trgm";
+
+-- CreateIndex
+CREATE INDEX "user_name_trgm_idx" ON "user" USING GIN ("name" gin_trgm_ops);
+
+-- CreateIndex
+CREATE INDEX "user_phone_number_trgm_idx" ON "user" USING GIN ("phone_number" gin_trgm_ops);
+
+-- CreateIndex
+CREATE INDEX "parking_lot_area_name_trgm_idx" ON "parking_lot" USING GIN ("area_name" gin_trgm_ops);
+
+-- CreateIndex
+CREATE INDEX "parking_lot_address_trgm_idx" ON "parking_lot" USING GIN ("address" gin_trgm_ops);
@@ -0,0 +1,5 @@
+-- DropIndex
+DROP INDEX "parking_history_receipt_code_key";
+
+-- AlterTable
+ALTER TABLE "parking_history" DROP COLUMN "receipt_code";
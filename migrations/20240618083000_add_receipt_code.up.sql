@@ -0,0 +1,5 @@
+-- AlterTable
+ALTER TABLE "parking_history" ADD COLUMN     "receipt_code" VARCHAR(16);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_history_receipt_code_key" ON "parking_history"("receipt_code");
@@ -0,0 +1,7 @@
+-- DropIndex
+DROP INDEX "parking_lot_latitude_longitude_idx";
+
+-- AlterTable
+ALTER TABLE "parking_lot" DROP CONSTRAINT "parking_lot_coordinates_check",
+DROP COLUMN "latitude",
+DROP COLUMN "longitude";
@@ -0,0 +1,14 @@
+-- AlterTable
+ALTER TABLE "parking_lot" ADD COLUMN     "latitude" DOUBLE PRECISION,
+ADD COLUMN     "longitude" DOUBLE PRECISION;
+
+-- AddCheckConstraint
+ALTER TABLE "parking_lot" ADD CONSTRAINT "parking_lot_coordinates_check" CHECK (
+    ("latitude" IS NULL AND "longitude" IS NULL) OR
+    ("latitude" BETWEEN -90 AND 90 AND "longitude" BETWEEN -180 AND 180)
+);
+
+-- CreateIndex
+-- Nearby search narrows to a bounding box on this index before computing the
+-- exact distance.
+CREATE INDEX "parking_lot_latitude_longitude_idx" ON "parking_lot"("latitude", "longitude");
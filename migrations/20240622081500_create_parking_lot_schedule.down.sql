@@ -0,0 +1,11 @@
+-- DropFunction
+DROP FUNCTION "parking_lot_is_open"(UUID, TIMESTAMP);
+DROP FUNCTION "parking_lot_hours_on"(UUID, DATE);
+
+-- DropTable
+DROP TABLE "parking_lot_closure";
+DROP TABLE "parking_lot_special_day";
+DROP TABLE "parking_lot_opening_hour";
+
+-- AlterTable
+ALTER TABLE "parking_lot" DROP COLUMN "timezone";
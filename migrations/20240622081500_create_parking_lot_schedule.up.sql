@@ -0,0 +1,126 @@
+-- AlterTable
+ALTER TABLE "parking_lot" ADD COLUMN     "timezone" TEXT NOT NULL DEFAULT 'Asia/Jakarta';
+
+-- CreateTable
+-- A close_time at or before open_time runs past midnight, equal times mean the
+-- lot is open the whole day. A lot without any row is always open.
+CREATE TABLE "parking_lot_opening_hour" (
+    "id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "day_of_week" SMALLINT NOT NULL,
+    "open_time" TIME NOT NULL,
+    "close_time" TIME NOT NULL,
+    "created_at" TIMESTAMP(3),
+
+    CONSTRAINT "parking_lot_opening_hour_day_of_week_check" CHECK ("day_of_week" BETWEEN 1 AND 7)
+);
+
+-- CreateTable
+-- Replaces the weekly hours on one date, without times the lot is closed.
+CREATE TABLE "parking_lot_special_day" (
+    "id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "date" DATE NOT NULL,
+    "open_time" TIME,
+    "close_time" TIME,
+    "note" TEXT,
+    "created_at" TIMESTAMP(3),
+
+    CONSTRAINT "parking_lot_special_day_time_check" CHECK (("open_time" IS NULL) = ("close_time" IS NULL))
+);
+
+-- CreateTable
+CREATE TABLE "parking_lot_closure" (
+    "id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "start_at" TIMESTAMP(3) NOT NULL,
+    "end_at" TIMESTAMP(3) NOT NULL,
+    "reason" TEXT,
+    "created_at" TIMESTAMP(3),
+
+    CONSTRAINT "parking_lot_closure_range_check" CHECK ("end_at" > "start_at")
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_opening_hour_id_key" ON "parking_lot_opening_hour"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_special_day_id_key" ON "parking_lot_special_day"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_closure_id_key" ON "parking_lot_closure"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_opening_hour_parking_lot_id_day_of_week_key" ON "parking_lot_opening_hour"("parking_lot_id", "day_of_week");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_special_day_parking_lot_id_date_key" ON "parking_lot_special_day"("parking_lot_id", "date");
+
+-- CreateIndex
+CREATE INDEX "parking_lot_closure_parking_lot_id_end_at_idx" ON "parking_lot_closure"("parking_lot_id", "end_at");
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_opening_hour" ADD CONSTRAINT "parking_lot_opening_hour_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_special_day" ADD CONSTRAINT "parking_lot_special_day_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_closure" ADD CONSTRAINT "parking_lot_closure_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- CreateFunction
+-- Hours that apply on a local date: the special day when there is one, else
+-- the weekly row, else the whole day when the lot has no weekly hours at all.
+CREATE FUNCTION "parking_lot_hours_on"("lot_id" UUID, "local_date" DATE)
+RETURNS TABLE ("open_time" TIME, "close_time" TIME)
+LANGUAGE plpgsql STABLE AS $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM "parking_lot_special_day" s WHERE s."parking_lot_id" = lot_id AND s."date" = local_date) THEN
+        RETURN QUERY
+            SELECT s."open_time", s."close_time"
+            FROM "parking_lot_special_day" s
+            WHERE s."parking_lot_id" = lot_id AND s."date" = local_date AND s."open_time" IS NOT NULL;
+    ELSIF EXISTS (SELECT 1 FROM "parking_lot_opening_hour" h WHERE h."parking_lot_id" = lot_id) THEN
+        RETURN QUERY
+            SELECT h."open_time", h."close_time"
+            FROM "parking_lot_opening_hour" h
+            WHERE h."parking_lot_id" = lot_id AND h."day_of_week" = EXTRACT(ISODOW FROM local_date);
+    ELSE
+        RETURN QUERY SELECT TIME '00:00', TIME '00:00';
+    END IF;
+END;
+$$;
+
+-- CreateFunction
+-- Whether the lot takes vehicles at a UTC timestamp. Hours running past
+-- midnight are looked up on the previous local date as well.
+CREATE FUNCTION "parking_lot_is_open"("lot_id" UUID, "at_utc" TIMESTAMP)
+RETURNS BOOLEAN
+LANGUAGE plpgsql STABLE AS $$
+DECLARE
+    local_at TIMESTAMP;
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM "parking_lot_closure" c
+        WHERE c."parking_lot_id" = lot_id AND c."start_at" <= at_utc AND c."end_at" > at_utc
+    ) THEN
+        RETURN FALSE;
+    END IF;
+
+    SELECT (at_utc AT TIME ZONE 'UTC') AT TIME ZONE pl."timezone" INTO local_at
+    FROM "parking_lot" pl
+    WHERE pl."id" = lot_id;
+
+    IF local_at IS NULL THEN
+        RETURN FALSE;
+    END IF;
+
+    RETURN EXISTS (
+        SELECT 1 FROM "parking_lot_hours_on"(lot_id, local_at::date) h
+        WHERE local_at::time >= h."open_time" AND (h."close_time" <= h."open_time" OR local_at::time < h."close_time")
+    ) OR EXISTS (
+        SELECT 1 FROM "parking_lot_hours_on"(lot_id, local_at::date - 1) h
+        WHERE h."close_time" <= h."open_time" AND local_at::time < h."close_time"
+    );
+END;
+$$;
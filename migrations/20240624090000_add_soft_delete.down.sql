@@ -0,0 +1,16 @@
+-- DropIndex
+DROP INDEX "parking_lot_owner_id_live_idx";
+DROP INDEX "user_owner_id_live_idx";
+DROP INDEX "user_phone_number_key";
+
+-- CreateIndex
+CREATE UNIQUE INDEX "user_phone_number_key" ON "user"("phone_number");
+
+-- AlterTable
+ALTER TABLE "parking_history" DROP COLUMN "deleted_at";
+
+-- AlterTable
+ALTER TABLE "parking_lot" DROP COLUMN "deleted_at";
+
+-- AlterTable
+ALTER TABLE "user" DROP COLUMN "deleted_at";
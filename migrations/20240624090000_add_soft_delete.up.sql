@@ -0,0 +1,22 @@
+-- AlterTable
+ALTER TABLE "user" ADD COLUMN     "deleted_at" TIMESTAMP(3);
+
+-- AlterTable
+ALTER TABLE "parking_lot" ADD COLUMN     "deleted_at" TIMESTAMP(3);
+
+-- AlterTable
+ALTER TABLE "parking_history" ADD COLUMN     "deleted_at" TIMESTAMP(3);
+
+-- CreateIndex
+-- Archived rows are the exception, reads only ever look at the live ones.
+CREATE INDEX "user_owner_id_live_idx" ON "user"("owner_id") WHERE "deleted_at" IS NULL;
+
+-- CreateIndex
+CREATE INDEX "parking_lot_owner_id_live_idx" ON "parking_lot"("owner_id") WHERE "deleted_at" IS NULL;
+
+-- DropIndex
+DROP INDEX "user_phone_number_key";
+
+-- CreateIndex
+-- An archived keeper frees their phone number for a new account.
+CREATE UNIQUE INDEX "user_phone_number_key" ON "user"("phone_number") WHERE "deleted_at" IS NULL;
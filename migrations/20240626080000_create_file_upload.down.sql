@@ -0,0 +1,6 @@
+-- DropTable
+DROP TABLE "parking_lot_image";
+DROP TABLE "file_upload";
+
+-- DropEnum
+DROP TYPE "storage_backend";
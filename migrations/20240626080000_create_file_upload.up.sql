@@ -0,0 +1,50 @@
+-- CreateEnum
+CREATE TYPE "storage_backend" AS ENUM ('local');
+
+-- CreateTable
+-- The public URL is not stored, it is derived from the backend and the key so
+-- moving the files only needs a config change.
+CREATE TABLE "file_upload" (
+    "id" UUID NOT NULL,
+    "storage" "storage_backend" NOT NULL DEFAULT 'local',
+    "storage_key" TEXT NOT NULL,
+    "original_name" TEXT NOT NULL,
+    "content_type" TEXT NOT NULL,
+    "size" BIGINT NOT NULL,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP
+);
+
+-- CreateTable
+CREATE TABLE "parking_lot_image" (
+    "id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "file_upload_id" UUID NOT NULL,
+    "position" INTEGER NOT NULL,
+    "is_cover" BOOLEAN NOT NULL DEFAULT false,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "file_upload_id_key" ON "file_upload"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_image_id_key" ON "parking_lot_image"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "file_upload_storage_key_key" ON "file_upload"("storage", "storage_key");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_image_parking_lot_id_file_upload_id_key" ON "parking_lot_image"("parking_lot_id", "file_upload_id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_image_parking_lot_id_cover_key" ON "parking_lot_image"("parking_lot_id") WHERE "is_cover";
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_image" ADD CONSTRAINT "parking_lot_image_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_image" ADD CONSTRAINT "parking_lot_image_file_upload_id_fkey" FOREIGN KEY ("file_upload_id") REFERENCES "file_upload"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- UpdateData
+-- Lots created before the gallery carry a placeholder instead of a URL.
+UPDATE "parking_lot" SET "image_url" = '' WHERE "image_url" = 'some url';
@@ -0,0 +1,40 @@
+-- CreateTable
+-- "user"."parking_lot_id" stays the current assignment, this table keeps every
+-- assignment a keeper ever had. An open row has no "ended_at".
+CREATE TABLE "keeper_assignment" (
+    "id" UUID NOT NULL,
+    "keeper_id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "owner_id" UUID NOT NULL,
+    "started_at" TIMESTAMP(3) NOT NULL,
+    "ended_at" TIMESTAMP(3),
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT "keeper_assignment_period_check" CHECK ("ended_at" IS NULL OR "ended_at" >= "started_at")
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "keeper_assignment_id_key" ON "keeper_assignment"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "keeper_assignment_keeper_id_open_key" ON "keeper_assignment"("keeper_id") WHERE "ended_at" IS NULL;
+
+-- CreateIndex
+CREATE INDEX "keeper_assignment_parking_lot_id_started_at_idx" ON "keeper_assignment"("parking_lot_id", "started_at");
+
+-- AddForeignKey
+ALTER TABLE "keeper_assignment" ADD CONSTRAINT "keeper_assignment_keeper_id_fkey" FOREIGN KEY ("keeper_id") REFERENCES "user"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "keeper_assignment" ADD CONSTRAINT "keeper_assignment_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "keeper_assignment" ADD CONSTRAINT "keeper_assignment_owner_id_fkey" FOREIGN KEY ("owner_id") REFERENCES "user"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- InsertData
+-- Keepers assigned before the history existed start from their last update.
+INSERT INTO "keeper_assignment" ("id", "keeper_id", "parking_lot_id", "owner_id", "started_at")
+SELECT gen_random_uuid(), u."id", u."parking_lot_id", pl."owner_id", COALESCE(u."updated_at", u."created_at", CURRENT_TIMESTAMP)
+FROM "user" u
+JOIN "parking_lot" pl ON pl."id" = u."parking_lot_id"
+WHERE u."role" = 'park_keeper';
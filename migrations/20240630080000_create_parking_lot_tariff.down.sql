@@ -0,0 +1,5 @@
+-- AlterTable
+ALTER TABLE "parking_history" DROP COLUMN "tariff_id";
+
+-- DropTable
+DROP TABLE "parking_lot_tariff";
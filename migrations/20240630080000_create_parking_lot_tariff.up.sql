@@ -0,0 +1,46 @@
+-- CreateTable
+-- Every price a lot ever had. The tariff in force at a time is the one with the
+-- latest "effective_from" not after it, "parking_lot"."car_cost" and
+-- "motor_cost" mirror the one in force now.
+CREATE TABLE "parking_lot_tariff" (
+    "id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "version" INTEGER NOT NULL,
+    "car_cost" DOUBLE PRECISION NOT NULL,
+    "motor_cost" DOUBLE PRECISION NOT NULL,
+    "effective_from" TIMESTAMP(3) NOT NULL,
+    "note" TEXT,
+    "created_by" UUID,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT "parking_lot_tariff_cost_check" CHECK ("car_cost" >= 0 AND "motor_cost" >= 0)
+);
+
+-- AlterTable
+ALTER TABLE "parking_history" ADD COLUMN     "tariff_id" UUID;
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_tariff_id_key" ON "parking_lot_tariff"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_tariff_parking_lot_id_version_key" ON "parking_lot_tariff"("parking_lot_id", "version");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_tariff_parking_lot_id_effective_from_key" ON "parking_lot_tariff"("parking_lot_id", "effective_from");
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_tariff" ADD CONSTRAINT "parking_lot_tariff_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_tariff" ADD CONSTRAINT "parking_lot_tariff_created_by_fkey" FOREIGN KEY ("created_by") REFERENCES "user"("id") ON DELETE SET NULL ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_history" ADD CONSTRAINT "parking_history_tariff_id_fkey" FOREIGN KEY ("tariff_id") REFERENCES "parking_lot_tariff"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- InsertData
+-- The prices a lot had before this migration are unknown, the current ones
+-- become a legacy first version. Existing tickets keep no tariff rather than
+-- pointing at prices they may not have been charged.
+INSERT INTO "parking_lot_tariff" ("id", "parking_lot_id", "version", "car_cost", "motor_cost", "effective_from", "note", "created_by")
+SELECT gen_random_uuid(), pl."id", 1, pl."car_cost", pl."motor_cost", COALESCE(pl."created_at", '1970-01-01'), 'Legacy tariff', pl."owner_id"
+FROM "parking_lot" pl;
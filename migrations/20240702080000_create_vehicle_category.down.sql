@@ -0,0 +1,14 @@
+-- AlterTable
+ALTER TABLE "vehicle" DROP COLUMN "vehicle_category_id";
+
+-- DropTable
+DROP TABLE "parking_lot_rate";
+
+-- DropTable
+DROP TABLE "parking_lot_vehicle_category";
+
+-- DropTable
+DROP TABLE "vehicle_category";
+
+-- AlterEnum
+-- Postgres cannot drop an enum value, "role" keeps 'admin'.
@@ -0,0 +1,106 @@
+-- AlterEnum
+-- Admins are added straight in the database, they manage what every lot shares
+-- such as the vehicle categories.
+ALTER TYPE "role" ADD VALUE 'admin';
+
+-- CreateTable
+-- Kinds of vehicle a lot can price. "vehicle_type" is the coarse class the
+-- reports group by, two-wheelers count as motor and everything else as car.
+CREATE TABLE "vehicle_category" (
+    "id" UUID NOT NULL,
+    "code" TEXT NOT NULL,
+    "name" TEXT NOT NULL,
+    "vehicle_type" "vehicle_type" NOT NULL,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP
+);
+
+-- CreateTable
+-- Categories a lot lets in.
+CREATE TABLE "parking_lot_vehicle_category" (
+    "parking_lot_id" UUID NOT NULL,
+    "vehicle_category_id" UUID NOT NULL,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP
+);
+
+-- CreateTable
+-- The price of one category under one tariff version of a lot.
+CREATE TABLE "parking_lot_rate" (
+    "id" UUID NOT NULL,
+    "tariff_id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "vehicle_category_id" UUID NOT NULL,
+    "cost" DOUBLE PRECISION NOT NULL,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT "parking_lot_rate_cost_check" CHECK ("cost" >= 0)
+);
+
+-- AlterTable
+ALTER TABLE "vehicle" ADD COLUMN     "vehicle_category_id" UUID;
+
+-- CreateIndex
+CREATE UNIQUE INDEX "vehicle_category_id_key" ON "vehicle_category"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_vehicle_category_parking_lot_id_vehicle_category_id_key" ON "parking_lot_vehicle_category"("parking_lot_id", "vehicle_category_id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_rate_id_key" ON "parking_lot_rate"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "vehicle_category_code_key" ON "vehicle_category"("code");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_lot_rate_tariff_id_vehicle_category_id_key" ON "parking_lot_rate"("tariff_id", "vehicle_category_id");
+
+-- CreateIndex
+CREATE INDEX "parking_lot_rate_parking_lot_id_idx" ON "parking_lot_rate"("parking_lot_id");
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_vehicle_category" ADD CONSTRAINT "parking_lot_vehicle_category_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_vehicle_category" ADD CONSTRAINT "parking_lot_vehicle_category_vehicle_category_id_fkey" FOREIGN KEY ("vehicle_category_id") REFERENCES "vehicle_category"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_rate" ADD CONSTRAINT "parking_lot_rate_tariff_id_fkey" FOREIGN KEY ("tariff_id") REFERENCES "parking_lot_tariff"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_rate" ADD CONSTRAINT "parking_lot_rate_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_lot_rate" ADD CONSTRAINT "parking_lot_rate_vehicle_category_id_fkey" FOREIGN KEY ("vehicle_category_id") REFERENCES "vehicle_category"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "vehicle" ADD CONSTRAINT "vehicle_vehicle_category_id_fkey" FOREIGN KEY ("vehicle_category_id") REFERENCES "vehicle_category"("id") ON DELETE SET NULL ON UPDATE CASCADE;
+
+-- InsertData
+INSERT INTO "vehicle_category" ("id", "code", "name", "vehicle_type") VALUES
+    (gen_random_uuid(), 'car', 'Car', 'car'),
+    (gen_random_uuid(), 'motor', 'Motorcycle', 'motor'),
+    (gen_random_uuid(), 'truck', 'Truck', 'car'),
+    (gen_random_uuid(), 'bus', 'Bus', 'car'),
+    (gen_random_uuid(), 'bicycle', 'Bicycle', 'motor'),
+    (gen_random_uuid(), 'ev', 'Electric car', 'car');
+
+-- UpdateData
+UPDATE "vehicle" v
+SET "vehicle_category_id" = c."id"
+FROM "vehicle_category" c
+WHERE c."code" = v."vehicle_type"::TEXT;
+
+-- InsertData
+-- Every lot keeps taking cars and motorcycles at the prices it has today.
+INSERT INTO "parking_lot_vehicle_category" ("parking_lot_id", "vehicle_category_id")
+SELECT pl."id", c."id"
+FROM "parking_lot" pl
+CROSS JOIN "vehicle_category" c
+WHERE c."code" IN ('car', 'motor');
+
+-- InsertData
+INSERT INTO "parking_lot_rate" ("id", "tariff_id", "parking_lot_id", "vehicle_category_id", "cost")
+SELECT gen_random_uuid(), t."id", t."parking_lot_id", c."id",
+    CASE c."code" WHEN 'car' THEN t."car_cost" ELSE t."motor_cost" END
+FROM "parking_lot_tariff" t
+CROSS JOIN "vehicle_category" c
+WHERE c."code" IN ('car', 'motor');
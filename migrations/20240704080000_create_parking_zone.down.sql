@@ -0,0 +1,14 @@
+-- DropTable
+DROP TABLE "disabled_permit";
+
+-- DropTable
+DROP TABLE "spot_allocation";
+
+-- DropTable
+DROP TABLE "parking_spot";
+
+-- DropTable
+DROP TABLE "parking_zone";
+
+-- DropEnum
+DROP TYPE "spot_kind";
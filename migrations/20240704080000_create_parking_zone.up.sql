@@ -0,0 +1,99 @@
+-- CreateEnum
+CREATE TYPE "spot_kind" AS ENUM ('regular', 'disabled', 'ev_charging');
+
+-- CreateTable
+-- An area of a lot, e.g. a floor of a mall or the visitor section of a
+-- hospital.
+CREATE TABLE "parking_zone" (
+    "id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "name" TEXT NOT NULL,
+    "floor" TEXT,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP
+);
+
+-- CreateTable
+CREATE TABLE "parking_spot" (
+    "id" UUID NOT NULL,
+    "zone_id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "number" TEXT NOT NULL,
+    "kind" "spot_kind" NOT NULL DEFAULT 'regular',
+    "is_active" BOOLEAN NOT NULL DEFAULT true,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP,
+    "updated_at" TIMESTAMP(3)
+);
+
+-- CreateTable
+-- A ticket holding a spot, the spot is free again once "released_at" is set.
+CREATE TABLE "spot_allocation" (
+    "id" UUID NOT NULL,
+    "spot_id" UUID NOT NULL,
+    "parking_history_id" UUID NOT NULL,
+    "allocated_at" TIMESTAMP(3) NOT NULL,
+    "released_at" TIMESTAMP(3)
+);
+
+-- CreateTable
+-- A disability parking permit an admin checked for a driver, reserved spots
+-- are only given to drivers with one that is still valid.
+CREATE TABLE "disabled_permit" (
+    "id" UUID NOT NULL,
+    "user_id" UUID NOT NULL,
+    "permit_number" TEXT NOT NULL,
+    "valid_until" TIMESTAMP(3) NOT NULL,
+    "verified_by" UUID,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "disabled_permit_id_key" ON "disabled_permit"("id");
+
+-- CreateIndex
+CREATE INDEX "disabled_permit_user_id_valid_until_idx" ON "disabled_permit"("user_id", "valid_until");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_zone_id_key" ON "parking_zone"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_spot_id_key" ON "parking_spot"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "spot_allocation_id_key" ON "spot_allocation"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_zone_parking_lot_id_name_key" ON "parking_zone"("parking_lot_id", "name");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_spot_zone_id_number_key" ON "parking_spot"("zone_id", "number");
+
+-- CreateIndex
+CREATE INDEX "parking_spot_parking_lot_id_idx" ON "parking_spot"("parking_lot_id");
+
+-- CreateIndex
+-- A spot holds one vehicle and a ticket holds one spot at a time.
+CREATE UNIQUE INDEX "spot_allocation_spot_id_open_key" ON "spot_allocation"("spot_id") WHERE "released_at" IS NULL;
+
+-- CreateIndex
+CREATE UNIQUE INDEX "spot_allocation_parking_history_id_open_key" ON "spot_allocation"("parking_history_id") WHERE "released_at" IS NULL;
+
+-- AddForeignKey
+ALTER TABLE "parking_zone" ADD CONSTRAINT "parking_zone_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_spot" ADD CONSTRAINT "parking_spot_zone_id_fkey" FOREIGN KEY ("zone_id") REFERENCES "parking_zone"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_spot" ADD CONSTRAINT "parking_spot_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "spot_allocation" ADD CONSTRAINT "spot_allocation_spot_id_fkey" FOREIGN KEY ("spot_id") REFERENCES "parking_spot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "spot_allocation" ADD CONSTRAINT "spot_allocation_parking_history_id_fkey" FOREIGN KEY ("parking_history_id") REFERENCES "parking_history"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "disabled_permit" ADD CONSTRAINT "disabled_permit_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "user"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "disabled_permit" ADD CONSTRAINT "disabled_permit_verified_by_fkey" FOREIGN KEY ("verified_by") REFERENCES "user"("id") ON DELETE SET NULL ON UPDATE CASCADE;
@@ -0,0 +1,11 @@
+-- DropTable
+DROP TABLE "parking_pass";
+
+-- DropTable
+DROP TABLE "parking_pass_plan";
+
+-- DropEnum
+DROP TYPE "pass_status";
+
+-- DropEnum
+DROP TYPE "pass_period";
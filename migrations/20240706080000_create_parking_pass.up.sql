@@ -0,0 +1,80 @@
+-- CreateEnum
+CREATE TYPE "pass_period" AS ENUM ('weekly', 'monthly');
+
+-- CreateEnum
+CREATE TYPE "pass_status" AS ENUM ('pending', 'active', 'cancelled');
+
+-- CreateTable
+-- What an owner sells for a lot. "discount_percent" of 100 makes tickets of
+-- the holder free.
+CREATE TABLE "parking_pass_plan" (
+    "id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "period" "pass_period" NOT NULL,
+    "price" DOUBLE PRECISION NOT NULL,
+    "discount_percent" DOUBLE PRECISION NOT NULL DEFAULT 100,
+    "is_active" BOOLEAN NOT NULL DEFAULT true,
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP,
+    "updated_at" TIMESTAMP(3),
+
+    CONSTRAINT "parking_pass_plan_price_check" CHECK ("price" >= 0),
+    CONSTRAINT "parking_pass_plan_discount_percent_check" CHECK ("discount_percent" > 0 AND "discount_percent" <= 100)
+);
+
+-- CreateTable
+-- A pass bought for one vehicle. Price and discount are copied from the plan,
+-- "starts_at" and "ends_at" are set once the payment settles.
+CREATE TABLE "parking_pass" (
+    "id" UUID NOT NULL,
+    "plan_id" UUID NOT NULL,
+    "parking_lot_id" UUID NOT NULL,
+    "vehicle_id" UUID NOT NULL,
+    "easypark_id" UUID NOT NULL,
+    "period" "pass_period" NOT NULL,
+    "price" DOUBLE PRECISION NOT NULL,
+    "discount_percent" DOUBLE PRECISION NOT NULL,
+    "status" "pass_status" NOT NULL DEFAULT 'pending',
+    "transaction_id" UUID NOT NULL,
+    "starts_at" TIMESTAMP(3),
+    "ends_at" TIMESTAMP(3),
+    "renewed_from_id" UUID,
+    "reminded_at" TIMESTAMP(3),
+    "created_at" TIMESTAMP(3) DEFAULT CURRENT_TIMESTAMP,
+    "updated_at" TIMESTAMP(3)
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_pass_plan_id_key" ON "parking_pass_plan"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_pass_id_key" ON "parking_pass"("id");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_pass_plan_parking_lot_id_period_active_key" ON "parking_pass_plan"("parking_lot_id", "period") WHERE "is_active";
+
+-- CreateIndex
+CREATE UNIQUE INDEX "parking_pass_transaction_id_key" ON "parking_pass"("transaction_id");
+
+-- CreateIndex
+CREATE INDEX "parking_pass_parking_lot_id_vehicle_id_idx" ON "parking_pass"("parking_lot_id", "vehicle_id");
+
+-- AddForeignKey
+ALTER TABLE "parking_pass_plan" ADD CONSTRAINT "parking_pass_plan_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_pass" ADD CONSTRAINT "parking_pass_plan_id_fkey" FOREIGN KEY ("plan_id") REFERENCES "parking_pass_plan"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_pass" ADD CONSTRAINT "parking_pass_parking_lot_id_fkey" FOREIGN KEY ("parking_lot_id") REFERENCES "parking_lot"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_pass" ADD CONSTRAINT "parking_pass_vehicle_id_fkey" FOREIGN KEY ("vehicle_id") REFERENCES "vehicle"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_pass" ADD CONSTRAINT "parking_pass_easypark_id_fkey" FOREIGN KEY ("easypark_id") REFERENCES "user"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_pass" ADD CONSTRAINT "parking_pass_transaction_id_fkey" FOREIGN KEY ("transaction_id") REFERENCES "transaction_history"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- AddForeignKey
+ALTER TABLE "parking_pass" ADD CONSTRAINT "parking_pass_renewed_from_id_fkey" FOREIGN KEY ("renewed_from_id") REFERENCES "parking_pass"("id") ON DELETE SET NULL ON UPDATE CASCADE;
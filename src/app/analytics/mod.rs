@@ -0,0 +1,235 @@
+pub mod router;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::{app::parking_history::VehicleType, error::aggregate::Result};
+
+/// The analytics refresh takes its own lock, a slow refresh never holds up the
+/// ticket work of the scheduler and two refreshes never rewrite the same hours.
+pub const ANALYTICS_LOCK_KEY: i64 = 0x6561_7379_7374_6174;
+
+/// Recomputes `parking_lot_hourly_stat` from the hour of `from` onwards, for one
+/// lot or for all of them. Earlier hours are left as they are, so the cost
+/// follows the window rather than the whole parking history. Readers keep
+/// seeing the previous rows until the caller commits, other refreshes wait on
+/// `ANALYTICS_LOCK_KEY` until then.
+pub async fn refresh_hourly_stat(
+    from: NaiveDateTime,
+    parking_lot_id: Option<Uuid>,
+    connection: &mut PgConnection,
+) -> Result<u64> {
+    sqlx::query!("select pg_advisory_xact_lock($1)", ANALYTICS_LOCK_KEY)
+        .execute(&mut *connection)
+        .await?;
+
+    sqlx::query!(
+        r#"
+            delete from parking_lot_hourly_stat
+            where hour >= date_trunc('hour', $1::timestamp) and
+                ($2::uuid is null or parking_lot_id = $2)
+        "#,
+        from,
+        parking_lot_id
+    )
+        .execute(&mut *connection)
+        .await?;
+
+    let refreshed = sqlx::query!(
+        r#"
+            insert into parking_lot_hourly_stat (parking_lot_id, vehicle_type, hour, occupied, arrivals, departures)
+            select ph.parking_lot_id,
+                ph.vehicle_type,
+                h.hour,
+                count(*),
+                count(*) filter (where date_trunc('hour', ph.check_in_date) = h.hour),
+                count(*) filter (where date_trunc('hour', ph.check_out_date) = h.hour)
+            from parking_history ph
+            cross join lateral generate_series(
+                greatest(date_trunc('hour', ph.check_in_date), date_trunc('hour', $1::timestamp)),
+                date_trunc('hour', coalesce(ph.check_out_date, now() at time zone 'UTC')),
+                interval '1 hour'
+            ) as h(hour)
+            where ph.check_in_date is not null and
+                ph.ticket_status in ('active', 'not_active') and
+                ph.deleted_at is null and
+                (ph.check_out_date is null or ph.check_out_date >= date_trunc('hour', $1::timestamp)) and
+                ($2::uuid is null or ph.parking_lot_id = $2)
+            group by ph.parking_lot_id, ph.vehicle_type, h.hour
+        "#,
+        from,
+        parking_lot_id
+    )
+        .execute(&mut *connection)
+        .await?;
+
+    Ok(refreshed.rows_affected())
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalyticsQuery {
+    pub owner_id: Uuid,
+    pub parking_lot_id: Option<Uuid>,
+    pub vehicle_type: Option<VehicleType>,
+    pub created_at_start_filter: NaiveDateTime,
+    pub created_at_end_filter: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DwellTime {
+    pub parking_lot_id: Uuid,
+    pub area_name: String,
+    pub vehicle_type: VehicleType,
+    pub total_stay: Option<i64>,
+    pub average_minutes: Option<f64>,
+    pub median_minutes: Option<f64>,
+}
+
+impl DwellTime {
+    /// Computed from the tickets themselves, the check-out index keeps it to the
+    /// requested range of the owner's lots.
+    pub async fn by_owner(query: &AnalyticsQuery, executor: impl PgExecutor<'_>) -> Result<Vec<DwellTime>> {
+        let data = sqlx::query_as!(
+            DwellTime,
+            r#"
+                with stay as (
+                    select ph.parking_lot_id,
+                        ph.vehicle_type,
+                        cast(extract(epoch from (ph.check_out_date - ph.check_in_date)) / 60 as double precision) as minutes
+                    from parking_history ph
+                    join parking_lot pl on pl.id = ph.parking_lot_id
+                    where pl.owner_id = $1 and
+                        ($2::uuid is null or ph.parking_lot_id = $2) and
+                        ($3 = 'default' or ph.vehicle_type = $3::vehicle_type) and
+                        ph.ticket_status = 'not_active' and
+                        ph.deleted_at is null and
+                        ph.check_in_date is not null and
+                        ph.check_out_date >= $4 and
+                        ph.check_out_date <= $5
+                )
+                select s.parking_lot_id,
+                    pl.area_name,
+                    s.vehicle_type as "vehicle_type!: VehicleType",
+                    count(*) as total_stay,
+                    avg(s.minutes) as average_minutes,
+                    percentile_cont(0.5) within group (order by s.minutes) as median_minutes
+                from stay s
+                join parking_lot pl on pl.id = s.parking_lot_id
+                group by s.parking_lot_id, pl.area_name, s.vehicle_type
+                order by pl.area_name, s.vehicle_type
+            "#,
+            query.owner_id,
+            query.parking_lot_id,
+            query.vehicle_type.clone().unwrap_or(VehicleType::Default) as VehicleType,
+            query.created_at_start_filter,
+            query.created_at_end_filter
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OccupancyHeatmap {
+    pub day_of_week: Option<i32>,
+    pub hour_of_day: Option<i32>,
+    pub average_occupancy: Option<f64>,
+    pub peak_occupancy: Option<i64>,
+}
+
+impl OccupancyHeatmap {
+    /// Hour-of-week grid in `timezone`, day 1 is Monday. Hours without any parked
+    /// vehicle count as zero so the averages are over every hour in the range.
+    pub async fn by_owner(query: &AnalyticsQuery, timezone: &str, executor: impl PgExecutor<'_>) -> Result<Vec<OccupancyHeatmap>> {
+        let data = sqlx::query_as!(
+            OccupancyHeatmap,
+            r#"
+                with slot as (
+                    select h as hour,
+                        cast(extract(isodow from (h at time zone 'UTC') at time zone $6) as integer) as day_of_week,
+                        cast(extract(hour from (h at time zone 'UTC') at time zone $6) as integer) as hour_of_day
+                    from generate_series(date_trunc('hour', $4::timestamp), $5::timestamp, interval '1 hour') h
+                ),
+                occupancy as (
+                    select s.hour, sum(s.occupied) as occupied
+                    from parking_lot_hourly_stat s
+                    join parking_lot pl on pl.id = s.parking_lot_id
+                    where pl.owner_id = $1 and
+                        pl.deleted_at is null and
+                        ($2::uuid is null or s.parking_lot_id = $2) and
+                        ($3 = 'default' or s.vehicle_type = $3::vehicle_type) and
+                        s.hour >= date_trunc('hour', $4::timestamp) and
+                        s.hour <= $5
+                    group by s.hour
+                )
+                select sl.day_of_week,
+                    sl.hour_of_day,
+                    cast(avg(coalesce(o.occupied, 0)) as double precision) as average_occupancy,
+                    cast(max(coalesce(o.occupied, 0)) as bigint) as peak_occupancy
+                from slot sl
+                left join occupancy o on o.hour = sl.hour
+                group by sl.day_of_week, sl.hour_of_day
+                order by sl.day_of_week, sl.hour_of_day
+            "#,
+            query.owner_id,
+            query.parking_lot_id,
+            query.vehicle_type.clone().unwrap_or(VehicleType::Default) as VehicleType,
+            query.created_at_start_filter,
+            query.created_at_end_filter,
+            timezone
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LotOccupancy {
+    pub parking_lot_id: Uuid,
+    pub area_name: String,
+    pub vehicle_type: VehicleType,
+    pub total_stay: Option<i64>,
+    pub peak_occupancy: Option<i64>,
+    pub peak_hour: Option<NaiveDateTime>,
+}
+
+impl LotOccupancy {
+    pub async fn by_owner(query: &AnalyticsQuery, executor: impl PgExecutor<'_>) -> Result<Vec<LotOccupancy>> {
+        let data = sqlx::query_as!(
+            LotOccupancy,
+            r#"
+                select s.parking_lot_id as "parking_lot_id!",
+                    pl.area_name,
+                    s.vehicle_type as "vehicle_type!: VehicleType",
+                    cast(sum(s.departures) as bigint) as total_stay,
+                    max(s.occupied) as peak_occupancy,
+                    (array_agg(s.hour order by s.occupied desc, s.hour))[1] as peak_hour
+                from parking_lot_hourly_stat s
+                join parking_lot pl on pl.id = s.parking_lot_id
+                where pl.owner_id = $1 and
+                    pl.deleted_at is null and
+                    ($2::uuid is null or s.parking_lot_id = $2) and
+                    ($3 = 'default' or s.vehicle_type = $3::vehicle_type) and
+                    s.hour >= date_trunc('hour', $4::timestamp) and
+                    s.hour <= $5
+                group by s.parking_lot_id, pl.area_name, s.vehicle_type
+                order by pl.area_name, s.vehicle_type
+            "#,
+            query.owner_id,
+            query.parking_lot_id,
+            query.vehicle_type.clone().unwrap_or(VehicleType::Default) as VehicleType,
+            query.created_at_start_filter,
+            query.created_at_end_filter
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+}
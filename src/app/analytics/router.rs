@@ -0,0 +1,150 @@
+use axum::{
+    extract::{Query, State},
+    middleware,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        parking_history::VehicleType,
+        report::{validate_timezone, DEFAULT_TIMEZONE},
+    },
+    error::aggregate::{Error, Result},
+    extractor::app_json::AppSuccess,
+    middleware::base::print_request_body,
+};
+
+use super::{AnalyticsQuery, DwellTime, LotOccupancy, OccupancyHeatmap};
+
+pub fn build(pool: Pool<Postgres>) -> Router {
+    let router = Router::new()
+        .route("/dwell-time", get(dwell_time))
+        .route("/occupancy-heatmap", get(occupancy_heatmap))
+        .route("/turnover", get(turnover))
+        .layer(middleware::from_fn(print_request_body))
+        .with_state(pool);
+
+    Router::new().nest("/analytics", router)
+}
+
+#[derive(Deserialize, Clone, Serialize)]
+pub struct AnalyticsPayload {
+    pub owner_id: Uuid,
+    pub parking_lot_id: Option<Uuid>,
+    pub vehicle_type: Option<VehicleType>,
+    pub created_at_start_filter: DateTime<Utc>,
+    pub created_at_end_filter: DateTime<Utc>,
+    pub timezone: Option<String>,
+}
+
+impl AnalyticsPayload {
+    fn into_analytics_query(self) -> Result<AnalyticsQuery> {
+        if self.created_at_start_filter > self.created_at_end_filter {
+            return Err(Error::BadRequest(String::from(
+                "created_at_start_filter must be before created_at_end_filter",
+            )));
+        }
+        if self.created_at_end_filter - self.created_at_start_filter > chrono::Duration::days(366) {
+            return Err(Error::BadRequest(String::from(
+                "Date range must not be longer than 366 days",
+            )));
+        }
+
+        Ok(AnalyticsQuery {
+            owner_id: self.owner_id,
+            parking_lot_id: self.parking_lot_id,
+            vehicle_type: self.vehicle_type,
+            created_at_start_filter: self.created_at_start_filter.naive_utc(),
+            created_at_end_filter: self.created_at_end_filter.naive_utc(),
+        })
+    }
+}
+
+async fn dwell_time(
+    State(pool): State<PgPool>,
+    Query(payload): Query<AnalyticsPayload>,
+) -> Result<AppSuccess<Vec<DwellTime>>> {
+    let query = payload.into_analytics_query()?;
+    let dwell_time = DwellTime::by_owner(&query, &pool).await?;
+    Ok(AppSuccess(dwell_time))
+}
+
+#[derive(Serialize)]
+struct Heatmap {
+    timezone: String,
+    heatmap: Vec<OccupancyHeatmap>,
+}
+
+async fn occupancy_heatmap(
+    State(pool): State<PgPool>,
+    Query(payload): Query<AnalyticsPayload>,
+) -> Result<AppSuccess<Heatmap>> {
+    let timezone = payload
+        .timezone
+        .clone()
+        .unwrap_or_else(|| String::from(DEFAULT_TIMEZONE));
+    validate_timezone(&timezone, &pool).await?;
+
+    let query = payload.into_analytics_query()?;
+    let heatmap = OccupancyHeatmap::by_owner(&query, &timezone, &pool).await?;
+
+    Ok(AppSuccess(Heatmap { timezone, heatmap }))
+}
+
+#[derive(Serialize)]
+struct Turnover {
+    parking_lot_id: Uuid,
+    area_name: String,
+    vehicle_type: VehicleType,
+    total_stay: i64,
+    peak_occupancy: i64,
+    peak_hour: Option<NaiveDateTime>,
+    stay_per_day: f64,
+    turnover_rate: f64,
+}
+
+async fn turnover(
+    State(pool): State<PgPool>,
+    Query(payload): Query<AnalyticsPayload>,
+) -> Result<AppSuccess<Vec<Turnover>>> {
+    let query = payload.into_analytics_query()?;
+    let days = ((query.created_at_end_filter - query.created_at_start_filter).num_minutes() as f64
+        / (24.0 * 60.0))
+        .max(1.0 / 24.0);
+
+    // Lots do not record a capacity, the busiest hour of the range stands in for
+    // it: a turnover rate of 2 means every space in use at peak was used by two
+    // vehicles per day on average.
+    let turnover = LotOccupancy::by_owner(&query, &pool)
+        .await?
+        .into_iter()
+        .map(|occupancy| {
+            let total_stay = occupancy.total_stay.unwrap_or(0);
+            let peak_occupancy = occupancy.peak_occupancy.unwrap_or(0);
+            let stay_per_day = total_stay as f64 / days;
+            let turnover_rate = if peak_occupancy > 0 {
+                stay_per_day / peak_occupancy as f64
+            } else {
+                0.0
+            };
+
+            Turnover {
+                parking_lot_id: occupancy.parking_lot_id,
+                area_name: occupancy.area_name,
+                vehicle_type: occupancy.vehicle_type,
+                total_stay,
+                peak_occupancy,
+                peak_hour: occupancy.peak_hour,
+                stay_per_day,
+                turnover_rate,
+            }
+        })
+        .collect();
+
+    Ok(AppSuccess(turnover))
+}
@@ -4,6 +4,7 @@ use crate::{
     extractor::{app_body::Body, app_json::AppSuccess, current_user::CurrentUser},
     jwt::config::KEYS,
     middleware::base::print_request_body,
+    unit_of_work::UnitOfWork,
 };
 use axum::{extract::State, middleware, routing::post, Router};
 use chrono::{DateTime, Datelike, Duration, Utc};
@@ -41,7 +42,7 @@ impl RegisterPayload {
             role: self.role,
             status: UserStatus::NotActive,
             otp: None,
-            created_at: None,
+            created_at: Some(Utc::now().naive_utc()),
             updated_at: None,
             parking_lot_id: None,
             owner_id: None
@@ -137,8 +138,10 @@ async fn activate_phone_number(
         return Err(Error::BadRequest("OTP not valid".to_string()));
     }
 
-    let user = user.update_status(UserStatus::Active, &pool).await?;
-    let user = user.invalidate_otp(&pool).await?;
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let user = user.update_status(UserStatus::Active, uow.connection()).await?;
+    let user = user.invalidate_otp(uow.connection()).await?;
+    uow.commit().await?;
 
     let now = Utc::now();
     let exp = now
@@ -0,0 +1,202 @@
+pub mod router;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::error::aggregate::Result;
+
+/// Local midnight of the first day of this month in `timezone`, expressed in
+/// UTC like every timestamp we store. `today` is local midnight of today in
+/// local time, the key of today's bucket.
+#[derive(Debug)]
+pub struct DashboardPeriod {
+    pub now: NaiveDateTime,
+    pub today: NaiveDateTime,
+    pub month_start: NaiveDateTime,
+}
+
+impl DashboardPeriod {
+    pub async fn current(timezone: &str, executor: impl PgExecutor<'_>) -> Result<DashboardPeriod> {
+        let period = sqlx::query_as!(
+            DashboardPeriod,
+            r#"
+                select (now() at time zone 'UTC') as "now!",
+                    date_trunc('day', now() at time zone $1) as "today!",
+                    (date_trunc('month', now() at time zone $1) at time zone $1) at time zone 'UTC' as "month_start!"
+            "#,
+            timezone
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(period)
+    }
+}
+
+/// Money a lot took in one local day: what the gateway settled that day plus
+/// cash taken at that day's check-outs. Today's figures and the month trend of
+/// the dashboard both come from these rows.
+#[derive(Debug)]
+pub struct LotRevenue {
+    pub parking_lot_id: Uuid,
+    pub day: NaiveDateTime,
+    pub revenue: f64,
+    pub paid_ticket: i64,
+}
+
+impl LotRevenue {
+    pub async fn by_owner(owner_id: Uuid, from: NaiveDateTime, until: NaiveDateTime, timezone: &str, executor: impl PgExecutor<'_>) -> Result<Vec<LotRevenue>> {
+        let data = sqlx::query_as!(
+            LotRevenue,
+            r#"
+                select paid.parking_lot_id as "parking_lot_id!",
+                    date_trunc('day', (paid.paid_at at time zone 'UTC') at time zone $4) as "day!",
+                    coalesce(sum(paid.amount), 0) as "revenue!",
+                    count(*) as "paid_ticket!"
+                from (
+                    -- Midtrans reports settlement_time as GMT+7 text.
+                    select ph.parking_lot_id,
+                        cast(th.gross_amount as double precision) as amount,
+                        (th.settlement_time::timestamp at time zone 'Asia/Jakarta') at time zone 'UTC' as paid_at
+                    from parking_history ph
+                    join transaction_history th on th.id = ph.transaction_id
+                    where ph.owner_id = $1 and
+                        ph.payment <> 'cash' and
+                        ph.deleted_at is null and
+                        th.transaction_status in ('settlement', 'capture') and
+                        th.settlement_time ~ '^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}$' and
+                        (th.settlement_time::timestamp at time zone 'Asia/Jakarta') at time zone 'UTC' >= $2 and
+                        (th.settlement_time::timestamp at time zone 'Asia/Jakarta') at time zone 'UTC' <= $3
+                    union all
+                    select ph.parking_lot_id,
+                        CEIL(EXTRACT(EPOCH FROM (ph.check_out_date - coalesce(ph.check_in_date, ph.created_at))) / 3600) * ph.amount + ph.penalty_amount,
+                        ph.check_out_date
+                    from parking_history ph
+                    where ph.owner_id = $1 and
+                        ph.payment = 'cash' and
+                        ph.ticket_status = 'not_active' and
+                        ph.deleted_at is null and
+                        ph.check_out_date >= $2 and
+                        ph.check_out_date <= $3
+                ) paid
+                join parking_lot pl on pl.id = paid.parking_lot_id
+                where pl.deleted_at is null
+                group by 1, 2
+                order by 2, 1
+            "#,
+            owner_id,
+            from,
+            until,
+            timezone
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+}
+
+/// Everything the owner app shows for one lot besides its revenue. Occupancy
+/// is measured against the busiest hour of the last 30 days since lots do not
+/// record a capacity.
+#[derive(Debug, Serialize)]
+pub struct LotSummary {
+    pub parking_lot_id: Uuid,
+    pub area_name: String,
+    pub active_ticket: Option<i64>,
+    pub active_car: Option<i64>,
+    pub active_motor: Option<i64>,
+    pub peak_occupancy: Option<i64>,
+    pub assigned_keeper: Option<i64>,
+    pub active_keeper: Option<i64>,
+    pub pending_payment: Option<i64>,
+    pub pending_amount: Option<f64>,
+}
+
+impl LotSummary {
+    /// Each figure is aggregated once per lot in its own CTE and joined back, so
+    /// the cost does not grow with the number of tickets times keepers.
+    pub async fn by_owner(owner_id: Uuid, period: &DashboardPeriod, executor: impl PgExecutor<'_>) -> Result<Vec<LotSummary>> {
+        let data = sqlx::query_as!(
+            LotSummary,
+            r#"
+                with active as (
+                    select ph.parking_lot_id,
+                        count(*) as active_ticket,
+                        count(*) filter (where ph.vehicle_type = 'car') as active_car,
+                        count(*) filter (where ph.vehicle_type = 'motor') as active_motor
+                    from parking_history ph
+                    where ph.owner_id = $1 and ph.ticket_status in ('active', 'default') and ph.deleted_at is null
+                    group by ph.parking_lot_id
+                ),
+                pending as (
+                    select ph.parking_lot_id,
+                        count(*) as pending_payment,
+                        sum(
+                            CEIL(EXTRACT(EPOCH FROM (coalesce(ph.check_out_date, NOW() at time zone 'UTC') - coalesce(ph.check_in_date, ph.created_at))) / 3600) * ph.amount + ph.penalty_amount
+                        ) as pending_amount
+                    from parking_history ph
+                    join transaction_history th on th.id = ph.transaction_id
+                    where ph.owner_id = $1 and
+                        ph.payment = 'qr' and
+                        ph.ticket_status <> 'expired' and
+                        ph.deleted_at is null and
+                        coalesce(th.transaction_status, 'pending') = 'pending'
+                    group by ph.parking_lot_id
+                ),
+                peak as (
+                    select s.parking_lot_id, max(s.occupied) as peak_occupancy
+                    from (
+                        select parking_lot_id, hour, sum(occupied) as occupied
+                        from parking_lot_hourly_stat
+                        where hour >= $2::timestamp - interval '30 days'
+                        group by parking_lot_id, hour
+                    ) s
+                    group by s.parking_lot_id
+                ),
+                keeper as (
+                    select u.parking_lot_id,
+                        count(*) as assigned_keeper,
+                        count(*) filter (where ks.id is not null) as active_keeper
+                    from "user" u
+                    left join keeper_shift ks on ks.keeper_id = u.id and ks.shift_status = 'open'
+                    where u.owner_id = $1 and u.role = 'park_keeper' and u.deleted_at is null
+                    group by u.parking_lot_id
+                )
+                select pl.id as parking_lot_id,
+                    pl.area_name,
+                    coalesce(a.active_ticket, 0) as active_ticket,
+                    coalesce(a.active_car, 0) as active_car,
+                    coalesce(a.active_motor, 0) as active_motor,
+                    cast(p.peak_occupancy as bigint) as peak_occupancy,
+                    coalesce(k.assigned_keeper, 0) as assigned_keeper,
+                    coalesce(k.active_keeper, 0) as active_keeper,
+                    coalesce(pd.pending_payment, 0) as pending_payment,
+                    coalesce(pd.pending_amount, 0) as pending_amount
+                from parking_lot pl
+                left join active a on a.parking_lot_id = pl.id
+                left join pending pd on pd.parking_lot_id = pl.id
+                left join peak p on p.parking_lot_id = pl.id
+                left join keeper k on k.parking_lot_id = pl.id
+                where pl.owner_id = $1 and pl.deleted_at is null
+                order by pl.area_name
+            "#,
+            owner_id,
+            period.now
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+
+    pub fn occupancy_rate(&self) -> Option<f64> {
+        occupancy_rate(self.active_ticket.unwrap_or(0), self.peak_occupancy.unwrap_or(0))
+    }
+}
+
+pub fn occupancy_rate(active_ticket: i64, peak_occupancy: i64) -> Option<f64> {
+    (peak_occupancy > 0).then(|| active_ticket as f64 / peak_occupancy as f64)
+}
@@ -0,0 +1,228 @@
+use axum::{
+    extract::{Query, State},
+    middleware,
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        report::{validate_timezone, Granularity, TimeSeriesBucket, TimeSeriesQuery, DEFAULT_TIMEZONE},
+        user::{Role, User},
+    },
+    error::aggregate::{Error, Result},
+    extractor::app_json::AppSuccess,
+    middleware::base::print_request_body,
+};
+
+use super::{occupancy_rate, DashboardPeriod, LotRevenue, LotSummary};
+
+pub fn build(pool: Pool<Postgres>) -> Router {
+    let router = Router::new()
+        .route("/owner", get(owner))
+        .layer(middleware::from_fn(print_request_body))
+        .with_state(pool);
+
+    Router::new().nest("/dashboard", router)
+}
+
+#[derive(Deserialize)]
+struct OwnerDashboardPayload {
+    owner_id: Uuid,
+    timezone: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LotDashboard {
+    #[serde(flatten)]
+    summary: LotSummary,
+    today_revenue: f64,
+    today_ticket: i64,
+    occupancy_rate: Option<f64>,
+}
+
+#[derive(Serialize, Default)]
+struct DashboardTotal {
+    today_revenue: f64,
+    today_ticket: i64,
+    active_ticket: i64,
+    active_car: i64,
+    active_motor: i64,
+    peak_occupancy: i64,
+    occupancy_rate: Option<f64>,
+    assigned_keeper: i64,
+    active_keeper: i64,
+    pending_payment: i64,
+    pending_amount: f64,
+}
+
+#[derive(Serialize)]
+struct OwnerDashboard {
+    timezone: String,
+    total: DashboardTotal,
+    parking_lot: Vec<LotDashboard>,
+    month_to_date: Vec<TimeSeriesBucket>,
+}
+
+async fn owner(
+    State(pool): State<PgPool>,
+    Query(payload): Query<OwnerDashboardPayload>,
+) -> Result<AppSuccess<OwnerDashboard>> {
+    let owner = User::find_one_by_id(payload.owner_id, &pool).await?;
+    if owner.role != Role::ParkOwner {
+        return Err(Error::BadRequest(
+            "Provided owner id is not having ParkOwner role".to_string(),
+        ));
+    }
+
+    let timezone = payload
+        .timezone
+        .unwrap_or_else(|| String::from(DEFAULT_TIMEZONE));
+    validate_timezone(&timezone, &pool).await?;
+
+    let period = DashboardPeriod::current(&timezone, &pool).await?;
+    let summary = LotSummary::by_owner(owner.id, &period, &pool).await?;
+    let revenue =
+        LotRevenue::by_owner(owner.id, period.month_start, period.now, &timezone, &pool).await?;
+    let mut month_to_date = TimeSeriesBucket::by_owner(
+        &TimeSeriesQuery {
+            owner_id: owner.id,
+            parking_lot_id: None,
+            created_at_start_filter: period.month_start,
+            created_at_end_filter: period.now,
+            granularity: Granularity::Day,
+            timezone: timezone.clone(),
+        },
+        &pool,
+    )
+    .await?;
+
+    // Today's revenue and the trend are read from the same rows, so today's
+    // bucket always shows the figure next to it on the screen.
+    for bucket in month_to_date.iter_mut() {
+        bucket.revenue = Some(
+            revenue
+                .iter()
+                .filter(|row| Some(row.day) == bucket.bucket)
+                .map(|row| row.revenue)
+                .sum(),
+        );
+    }
+
+    let parking_lot: Vec<LotDashboard> = summary
+        .into_iter()
+        .map(|summary| {
+            let today = revenue
+                .iter()
+                .filter(|row| row.parking_lot_id == summary.parking_lot_id && row.day == period.today);
+            LotDashboard {
+                today_revenue: today.clone().map(|row| row.revenue).sum(),
+                today_ticket: today.map(|row| row.paid_ticket).sum(),
+                occupancy_rate: summary.occupancy_rate(),
+                summary,
+            }
+        })
+        .collect();
+
+    let mut total = parking_lot.iter().fold(DashboardTotal::default(), |mut total, lot| {
+        total.today_revenue += lot.today_revenue;
+        total.today_ticket += lot.today_ticket;
+        let lot = &lot.summary;
+        total.active_ticket += lot.active_ticket.unwrap_or(0);
+        total.active_car += lot.active_car.unwrap_or(0);
+        total.active_motor += lot.active_motor.unwrap_or(0);
+        total.peak_occupancy += lot.peak_occupancy.unwrap_or(0);
+        total.assigned_keeper += lot.assigned_keeper.unwrap_or(0);
+        total.active_keeper += lot.active_keeper.unwrap_or(0);
+        total.pending_payment += lot.pending_payment.unwrap_or(0);
+        total.pending_amount += lot.pending_amount.unwrap_or(0.0);
+        total
+    });
+    total.occupancy_rate = occupancy_rate(total.active_ticket, total.peak_occupancy);
+
+    Ok(AppSuccess(OwnerDashboard {
+        timezone,
+        total,
+        parking_lot,
+        month_to_date,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body as AxumBody},
+        http::{Request, StatusCode},
+    };
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use super::build;
+    use crate::app::fixture::{seed, EASYPARK_ID, KEEPER_ID, OWNER_ID, PARKING_LOT_ID};
+
+    /// A car ticket booked yesterday and checked out a moment ago after one
+    /// hour at 5000, paid in cash or through a gateway order settled just now.
+    async fn paid_ticket(payment: &str, transaction_status: Option<&str>, gross_amount: Option<&str>, pool: &PgPool) {
+        let transaction_id = Uuid::new_v4();
+        sqlx::query(
+            r#"
+                insert into transaction_history (id, transaction_status, gross_amount, settlement_time)
+                values ($1, $2, $3, to_char(now() at time zone 'Asia/Jakarta', 'YYYY-MM-DD HH24:MI:SS'))
+            "#,
+        )
+        .bind(transaction_id)
+        .bind(transaction_status)
+        .bind(gross_amount)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        sqlx::query(
+            r#"
+                insert into parking_history (id, ticket_status, vehicle_type, payment, amount, parking_lot_id,
+                    easypark_id, keeper_id, owner_id, transaction_id, created_at, check_in_date, check_out_date)
+                values ($1, 'not_active', 'car', $2::payment_type, 5000, $3, $4, $5, $6, $1,
+                    now() at time zone 'UTC' - interval '1 day',
+                    now() at time zone 'UTC' - interval '1 minute',
+                    now() at time zone 'UTC' - interval '1 second')
+            "#,
+        )
+        .bind(transaction_id)
+        .bind(payment)
+        .bind(PARKING_LOT_ID)
+        .bind(EASYPARK_ID)
+        .bind(KEEPER_ID)
+        .bind(OWNER_ID)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn todays_bucket_matches_todays_revenue(pool: PgPool) {
+        seed(&pool).await;
+        paid_ticket("cash", None, None, &pool).await;
+        paid_ticket("qr", Some("settlement"), Some("7000.00"), &pool).await;
+        paid_ticket("qr", Some("pending"), Some("9000.00"), &pool).await;
+
+        let uri = format!("/dashboard/owner?owner_id={}&timezone=UTC", OWNER_ID);
+        let response = build(pool.clone())
+            .oneshot(Request::get(uri).body(AxumBody::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let dashboard: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let dashboard = &dashboard["data"];
+        assert_eq!(dashboard["total"]["today_revenue"], 12000.0);
+        assert_eq!(dashboard["parking_lot"][0]["today_ticket"], 2);
+
+        let today = dashboard["month_to_date"].as_array().unwrap().last().unwrap();
+        assert_eq!(today["revenue"], dashboard["total"]["today_revenue"]);
+    }
+}
@@ -1 +1,159 @@
-pub mod router;
\ No newline at end of file
+pub mod router;
+
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::error::aggregate::{Error, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, sqlx::Type, Deserialize, Serialize)]
+#[sqlx(type_name = "storage_backend", rename_all = "snake_case")]
+pub enum StorageBackend {
+    Local,
+}
+
+/// Where uploaded files are written and the base URL clients read them from.
+/// Local files are served by the `/asset` route unless `ASSET_BASE_URL` points
+/// somewhere else, e.g. a CDN in front of the same directory.
+pub struct Storage {
+    pub backend: StorageBackend,
+    pub root: PathBuf,
+    pub base_url: String,
+}
+
+pub static STORAGE: Lazy<Storage> = Lazy::new(|| Storage {
+    backend: StorageBackend::Local,
+    root: PathBuf::from(
+        std::env::var("FILE_STORAGE_DIR").unwrap_or_else(|_| String::from("./public/files")),
+    ),
+    base_url: std::env::var("ASSET_BASE_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| String::from("/asset")),
+});
+
+impl Storage {
+    pub async fn put(&self, storage_key: &str, data: &[u8]) -> Result<()> {
+        match self.backend {
+            StorageBackend::Local => {
+                tokio::fs::create_dir_all(&self.root).await?;
+                tokio::fs::write(self.root.join(storage_key), data).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete(&self, storage_key: &str) -> Result<()> {
+        match self.backend {
+            StorageBackend::Local => tokio::fs::remove_file(self.root.join(storage_key)).await?,
+        }
+
+        Ok(())
+    }
+
+    pub fn url(&self, storage: StorageBackend, storage_key: &str) -> String {
+        match storage {
+            StorageBackend::Local => format!("{}/{}", self.base_url, storage_key),
+        }
+    }
+}
+
+/// A fresh key for an uploaded file. Only the extension of the client's file
+/// name is kept so the name cannot reach outside the storage directory.
+pub fn storage_key(original_name: &str) -> String {
+    let extension = Path::new(original_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| extension.chars().all(|char| char.is_ascii_alphanumeric()))
+        .map(|extension| extension.to_ascii_lowercase());
+
+    match extension {
+        Some(extension) => format!("{}.{}", Uuid::new_v4(), extension),
+        None => Uuid::new_v4().to_string(),
+    }
+}
+
+/// Image types a lot photo can have. The type is read from the first bytes of
+/// the file, whatever the client claims.
+pub fn sniff_image(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileUpload {
+    pub id: Uuid,
+    pub storage: StorageBackend,
+    pub storage_key: String,
+    pub original_name: String,
+    pub content_type: String,
+    pub size: i64,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl FileUpload {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<FileUpload> {
+        let file = sqlx::query_as!(
+            FileUpload,
+            r#"
+                insert into "file_upload" (id, storage, storage_key, original_name, content_type, size, created_at)
+                values ($1, $2, $3, $4, $5, $6, $7)
+                returning id, storage as "storage!: StorageBackend", storage_key, original_name, content_type, size, created_at
+            "#,
+            self.id,
+            self.storage as StorageBackend,
+            self.storage_key,
+            self.original_name,
+            self.content_type,
+            self.size,
+            self.created_at
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(file)
+    }
+
+    /// Returned in the order of `ids`, every id has to exist.
+    pub async fn find_many(ids: &[Uuid], executor: impl PgExecutor<'_>) -> Result<Vec<FileUpload>> {
+        let file = sqlx::query_as!(
+            FileUpload,
+            r#"
+                select f.id, f.storage as "storage!: StorageBackend", f.storage_key, f.original_name, f.content_type, f.size, f.created_at
+                from unnest($1::uuid[]) with ordinality as requested(id, position)
+                join "file_upload" f on f.id = requested.id
+                order by requested.position
+            "#,
+            ids
+        )
+            .fetch_all(executor)
+            .await?;
+
+        if file.len() != ids.len() {
+            return Err(Error::NotFoundRejection("File is not found".to_string()));
+        }
+
+        Ok(file)
+    }
+
+    pub fn url(&self) -> String {
+        STORAGE.url(self.storage, &self.storage_key)
+    }
+
+    /// Uploads store the sniffed type of an image, so only files that really
+    /// are png, jpeg or webp pass.
+    pub fn is_image(&self) -> bool {
+        matches!(self.content_type.as_str(), "image/png" | "image/jpeg" | "image/webp")
+    }
+}
@@ -1,49 +1,101 @@
-use std::fs;
-
-use axum::{extract::Multipart, routing::post, Router};
+use axum::{
+    extract::{Multipart, State},
+    routing::post,
+    Router,
+};
+use chrono::Utc;
 use serde::Serialize;
+use sqlx::{PgPool, Pool, Postgres};
+use tracing::error;
 use uuid::Uuid;
 
-use crate::{error::aggregate::Result, extractor::app_json::AppSuccess};
+use crate::{
+    error::aggregate::{Error, Result},
+    extractor::app_json::AppSuccess,
+};
+
+use super::{sniff_image, storage_key, FileUpload, STORAGE};
 
-pub fn build() -> Router {
-    let router = Router::new().route("/", post(file_upload));
-    // .layer(middleware::from_fn(print_request_body));
+pub fn build(pool: Pool<Postgres>) -> Router {
+    let router = Router::new()
+        .route("/", post(file_upload))
+        // .layer(middleware::from_fn(print_request_body));
+        .with_state(pool);
 
     Router::new().nest("/file-upload", router)
 }
 
 #[derive(Serialize)]
-struct FileMetadata {
-    name: String,
-    file_name: String,
-    content_type: String,
-    location: String,
+struct UploadedFile {
+    #[serde(flatten)]
+    file: FileUpload,
+    url: String,
 }
 
-async fn file_upload(mut multipart: Multipart) -> Result<AppSuccess<FileMetadata>> {
-    let mut name = String::from("-");
-    let mut file_name = String::from("-");
-    let mut content_type = String::from("-");
-    let mut location = String::from("-");
-
-    while let Some(field) = multipart.next_field().await.unwrap() {
-        name = field.name().unwrap().to_string();
-        file_name = format!(
-            "{}-{}",
-            Uuid::new_v4(),
-            field.file_name().unwrap().to_string()
-        );
-        content_type = field.content_type().unwrap().to_string();
-        let data = field.bytes().await.unwrap();
-        location = format!("./public/files/{}", file_name);
-        fs::write(location.clone(), data)?;
+/// Stores the first file of the form. Lots and other records refer to the
+/// returned id, never to the path on disk.
+async fn file_upload(
+    State(pool): State<PgPool>,
+    mut multipart: Multipart,
+) -> Result<AppSuccess<UploadedFile>> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| Error::BadRequest(err.body_text()))?
+    {
+        let Some(original_name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        let content_type = field
+            .content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let data = field
+            .bytes()
+            .await
+            .map_err(|err| Error::BadRequest(err.body_text()))?;
+
+        // Images are typed and named from their content, a renamed file cannot
+        // pass as a photo or be served as something else.
+        let image_type = sniff_image(&data);
+        if image_type.is_none() && content_type.starts_with("image/") {
+            return Err(Error::BadRequest(
+                "Only png, jpeg and webp images can be uploaded".to_string(),
+            ));
+        }
+        let (content_type, storage_key) = match image_type {
+            Some(image_type) => (
+                image_type.to_string(),
+                format!("{}.{}", Uuid::new_v4(), image_type.trim_start_matches("image/")),
+            ),
+            None => (content_type, storage_key(&original_name)),
+        };
+        STORAGE.put(&storage_key, &data).await?;
+
+        let file = FileUpload {
+            id: Uuid::new_v4(),
+            storage: STORAGE.backend,
+            storage_key: storage_key.clone(),
+            original_name,
+            content_type,
+            size: data.len() as i64,
+            created_at: Some(Utc::now().naive_utc()),
+        };
+        let file = match file.save(&pool).await {
+            Ok(file) => file,
+            Err(err) => {
+                if let Err(delete_err) = STORAGE.delete(&storage_key).await {
+                    error!("Failed to delete unsaved upload {}: {:?}", storage_key, delete_err);
+                }
+                return Err(err);
+            }
+        };
+
+        return Ok(AppSuccess(UploadedFile {
+            url: file.url(),
+            file,
+        }));
     }
 
-    Ok(AppSuccess(FileMetadata {
-        name,
-        file_name,
-        content_type,
-        location,
-    }))
+    Err(Error::BadRequest("No file is uploaded".to_string()))
 }
@@ -0,0 +1,150 @@
+//! Rows shared by the router tests: an owner with one lot priced for cars, a
+//! keeper assigned to it and an easypark with a car.
+
+use jsonwebtoken::{encode, Header};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{extractor::current_user::CurrentUser, jwt::config::KEYS};
+
+pub const OWNER_ID: Uuid = Uuid::from_u128(0xa1);
+pub const KEEPER_ID: Uuid = Uuid::from_u128(0xc1);
+pub const EASYPARK_ID: Uuid = Uuid::from_u128(0xe1);
+pub const PARKING_LOT_ID: Uuid = Uuid::from_u128(0xb1);
+pub const VEHICLE_ID: Uuid = Uuid::from_u128(0xd1);
+
+pub const OWNER_PHONE_NUMBER: &str = "6281000000001";
+pub const EASYPARK_PHONE_NUMBER: &str = "6281000000002";
+
+/// Authorization header of a signed in user, tests run without the secret of
+/// the deployment so one is made up when it is missing.
+pub fn bearer(phone_number: &str) -> String {
+    if std::env::var("JWT_SECRET").is_err() {
+        std::env::set_var("JWT_SECRET", "test-secret");
+    }
+
+    let current_user = CurrentUser {
+        sub: phone_number.to_string(),
+        company: "Backend Parking".to_string(),
+        exp: usize::MAX,
+    };
+    let token = encode(&Header::default(), &current_user, &KEYS.encoding).unwrap();
+
+    format!("Bearer {}", token)
+}
+
+pub async fn seed(pool: &PgPool) {
+    sqlx::query(
+        r#"
+            insert into "user" (id, phone_number, name, nik, role, status) values
+                ($1, '6281000000001', 'Owner', '1', 'park_owner', 'active'),
+                ($2, '6281000000002', 'Easypark', '2', 'easypark', 'active')
+        "#,
+    )
+    .bind(OWNER_ID)
+    .bind(EASYPARK_ID)
+    .execute(pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        r#"
+            insert into parking_lot (id, area_name, address, image_url, car_cost, motor_cost, owner_id)
+            values ($1, 'Lot', 'Address', 'image', 5000, 2000, $2)
+        "#,
+    )
+    .bind(PARKING_LOT_ID)
+    .bind(OWNER_ID)
+    .execute(pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        r#"
+            with tariff as (
+                insert into parking_lot_tariff (id, parking_lot_id, version, car_cost, motor_cost, effective_from)
+                values (gen_random_uuid(), $1, 1, 5000, 2000, '1970-01-01')
+                returning id
+            )
+            insert into parking_lot_rate (id, tariff_id, parking_lot_id, vehicle_category_id, cost)
+            select gen_random_uuid(), tariff.id, $1, c.id, 5000
+            from tariff, vehicle_category c
+            where c.code = 'car'
+        "#,
+    )
+    .bind(PARKING_LOT_ID)
+    .execute(pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        r#"
+            insert into parking_lot_vehicle_category (parking_lot_id, vehicle_category_id)
+            select $1, id from vehicle_category where code = 'car'
+        "#,
+    )
+    .bind(PARKING_LOT_ID)
+    .execute(pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        r#"
+            insert into "user" (id, phone_number, name, nik, role, status, owner_id, parking_lot_id)
+            values ($1, '6281000000003', 'Keeper', '3', 'park_keeper', 'active', $2, $3)
+        "#,
+    )
+    .bind(KEEPER_ID)
+    .bind(OWNER_ID)
+    .bind(PARKING_LOT_ID)
+    .execute(pool)
+    .await
+    .unwrap();
+
+    sqlx::query(
+        r#"
+            insert into vehicle (id, user_id, plate_number, vehicle_type, color)
+            values ($1, $2, 'B 1234 ABC', 'car', 'Black')
+        "#,
+    )
+    .bind(VEHICLE_ID)
+    .bind(EASYPARK_ID)
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// Sells a weekly pass of the lot and gives the seeded car one that started a
+/// day ago, returns the id of that pass.
+pub async fn seed_pass(pool: &PgPool, discount_percent: f64) -> Uuid {
+    let pass_id = Uuid::new_v4();
+
+    sqlx::query(
+        r#"
+            with plan as (
+                insert into parking_pass_plan (id, parking_lot_id, period, price, discount_percent)
+                values (gen_random_uuid(), $1, 'weekly', 35000, $2)
+                returning id
+            ),
+            transaction as (
+                insert into transaction_history (id) values (gen_random_uuid())
+                returning id
+            )
+            insert into parking_pass (id, plan_id, parking_lot_id, vehicle_id, easypark_id, period, price, discount_percent, status, transaction_id, starts_at, ends_at)
+            select $3, plan.id, $1, $4, $5, 'weekly', 35000, $2, 'active', transaction.id,
+                date_trunc('second', now() at time zone 'utc' - interval '1 day'),
+                date_trunc('second', now() at time zone 'utc' + interval '6 days')
+            from plan, transaction
+        "#,
+    )
+    .bind(PARKING_LOT_ID)
+    .bind(discount_percent)
+    .bind(pass_id)
+    .bind(VEHICLE_ID)
+    .bind(EASYPARK_ID)
+    .execute(pool)
+    .await
+    .unwrap();
+
+    pass_id
+}
@@ -0,0 +1,255 @@
+pub mod router;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::{
+    app::parking_history::{PenaltyReason, VehicleType},
+    error::aggregate::{Error, Result},
+};
+
+const OPEN_SHIFT_CONSTRAINT: &str = "keeper_shift_keeper_id_open_key";
+
+fn map_open_shift_violation(error: sqlx::Error) -> Error {
+    match &error {
+        sqlx::Error::Database(dbx) if dbx.constraint() == Some(OPEN_SHIFT_CONSTRAINT) => {
+            Error::Conflict("Keeper already has an open shift".to_string())
+        }
+        _ => Error::Sqlx(error),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeeperShift {
+    pub id: Uuid,
+    pub keeper_id: Uuid,
+    pub owner_id: Uuid,
+    pub parking_lot_id: Option<Uuid>,
+    pub shift_status: ShiftStatus,
+    pub clock_in_at: NaiveDateTime,
+    pub clock_out_at: Option<NaiveDateTime>,
+    pub total_ticket: Option<i64>,
+    pub expected_cash: Option<f64>,
+    pub declared_cash: Option<f64>,
+    pub discrepancy: Option<f64>,
+    pub signed_off_at: Option<NaiveDateTime>,
+    pub sign_off_note: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, sqlx::Type, Deserialize, Serialize)]
+#[sqlx(type_name = "shift_status", rename_all = "snake_case")]
+pub enum ShiftStatus {
+    Default,
+    Open,
+    Closed,
+    SignedOff,
+}
+
+/// A cash ticket closed by the keeper during a shift. `expected_cash` is the
+/// settled amount when there is one, otherwise the hourly rate times the started
+/// hours plus any penalty, which is what the keeper should have collected.
+#[derive(Debug, Serialize)]
+pub struct ShiftCashTicket {
+    pub id: Uuid,
+    pub plate_number: Option<String>,
+    pub vehicle_type: VehicleType,
+    pub area_name: String,
+    pub check_in_date: Option<NaiveDateTime>,
+    pub check_out_date: Option<NaiveDateTime>,
+    pub amount: f64,
+    pub penalty_amount: f64,
+    pub penalty_reason: Option<PenaltyReason>,
+    pub expected_cash: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ShiftQuery {
+    pub owner_id: Option<Uuid>,
+    pub keeper_id: Option<Uuid>,
+    pub parking_lot_id: Option<Uuid>,
+    pub shift_status: Option<ShiftStatus>,
+}
+
+impl KeeperShift {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<KeeperShift> {
+        let shift = sqlx::query_as!(
+            KeeperShift,
+            r#"
+                insert into "keeper_shift" (id, keeper_id, owner_id, parking_lot_id, shift_status, clock_in_at, created_at, updated_at)
+                values ($1, $2, $3, $4, $5, $6, $7, $8)
+                returning id, keeper_id, owner_id, parking_lot_id, shift_status as "shift_status!: ShiftStatus", clock_in_at, clock_out_at,
+                    total_ticket, expected_cash, declared_cash, discrepancy, signed_off_at, sign_off_note, created_at, updated_at
+            "#,
+            self.id,
+            self.keeper_id,
+            self.owner_id,
+            self.parking_lot_id,
+            self.shift_status as ShiftStatus,
+            self.clock_in_at,
+            self.created_at,
+            self.updated_at
+        )
+            .fetch_one(executor)
+            .await
+            .map_err(map_open_shift_violation)?;
+
+        Ok(shift)
+    }
+
+    pub async fn find_one(id: Uuid, executor: impl PgExecutor<'_>) -> Result<KeeperShift> {
+        let shift = sqlx::query_as!(
+            KeeperShift,
+            r#"
+                select id, keeper_id, owner_id, parking_lot_id, shift_status as "shift_status!: ShiftStatus", clock_in_at, clock_out_at,
+                    total_ticket, expected_cash, declared_cash, discrepancy, signed_off_at, sign_off_note, created_at, updated_at
+                from "keeper_shift"
+                where id = $1
+            "#,
+            id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(shift)
+    }
+
+    pub async fn aggregate(payload: ShiftQuery, executor: impl PgExecutor<'_>) -> Result<Vec<KeeperShift>> {
+        let shift = sqlx::query_as!(
+            KeeperShift,
+            r#"
+                select id, keeper_id, owner_id, parking_lot_id, shift_status as "shift_status!: ShiftStatus", clock_in_at, clock_out_at,
+                    total_ticket, expected_cash, declared_cash, discrepancy, signed_off_at, sign_off_note, created_at, updated_at
+                from "keeper_shift"
+                where ($1::uuid is null or owner_id = $1) and
+                    ($2::uuid is null or keeper_id = $2) and
+                    ($3 = 'default' or shift_status = $3::shift_status) and
+                    ($4::uuid is null or parking_lot_id = $4)
+                order by clock_in_at desc
+            "#,
+            payload.owner_id,
+            payload.keeper_id,
+            payload.shift_status.unwrap_or(ShiftStatus::Default) as ShiftStatus,
+            payload.parking_lot_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(shift)
+    }
+
+    /// Closes an open shift. Returns `Conflict` when the shift was closed in the
+    /// meantime so a handover is never recorded twice.
+    pub async fn clock_out(
+        id: Uuid,
+        clock_out_at: NaiveDateTime,
+        total_ticket: i64,
+        expected_cash: f64,
+        declared_cash: f64,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<KeeperShift> {
+        let shift = sqlx::query_as!(
+            KeeperShift,
+            r#"
+                update "keeper_shift"
+                set shift_status = 'closed',
+                    clock_out_at = $2,
+                    total_ticket = $3,
+                    expected_cash = $4,
+                    declared_cash = $5,
+                    discrepancy = $5::float8 - $4::float8,
+                    updated_at = $2
+                where id = $1 and shift_status = 'open'
+                returning id, keeper_id, owner_id, parking_lot_id, shift_status as "shift_status!: ShiftStatus", clock_in_at, clock_out_at,
+                    total_ticket, expected_cash, declared_cash, discrepancy, signed_off_at, sign_off_note, created_at, updated_at
+            "#,
+            id,
+            clock_out_at,
+            total_ticket,
+            expected_cash,
+            declared_cash
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        shift.ok_or(Error::Conflict("Shift is not open".to_string()))
+    }
+
+    pub async fn sign_off(
+        id: Uuid,
+        owner_id: Uuid,
+        note: Option<String>,
+        signed_off_at: NaiveDateTime,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<KeeperShift> {
+        let shift = sqlx::query_as!(
+            KeeperShift,
+            r#"
+                update "keeper_shift"
+                set shift_status = 'signed_off',
+                    signed_off_at = $3,
+                    sign_off_note = $4,
+                    updated_at = $3
+                where id = $1 and owner_id = $2 and shift_status = 'closed'
+                returning id, keeper_id, owner_id, parking_lot_id, shift_status as "shift_status!: ShiftStatus", clock_in_at, clock_out_at,
+                    total_ticket, expected_cash, declared_cash, discrepancy, signed_off_at, sign_off_note, created_at, updated_at
+            "#,
+            id,
+            owner_id,
+            signed_off_at,
+            note
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        shift.ok_or(Error::Conflict("Only a closed shift of your own keeper can be signed off".to_string()))
+    }
+}
+
+impl ShiftCashTicket {
+    pub async fn find_by_keeper(
+        keeper_id: Uuid,
+        clock_in_at: NaiveDateTime,
+        clock_out_at: NaiveDateTime,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<Vec<ShiftCashTicket>> {
+        let data = sqlx::query_as!(
+            ShiftCashTicket,
+            r#"
+                select ph.id,
+                    ph.plate_number,
+                    ph.vehicle_type as "vehicle_type!: VehicleType",
+                    pl.area_name,
+                    ph.check_in_date,
+                    ph.check_out_date,
+                    ph.amount,
+                    ph.penalty_amount,
+                    ph.penalty_reason as "penalty_reason: PenaltyReason",
+                    coalesce(
+                        cast(th.gross_amount as float),
+                        CEIL(EXTRACT(EPOCH FROM (ph.check_out_date - coalesce(ph.check_in_date, ph.created_at))) / 3600) * ph.amount + ph.penalty_amount
+                    ) as expected_cash
+                from parking_history ph
+                join parking_lot pl on pl.id = ph.parking_lot_id
+                left join transaction_history th on th.id = ph.transaction_id
+                where ph.keeper_id = $1 and
+                    ph.payment = 'cash' and
+                    ph.ticket_status = 'not_active' and
+                    ph.deleted_at is null and
+                    ph.check_out_date >= $2 and
+                    ph.check_out_date <= $3
+                order by ph.check_out_date
+            "#,
+            keeper_id,
+            clock_in_at,
+            clock_out_at
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+}
@@ -0,0 +1,189 @@
+use axum::{
+    extract::{Path, Query, State},
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    app::user::{Role, User},
+    error::aggregate::{Error, Result},
+    extractor::{app_body::Body, app_json::AppSuccess},
+    middleware::base::print_request_body,
+    unit_of_work::UnitOfWork,
+};
+
+use super::{KeeperShift, ShiftCashTicket, ShiftQuery, ShiftStatus};
+
+pub fn build(pool: Pool<Postgres>) -> Router {
+    let router = Router::new()
+        .route("/", get(aggregate))
+        .route("/clock-in", post(clock_in))
+        .route("/:id", get(report))
+        .route("/:id/clock-out", post(clock_out))
+        .route("/:id/sign-off", post(sign_off))
+        .layer(middleware::from_fn(print_request_body))
+        .with_state(pool);
+
+    Router::new().nest("/keeper-shift", router)
+}
+
+#[derive(Deserialize, Serialize)]
+struct ClockInPayload {
+    keeper_id: Uuid,
+}
+
+async fn clock_in(
+    State(pool): State<PgPool>,
+    Body(payload): Body<ClockInPayload>,
+) -> Result<AppSuccess<KeeperShift>> {
+    let keeper = User::find_one_by_id(payload.keeper_id, &pool).await?;
+    if keeper.role != Role::ParkKeeper {
+        return Err(Error::BadRequest(
+            "Provided keeper id is not having ParkKeeper role".to_string(),
+        ));
+    }
+    let owner_id = keeper
+        .owner_id
+        .ok_or(Error::BadRequest("Keeper is not assigned to an owner".to_string()))?;
+
+    let now = Utc::now().naive_utc();
+    let shift = KeeperShift {
+        id: Uuid::new_v4(),
+        keeper_id: keeper.id,
+        owner_id,
+        parking_lot_id: keeper.parking_lot_id,
+        shift_status: ShiftStatus::Open,
+        clock_in_at: now,
+        clock_out_at: None,
+        total_ticket: None,
+        expected_cash: None,
+        declared_cash: None,
+        discrepancy: None,
+        signed_off_at: None,
+        sign_off_note: None,
+        created_at: Some(now),
+        updated_at: Some(now),
+    };
+    let shift = shift.save(&pool).await?;
+
+    Ok(AppSuccess(shift))
+}
+
+#[derive(Deserialize, Serialize)]
+struct ClockOutPayload {
+    declared_cash: f64,
+}
+
+#[derive(Serialize)]
+struct ShiftReport {
+    shift: KeeperShift,
+    tickets: Vec<ShiftCashTicket>,
+    expected_cash: f64,
+}
+
+async fn clock_out(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<ClockOutPayload>,
+) -> Result<AppSuccess<ShiftReport>> {
+    if payload.declared_cash < 0.0 {
+        return Err(Error::BadRequest(
+            "declared_cash must not be negative".to_string(),
+        ));
+    }
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+
+    let shift = KeeperShift::find_one(id, uow.connection()).await?;
+    let now = Utc::now().naive_utc();
+    let tickets =
+        ShiftCashTicket::find_by_keeper(shift.keeper_id, shift.clock_in_at, now, uow.connection())
+            .await?;
+    let expected_cash = sum_expected_cash(&tickets);
+    let shift = KeeperShift::clock_out(
+        id,
+        now,
+        tickets.len() as i64,
+        expected_cash,
+        payload.declared_cash,
+        uow.connection(),
+    )
+    .await?;
+
+    uow.commit().await?;
+
+    Ok(AppSuccess(ShiftReport {
+        shift,
+        tickets,
+        expected_cash,
+    }))
+}
+
+async fn report(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<ShiftReport>> {
+    let shift = KeeperShift::find_one(id, &pool).await?;
+    // An open shift reports everything collected so far.
+    let clock_out_at = shift.clock_out_at.unwrap_or(Utc::now().naive_utc());
+    let tickets =
+        ShiftCashTicket::find_by_keeper(shift.keeper_id, shift.clock_in_at, clock_out_at, &pool)
+            .await?;
+    let expected_cash = sum_expected_cash(&tickets);
+
+    Ok(AppSuccess(ShiftReport {
+        shift,
+        tickets,
+        expected_cash,
+    }))
+}
+
+fn sum_expected_cash(tickets: &[ShiftCashTicket]) -> f64 {
+    tickets
+        .iter()
+        .filter_map(|ticket| ticket.expected_cash)
+        .fold(0.0, |total, cash| total + cash)
+}
+
+async fn aggregate(
+    State(pool): State<PgPool>,
+    Query(payload): Query<ShiftQuery>,
+) -> Result<AppSuccess<Vec<KeeperShift>>> {
+    let shift = KeeperShift::aggregate(payload, &pool).await?;
+    Ok(AppSuccess(shift))
+}
+
+#[derive(Deserialize, Serialize)]
+struct SignOffPayload {
+    owner_id: Uuid,
+    note: Option<String>,
+}
+
+async fn sign_off(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<SignOffPayload>,
+) -> Result<AppSuccess<KeeperShift>> {
+    let owner = User::find_one_by_id(payload.owner_id, &pool).await?;
+    if owner.role != Role::ParkOwner {
+        return Err(Error::BadRequest(
+            "Provided owner id is not having ParkOwner role".to_string(),
+        ));
+    }
+
+    let shift = KeeperShift::sign_off(
+        id,
+        owner.id,
+        payload.note,
+        Utc::now().naive_utc(),
+        &pool,
+    )
+    .await?;
+
+    Ok(AppSuccess(shift))
+}
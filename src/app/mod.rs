@@ -1,8 +1,17 @@
+pub mod analytics;
 pub mod auth;
+pub mod dashboard;
+#[cfg(test)]
+pub mod fixture;
 pub mod payment;
 pub mod router;
+pub mod scheduler;
 pub mod user;
 pub mod whatsapp;
 pub mod file_upload;
+pub mod keeper_shift;
 pub mod parking_area;
 pub mod parking_history;
+pub mod parking_pass;
+pub mod report;
+pub mod vehicle;
@@ -0,0 +1,102 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::error::aggregate::Result;
+
+/// One period a keeper worked a lot, still running while `ended_at` is empty.
+#[derive(Debug, Serialize)]
+pub struct KeeperAssignment {
+    pub id: Uuid,
+    pub keeper_id: Uuid,
+    pub parking_lot_id: Uuid,
+    pub owner_id: Uuid,
+    pub started_at: NaiveDateTime,
+    pub ended_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeeperAssignmentHistory {
+    pub id: Uuid,
+    pub keeper_id: Uuid,
+    pub keeper_name: String,
+    pub started_at: NaiveDateTime,
+    pub ended_at: Option<NaiveDateTime>,
+}
+
+impl KeeperAssignment {
+    /// Opens an assignment to the lot for every keeper, ending whatever
+    /// assignment they had before.
+    pub async fn start(
+        parking_lot_id: Uuid,
+        owner_id: Uuid,
+        keeper_ids: &[Uuid],
+        started_at: NaiveDateTime,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<KeeperAssignment>> {
+        Self::end(keeper_ids, started_at, &mut *connection).await?;
+
+        let mut assignment = Vec::with_capacity(keeper_ids.len());
+        for keeper_id in keeper_ids {
+            let started = sqlx::query_as!(
+                KeeperAssignment,
+                r#"
+                    insert into "keeper_assignment" (id, keeper_id, parking_lot_id, owner_id, started_at, created_at)
+                    values ($1, $2, $3, $4, $5, $5)
+                    returning id, keeper_id, parking_lot_id, owner_id, started_at, ended_at, created_at
+                "#,
+                Uuid::new_v4(),
+                keeper_id,
+                parking_lot_id,
+                owner_id,
+                started_at
+            )
+                .fetch_one(&mut *connection)
+                .await?;
+            assignment.push(started);
+        }
+
+        Ok(assignment)
+    }
+
+    /// Ends the running assignment of each keeper, keepers without one are
+    /// skipped.
+    pub async fn end(keeper_ids: &[Uuid], ended_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Vec<KeeperAssignment>> {
+        let assignment = sqlx::query_as!(
+            KeeperAssignment,
+            r#"
+                update "keeper_assignment"
+                set ended_at = $2
+                where keeper_id = any($1) and ended_at is null
+                returning id, keeper_id, parking_lot_id, owner_id, started_at, ended_at, created_at
+            "#,
+            keeper_ids,
+            ended_at
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(assignment)
+    }
+
+    /// Every keeper the lot ever had, the latest assignment first.
+    pub async fn find_by_parking_lot(parking_lot_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<KeeperAssignmentHistory>> {
+        let history = sqlx::query_as!(
+            KeeperAssignmentHistory,
+            r#"
+                select ka.id, ka.keeper_id, u.name as keeper_name, ka.started_at, ka.ended_at
+                from "keeper_assignment" ka
+                join "user" u on u.id = ka.keeper_id
+                where ka.parking_lot_id = $1
+                order by ka.started_at desc, u.name
+            "#,
+            parking_lot_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(history)
+    }
+}
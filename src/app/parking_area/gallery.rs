@@ -0,0 +1,101 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::{
+    app::file_upload::{StorageBackend, STORAGE},
+    error::aggregate::Result,
+};
+
+/// Lots show at most this many photos.
+pub const MAX_GALLERY_IMAGE: usize = 10;
+
+#[derive(Debug)]
+pub struct ParkingLotImage {
+    pub file_upload_id: Uuid,
+    pub position: i32,
+    pub is_cover: bool,
+    pub storage: StorageBackend,
+    pub storage_key: String,
+    pub content_type: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GalleryImage {
+    pub file_upload_id: Uuid,
+    pub position: i32,
+    pub is_cover: bool,
+    pub content_type: String,
+    pub url: String,
+}
+
+impl ParkingLotImage {
+    pub fn into_gallery_image(self) -> GalleryImage {
+        GalleryImage {
+            url: STORAGE.url(self.storage, &self.storage_key),
+            file_upload_id: self.file_upload_id,
+            position: self.position,
+            is_cover: self.is_cover,
+            content_type: self.content_type,
+        }
+    }
+
+    pub async fn find_by_parking_lot(parking_lot_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<ParkingLotImage>> {
+        let image = sqlx::query_as!(
+            ParkingLotImage,
+            r#"
+                select pi.file_upload_id,
+                    pi.position,
+                    pi.is_cover,
+                    f.storage as "storage!: StorageBackend",
+                    f.storage_key,
+                    f.content_type
+                from "parking_lot_image" pi
+                join "file_upload" f on f.id = pi.file_upload_id
+                where pi.parking_lot_id = $1
+                order by pi.position
+            "#,
+            parking_lot_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(image)
+    }
+
+    /// Swaps the whole gallery at once, `file_upload_ids` in display order.
+    pub async fn replace(
+        parking_lot_id: Uuid,
+        file_upload_ids: &[Uuid],
+        cover_id: Option<Uuid>,
+        created_at: NaiveDateTime,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<ParkingLotImage>> {
+        sqlx::query!(
+            r#"delete from "parking_lot_image" where parking_lot_id = $1"#,
+            parking_lot_id
+        )
+            .execute(&mut *connection)
+            .await?;
+
+        for (position, file_upload_id) in file_upload_ids.iter().enumerate() {
+            sqlx::query!(
+                r#"
+                    insert into "parking_lot_image" (id, parking_lot_id, file_upload_id, position, is_cover, created_at)
+                    values ($1, $2, $3, $4, $5, $6)
+                "#,
+                Uuid::new_v4(),
+                parking_lot_id,
+                file_upload_id,
+                position as i32,
+                cover_id == Some(*file_upload_id),
+                created_at
+            )
+                .execute(&mut *connection)
+                .await?;
+        }
+
+        Self::find_by_parking_lot(parking_lot_id, &mut *connection).await
+    }
+}
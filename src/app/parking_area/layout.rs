@@ -0,0 +1,317 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::error::aggregate::{Error, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, sqlx::Type, Deserialize, Serialize)]
+#[sqlx(type_name = "spot_kind", rename_all = "snake_case")]
+pub enum SpotKind {
+    Regular,
+    Disabled,
+    EvCharging,
+}
+
+/// An area of a lot, e.g. one floor of a mall.
+#[derive(Debug, Serialize)]
+pub struct ParkingZone {
+    pub id: Uuid,
+    pub parking_lot_id: Uuid,
+    pub name: String,
+    pub floor: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ZoneOccupancy {
+    pub id: Uuid,
+    pub name: String,
+    pub floor: Option<String>,
+    pub total_spot: i64,
+    pub occupied_spot: i64,
+    pub free_spot: i64,
+    pub free_disabled_spot: i64,
+    pub free_ev_charging_spot: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParkingSpot {
+    pub id: Uuid,
+    pub zone_id: Uuid,
+    pub parking_lot_id: Uuid,
+    pub number: String,
+    pub kind: SpotKind,
+    pub is_active: bool,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+/// A spot with the ticket holding it, if any.
+#[derive(Debug, Serialize)]
+pub struct SpotStatus {
+    pub id: Uuid,
+    pub number: String,
+    pub kind: SpotKind,
+    pub is_active: bool,
+    pub parking_history_id: Option<Uuid>,
+    pub plate_number: Option<String>,
+    pub allocated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpotAllocation {
+    pub id: Uuid,
+    pub spot_id: Uuid,
+    pub parking_history_id: Uuid,
+    pub allocated_at: NaiveDateTime,
+    pub released_at: Option<NaiveDateTime>,
+}
+
+impl ParkingZone {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<ParkingZone> {
+        let zone = sqlx::query_as!(
+            ParkingZone,
+            r#"
+                insert into "parking_zone" (id, parking_lot_id, name, floor, created_at)
+                values ($1, $2, $3, $4, $5)
+                returning id, parking_lot_id, name, floor, created_at
+            "#,
+            self.id,
+            self.parking_lot_id,
+            self.name,
+            self.floor,
+            self.created_at
+        )
+            .fetch_one(executor)
+            .await
+            .map_err(|err| match &err {
+                sqlx::Error::Database(dbx) if dbx.is_unique_violation() => {
+                    Error::Conflict("Zone name is already used in the parking lot".to_string())
+                }
+                _ => Error::from(err),
+            })?;
+
+        Ok(zone)
+    }
+
+    pub async fn find_one(parking_lot_id: Uuid, id: Uuid, executor: impl PgExecutor<'_>) -> Result<ParkingZone> {
+        let zone = sqlx::query_as!(
+            ParkingZone,
+            r#"
+                select id, parking_lot_id, name, floor, created_at
+                from "parking_zone"
+                where parking_lot_id = $1 and id = $2
+            "#,
+            parking_lot_id,
+            id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(zone)
+    }
+
+    /// Inactive spots are left out of every count.
+    pub async fn occupancy(parking_lot_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<ZoneOccupancy>> {
+        let zone = sqlx::query_as!(
+            ZoneOccupancy,
+            r#"
+                select z.id,
+                    z.name,
+                    z.floor,
+                    count(s.id) as "total_spot!",
+                    count(a.id) as "occupied_spot!",
+                    count(s.id) - count(a.id) as "free_spot!",
+                    count(s.id) filter (where s.kind = 'disabled' and a.id is null) as "free_disabled_spot!",
+                    count(s.id) filter (where s.kind = 'ev_charging' and a.id is null) as "free_ev_charging_spot!"
+                from "parking_zone" z
+                left join "parking_spot" s on s.zone_id = z.id and s.is_active
+                left join "spot_allocation" a on a.spot_id = s.id and a.released_at is null
+                where z.parking_lot_id = $1
+                group by z.id, z.name, z.floor
+                order by z.floor nulls first, z.name
+            "#,
+            parking_lot_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(zone)
+    }
+}
+
+impl ParkingSpot {
+    /// Adds the spots of a zone at once, numbers have to be new in the zone.
+    pub async fn save_many(
+        zone: &ParkingZone,
+        spots: &[(String, SpotKind)],
+        created_at: NaiveDateTime,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<ParkingSpot>> {
+        let mut spot = Vec::with_capacity(spots.len());
+        for (number, kind) in spots {
+            let saved = sqlx::query_as!(
+                ParkingSpot,
+                r#"
+                    insert into "parking_spot" (id, zone_id, parking_lot_id, number, kind, created_at)
+                    values ($1, $2, $3, $4, $5, $6)
+                    returning id, zone_id, parking_lot_id, number, kind as "kind!: SpotKind", is_active, created_at, updated_at
+                "#,
+                Uuid::new_v4(),
+                zone.id,
+                zone.parking_lot_id,
+                number,
+                *kind as SpotKind,
+                created_at
+            )
+                .fetch_one(&mut *connection)
+                .await
+                .map_err(|err| match &err {
+                    sqlx::Error::Database(dbx) if dbx.is_unique_violation() => {
+                        Error::Conflict(format!("Spot {} already exists in the zone", number))
+                    }
+                    _ => Error::from(err),
+                })?;
+            spot.push(saved);
+        }
+
+        Ok(spot)
+    }
+
+    pub async fn find_one(parking_lot_id: Uuid, id: Uuid, executor: impl PgExecutor<'_>) -> Result<ParkingSpot> {
+        let spot = sqlx::query_as!(
+            ParkingSpot,
+            r#"
+                select id, zone_id, parking_lot_id, number, kind as "kind!: SpotKind", is_active, created_at, updated_at
+                from "parking_spot"
+                where parking_lot_id = $1 and id = $2
+            "#,
+            parking_lot_id,
+            id
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        spot.ok_or(Error::NotFoundRejection("Spot is not found in the parking lot".to_string()))
+    }
+
+    pub async fn find_by_zone(zone_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<SpotStatus>> {
+        let spot = sqlx::query_as!(
+            SpotStatus,
+            r#"
+                select s.id,
+                    s.number,
+                    s.kind as "kind!: SpotKind",
+                    s.is_active,
+                    a.parking_history_id as "parking_history_id?",
+                    ph.plate_number as "plate_number?",
+                    a.allocated_at as "allocated_at?"
+                from "parking_spot" s
+                left join "spot_allocation" a on a.spot_id = s.id and a.released_at is null
+                left join "parking_history" ph on ph.id = a.parking_history_id
+                where s.zone_id = $1
+                order by length(s.number), s.number
+            "#,
+            zone_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(spot)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateParkingSpot {
+    pub kind: Option<SpotKind>,
+    pub is_active: Option<bool>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl UpdateParkingSpot {
+    pub async fn update(self, parking_lot_id: Uuid, id: Uuid, executor: impl PgExecutor<'_>) -> Result<ParkingSpot> {
+        let spot = sqlx::query_as!(
+            ParkingSpot,
+            r#"
+                update "parking_spot"
+                set kind = coalesce($1, kind),
+                    is_active = coalesce($2, is_active),
+                    updated_at = coalesce($3, updated_at)
+                where parking_lot_id = $4 and id = $5
+                returning id, zone_id, parking_lot_id, number, kind as "kind!: SpotKind", is_active, created_at, updated_at
+            "#,
+            self.kind as Option<SpotKind>,
+            self.is_active,
+            self.updated_at,
+            parking_lot_id,
+            id
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        spot.ok_or(Error::NotFoundRejection("Spot is not found in the parking lot".to_string()))
+    }
+}
+
+impl SpotAllocation {
+    pub async fn allocate(spot_id: Uuid, parking_history_id: Uuid, allocated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<SpotAllocation> {
+        let allocation = sqlx::query_as!(
+            SpotAllocation,
+            r#"
+                insert into "spot_allocation" (id, spot_id, parking_history_id, allocated_at)
+                values ($1, $2, $3, $4)
+                returning id, spot_id, parking_history_id, allocated_at, released_at
+            "#,
+            Uuid::new_v4(),
+            spot_id,
+            parking_history_id,
+            allocated_at
+        )
+            .fetch_one(executor)
+            .await
+            .map_err(|err| match &err {
+                sqlx::Error::Database(dbx) if dbx.is_unique_violation() => {
+                    Error::Conflict("Spot is already taken or the ticket already holds one".to_string())
+                }
+                _ => Error::from(err),
+            })?;
+
+        Ok(allocation)
+    }
+
+    /// Frees the spot of the ticket, tickets without one are skipped.
+    pub async fn release(parking_history_id: Uuid, released_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Option<SpotAllocation>> {
+        let allocation = sqlx::query_as!(
+            SpotAllocation,
+            r#"
+                update "spot_allocation"
+                set released_at = $2
+                where parking_history_id = $1 and released_at is null
+                returning id, spot_id, parking_history_id, allocated_at, released_at
+            "#,
+            parking_history_id,
+            released_at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        Ok(allocation)
+    }
+
+    pub async fn is_occupied(spot_id: Uuid, executor: impl PgExecutor<'_>) -> Result<bool> {
+        let occupied = sqlx::query_scalar!(
+            r#"
+                select exists(
+                    select 1 from "spot_allocation"
+                    where spot_id = $1 and released_at is null
+                ) as "occupied!"
+            "#,
+            spot_id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(occupied)
+    }
+}
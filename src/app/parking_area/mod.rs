@@ -1,11 +1,21 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres};
+use sqlx::{PgConnection, PgExecutor, Postgres, QueryBuilder};
 use uuid::Uuid;
 
-use crate::error::aggregate::Result;
+use crate::{
+    error::aggregate::{Error, Result},
+    query_filter::QueryFilter,
+    types::count::SqlxCount,
+};
 
+pub mod assignment;
+pub mod gallery;
+pub mod layout;
+pub mod rate;
 pub mod router;
+pub mod schedule;
+pub mod tariff;
 
 #[derive(Debug, Serialize)]
 pub struct ParkingLot {
@@ -18,6 +28,13 @@ pub struct ParkingLot {
     pub owner_id: Uuid,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub lost_ticket_fee: f64,
+    pub overstay_fee: f64,
+    pub max_stay_hours: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: String,
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -31,6 +48,14 @@ pub struct DetailParkingLotFromQuery {
     pub owner_id: Uuid,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub lost_ticket_fee: f64,
+    pub overstay_fee: f64,
+    pub max_stay_hours: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: String,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub is_open: Option<bool>,
     pub keeper_id: Option<Uuid>,
     pub keeper_name: Option<String>,
 }
@@ -46,14 +71,22 @@ pub struct ParkingLotWithCountOfKeeper {
     pub owner_id: Uuid,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub lost_ticket_fee: f64,
+    pub overstay_fee: f64,
+    pub max_stay_hours: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: String,
+    pub deleted_at: Option<NaiveDateTime>,
     pub keeper_count: Option<i64>,
+    pub is_open: Option<bool>,
 }
 
 impl ParkingLot {
-    pub async fn save(self, pool: &Pool<Postgres>) -> Result<ParkingLot> {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<ParkingLot> {
         let parking_lot = sqlx::query_as!(
             ParkingLot, 
-            r#"insert into "parking_lot" values ($1, $2, $3, $4, $5, $6, $7, $8, $9) returning id, area_name, address, image_url, car_cost, motor_cost, owner_id, created_at, updated_at"#,  
+            r#"insert into "parking_lot" values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15) returning id, area_name, address, image_url, car_cost, motor_cost, owner_id, created_at, updated_at, lost_ticket_fee, overstay_fee, max_stay_hours, latitude, longitude, timezone, deleted_at"#,  
             self.id,
             self.area_name,
             self.address,
@@ -62,55 +95,175 @@ impl ParkingLot {
             self.motor_cost,
             self.owner_id,
             self.created_at,
-            self.updated_at
+            self.updated_at,
+            self.lost_ticket_fee,
+            self.overstay_fee,
+            self.max_stay_hours,
+            self.latitude,
+            self.longitude,
+            self.timezone
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(parking_lot)
+    }
+
+    pub async fn find_one(id: Uuid, executor: impl PgExecutor<'_>) -> Result<ParkingLot> {
+        let parking_lot = sqlx::query_as!(
+            ParkingLot, 
+            r#"select * from parking_lot where id = $1 and deleted_at is null"#,  
+            id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(parking_lot)
+    }
+
+    /// Locks the lot row until the transaction ends, tickets and shifts of
+    /// the lot wait on it through their foreign key.
+    pub async fn find_one_for_update(id: Uuid, executor: impl PgExecutor<'_>) -> Result<ParkingLot> {
+        let parking_lot = sqlx::query_as!(
+            ParkingLot, 
+            r#"select * from parking_lot where id = $1 and deleted_at is null for update"#,  
+            id
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(parking_lot)
     }
 
-    pub async fn find_one(id: Uuid, pool: &Pool<Postgres>) -> Result<ParkingLot> {
+    /// Also finds archived lots, for showing where an old ticket was issued.
+    pub async fn find_one_with_archived(id: Uuid, executor: impl PgExecutor<'_>) -> Result<ParkingLot> {
         let parking_lot = sqlx::query_as!(
             ParkingLot, 
             r#"select * from parking_lot where id = $1"#,  
             id
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(parking_lot)
     }
+
+    /// Archives the lot and releases its keepers in the same statement, so no
+    /// keeper is left assigned to a lot nobody can see.
+    pub async fn archive(id: Uuid, owner_id: Uuid, deleted_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<ParkingLot> {
+        let parking_lot = sqlx::query_as!(
+            ParkingLot,
+            r#"
+                with archived as (
+                    update "parking_lot"
+                    set deleted_at = $3,
+                        updated_at = $3
+                    where id = $1 and owner_id = $2 and deleted_at is null
+                    returning id, area_name, address, image_url, car_cost, motor_cost, owner_id, created_at, updated_at, lost_ticket_fee, overstay_fee, max_stay_hours, latitude, longitude, timezone, deleted_at
+                ),
+                released as (
+                    update "user"
+                    set parking_lot_id = null
+                    where parking_lot_id in (select id from archived)
+                ),
+                ended as (
+                    update "keeper_assignment"
+                    set ended_at = $3
+                    where parking_lot_id in (select id from archived) and ended_at is null
+                )
+                select id as "id!",
+                    area_name as "area_name!",
+                    address as "address!",
+                    image_url as "image_url!",
+                    car_cost as "car_cost!",
+                    motor_cost as "motor_cost!",
+                    owner_id as "owner_id!",
+                    created_at,
+                    updated_at,
+                    lost_ticket_fee as "lost_ticket_fee!",
+                    overstay_fee as "overstay_fee!",
+                    max_stay_hours,
+                    latitude,
+                    longitude,
+                    timezone as "timezone!",
+                    deleted_at
+                from archived
+            "#,
+            id,
+            owner_id,
+            deleted_at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        parking_lot.ok_or(Error::NotFoundRejection("Parking lot is not found".to_string()))
+    }
+
+    /// `image_url` mirrors the cover of the gallery so listings do not have to
+    /// join the images, it is empty while the lot has no photo.
+    pub async fn update_image_url(id: Uuid, image_url: String, executor: impl PgExecutor<'_>) -> Result<()> {
+        sqlx::query!(
+            r#"update "parking_lot" set image_url = $2 where id = $1"#,
+            id,
+            image_url
+        )
+            .execute(executor)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn restore(id: Uuid, owner_id: Uuid, updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<ParkingLot> {
+        let parking_lot = sqlx::query_as!(
+            ParkingLot,
+            r#"
+                update "parking_lot"
+                set deleted_at = null,
+                    updated_at = $3
+                where id = $1 and owner_id = $2 and deleted_at is not null
+                returning id, area_name, address, image_url, car_cost, motor_cost, owner_id, created_at, updated_at, lost_ticket_fee, overstay_fee, max_stay_hours, latitude, longitude, timezone, deleted_at
+            "#,
+            id,
+            owner_id,
+            updated_at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        parking_lot.ok_or(Error::NotFoundRejection("Archived parking lot is not found".to_string()))
+    }
     
-    pub async fn detail(id: Uuid, pool: &Pool<Postgres>) -> Result<Vec<DetailParkingLotFromQuery>> {
+    pub async fn detail(id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<DetailParkingLotFromQuery>> {
         let parking_lot = sqlx::query_as!(
             DetailParkingLotFromQuery, 
             r#"
                 select pl.*, 
+                    parking_lot_is_open(pl.id, now() at time zone 'UTC') as is_open,
                     coalesce(u.id, null) as keeper_id, 
                     coalesce(u.name, null) as keeper_name 
                 from parking_lot pl
                 left join "user" u on u.parking_lot_id = pl.id
-                where pl.id = $1
+                where pl.id = $1 and pl.deleted_at is null
             "#,  
             id
         )
-            .fetch_all(pool)
+            .fetch_all(executor)
             .await?;
 
         Ok(parking_lot)
     }
     
-    pub async fn find_by_owner(owner_id: Uuid, pool: &Pool<Postgres>) -> Result<Vec<ParkingLotWithCountOfKeeper>> {
+    pub async fn find_by_owner(owner_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<ParkingLotWithCountOfKeeper>> {
         let parking_lot = sqlx::query_as!(
             ParkingLotWithCountOfKeeper, 
             r#"
                 select 
                     pl.*,
-                    count(u.*) as keeper_count
+                    count(u.*) as keeper_count,
+                    parking_lot_is_open(pl.id, now() at time zone 'UTC') as is_open
                 from parking_lot pl
                 left join "user" u on u.parking_lot_id = pl.id
-                where pl.owner_id = $1
+                where pl.owner_id = $1 and pl.deleted_at is null
                 group by pl.id,
                     pl.area_name,
                     pl.address,
@@ -119,17 +272,368 @@ impl ParkingLot {
                     pl.motor_cost,
                     pl.owner_id,
                     pl.created_at,
-                    pl.updated_at
+                    pl.updated_at,
+                    pl.lost_ticket_fee,
+                    pl.overstay_fee,
+                    pl.max_stay_hours,
+                    pl.latitude,
+                    pl.longitude,
+                    pl.timezone,
+                    pl.deleted_at
             "#,  
             owner_id
         )
-            .fetch_all(pool)
+            .fetch_all(executor)
+            .await?;
+
+        Ok(parking_lot)
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ParkingLotSearchQuery {
+    pub search: String,
+    pub owner_id: Option<Uuid>,
+    pub take: Option<i64>,
+    pub skip: Option<i64>,
+}
+
+impl QueryFilter for ParkingLotSearchQuery {
+    const FROM: &'static str = "from parking_lot pl";
+
+    fn push_filters(&self, builder: &mut QueryBuilder<'static, Postgres>) {
+        builder
+            .push(" and pl.deleted_at is null and (")
+            .push_bind(self.search.clone())
+            .push(" <% pl.area_name or ")
+            .push_bind(self.search.clone())
+            .push(" <% pl.address)");
+        if let Some(owner_id) = self.owner_id {
+            builder.push(" and pl.owner_id = ").push_bind(owner_id);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SearchedParkingLot {
+    pub id: Uuid,
+    pub area_name: String,
+    pub address: String,
+    pub image_url: String,
+    pub car_cost: f64,
+    pub motor_cost: f64,
+    pub is_open: Option<bool>,
+    pub rank: f32,
+}
+
+impl SearchedParkingLot {
+    /// Ranked by the best word similarity of area name or address. Expects the
+    /// connection to run `tolerate_typos` first.
+    pub async fn search(payload: &ParkingLotSearchQuery, connection: &mut PgConnection) -> Result<(i64, Vec<SearchedParkingLot>)> {
+        let total = payload
+            .count()
+            .build_query_scalar::<i64>()
+            .fetch_one(&mut *connection)
+            .await?;
+
+        let mut query = QueryBuilder::new(
+            "select pl.id, pl.area_name, pl.address, pl.image_url, pl.car_cost, pl.motor_cost, parking_lot_is_open(pl.id, now() at time zone 'UTC') as is_open, greatest(word_similarity(",
+        );
+        query
+            .push_bind(payload.search.clone())
+            .push(", pl.area_name), word_similarity(")
+            .push_bind(payload.search.clone())
+            .push(", pl.address)) as rank");
+        payload.push_from(&mut query);
+        query
+            .push(" order by rank desc, pl.id limit ")
+            .push_bind(payload.take)
+            .push(" offset ")
+            .push_bind(payload.skip);
+
+        let parking_lot = query
+            .build_query_as::<SearchedParkingLot>()
+            .fetch_all(&mut *connection)
+            .await?;
+
+        Ok((total, parking_lot))
+    }
+}
+
+/// Mean earth radius in meters, what the haversine distance is measured in.
+pub const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+const METERS_PER_LATITUDE_DEGREE: f64 = 111_320.0;
+
+#[derive(Debug, Clone)]
+pub struct NearbyQuery {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius: f64,
+    pub take: i64,
+}
+
+impl NearbyQuery {
+    /// Half the width and height of a box around the point that contains the
+    /// whole radius, in degrees. Lots outside it are skipped by the index.
+    fn bounding_box(&self) -> (f64, f64) {
+        let latitude_delta = self.radius / METERS_PER_LATITUDE_DEGREE;
+        let longitude_delta = self.radius
+            / (METERS_PER_LATITUDE_DEGREE * self.latitude.to_radians().cos().max(0.01));
+        (latitude_delta, longitude_delta.min(180.0))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NearbyParkingLot {
+    pub id: Uuid,
+    pub area_name: String,
+    pub address: String,
+    pub image_url: String,
+    pub car_cost: f64,
+    pub motor_cost: f64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub distance: f64,
+    pub is_open: Option<bool>,
+    pub active_car: i64,
+    pub active_motor: i64,
+}
+
+impl NearbyParkingLot {
+    /// Lots within `radius` meters of the point, closest first. Distance is the
+    /// haversine great-circle distance in meters.
+    pub async fn find(query: &NearbyQuery, executor: impl PgExecutor<'_>) -> Result<Vec<NearbyParkingLot>> {
+        let (latitude_delta, longitude_delta) = query.bounding_box();
+        let parking_lot = sqlx::query_as!(
+            NearbyParkingLot,
+            r#"
+                with candidate as (
+                    select pl.id,
+                        pl.area_name,
+                        pl.address,
+                        pl.image_url,
+                        pl.car_cost,
+                        pl.motor_cost,
+                        pl.latitude,
+                        pl.longitude,
+                        2 * $7::float8 * asin(sqrt(
+                            power(sin(radians(pl.latitude - $1) / 2), 2) +
+                            cos(radians($1)) * cos(radians(pl.latitude)) * power(sin(radians(pl.longitude - $2) / 2), 2)
+                        )) as distance
+                    from parking_lot pl
+                    where pl.latitude between $1 - $5 and $1 + $5 and
+                        pl.longitude between $2 - $6 and $2 + $6 and
+                        pl.deleted_at is null
+                )
+                select c.id,
+                    c.area_name,
+                    c.address,
+                    c.image_url,
+                    c.car_cost,
+                    c.motor_cost,
+                    c.latitude as "latitude!",
+                    c.longitude as "longitude!",
+                    c.distance as "distance!",
+                    parking_lot_is_open(c.id, now() at time zone 'UTC') as is_open,
+                    count(ph.id) filter (where ph.vehicle_type = 'car') as "active_car!",
+                    count(ph.id) filter (where ph.vehicle_type = 'motor') as "active_motor!"
+                from candidate c
+                left join parking_history ph on ph.parking_lot_id = c.id and ph.ticket_status in ('active', 'default') and ph.deleted_at is null
+                where c.distance <= $3
+                group by c.id, c.area_name, c.address, c.image_url, c.car_cost, c.motor_cost, c.latitude, c.longitude, c.distance
+                order by c.distance, c.id
+                limit $4
+            "#,
+            query.latitude,
+            query.longitude,
+            query.radius,
+            query.take,
+            latitude_delta,
+            longitude_delta,
+            EARTH_RADIUS_METERS
+        )
+            .fetch_all(executor)
             .await?;
 
         Ok(parking_lot)
     }
 }
 
+/// Accepted values of the directory `sort` parameter, ties are broken by id.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum DirectorySort {
+    #[default]
+    #[serde(rename = "name")]
+    NameAsc,
+    #[serde(rename = "-name")]
+    NameDesc,
+    #[serde(rename = "price")]
+    PriceAsc,
+    #[serde(rename = "-price")]
+    PriceDesc,
+}
+
+impl DirectorySort {
+    fn order_by(&self) -> &'static str {
+        match self {
+            DirectorySort::NameAsc => " order by pl.area_name asc, pl.id asc",
+            DirectorySort::NameDesc => " order by pl.area_name desc, pl.id desc",
+            DirectorySort::PriceAsc => " order by price asc, pl.id asc",
+            DirectorySort::PriceDesc => " order by price desc, pl.id desc",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectoryQuery {
+    pub vehicle_category_id: Option<Uuid>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub open_now: Option<bool>,
+    pub area: Option<String>,
+    pub sort: DirectorySort,
+    pub take: Option<i64>,
+    pub skip: Option<i64>,
+}
+
+impl DirectoryQuery {
+    /// The hourly rate a driver would pay under the tariff in force: the rate
+    /// of the requested category, otherwise the cheapest rate of a category
+    /// the lot accepts. Null when the lot would turn the vehicle away.
+    fn push_price(&self, builder: &mut QueryBuilder<'static, Postgres>) {
+        builder.push(
+            r#"(
+                select min(r.cost)
+                from parking_lot_rate r
+                join parking_lot_vehicle_category a on a.parking_lot_id = r.parking_lot_id and
+                    a.vehicle_category_id = r.vehicle_category_id
+                where r.tariff_id = (
+                    select t.id from parking_lot_tariff t
+                    where t.parking_lot_id = pl.id and t.effective_from <= now() at time zone 'UTC'
+                    order by t.effective_from desc
+                    limit 1
+                )"#,
+        );
+        if let Some(vehicle_category_id) = self.vehicle_category_id {
+            builder
+                .push(" and r.vehicle_category_id = ")
+                .push_bind(vehicle_category_id);
+        }
+        builder.push(")");
+    }
+}
+
+impl QueryFilter for DirectoryQuery {
+    const FROM: &'static str = "from parking_lot pl";
+
+    fn push_filters(&self, builder: &mut QueryBuilder<'static, Postgres>) {
+        builder.push(" and pl.deleted_at is null");
+        // Only lots that accept the vehicle and price it are listed.
+        if let Some(vehicle_category_id) = self.vehicle_category_id {
+            builder
+                .push(" and exists (select 1 from parking_lot_vehicle_category a where a.parking_lot_id = pl.id and a.vehicle_category_id = ")
+                .push_bind(vehicle_category_id)
+                .push(")");
+        }
+        builder.push(" and ");
+        self.push_price(builder);
+        builder.push(" is not null");
+        if let Some(min_price) = self.min_price {
+            builder.push(" and ");
+            self.push_price(builder);
+            builder.push(" >= ").push_bind(min_price);
+        }
+        if let Some(max_price) = self.max_price {
+            builder.push(" and ");
+            self.push_price(builder);
+            builder.push(" <= ").push_bind(max_price);
+        }
+        match self.open_now {
+            Some(true) => {
+                builder.push(" and parking_lot_is_open(pl.id, now() at time zone 'UTC')");
+            }
+            Some(false) => {
+                builder.push(" and not parking_lot_is_open(pl.id, now() at time zone 'UTC')");
+            }
+            None => {}
+        }
+        if let Some(area) = &self.area {
+            let pattern = format!(
+                "%{}%",
+                area.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            );
+            builder
+                .push(" and (pl.area_name ilike ")
+                .push_bind(pattern.clone())
+                .push(" or pl.address ilike ")
+                .push_bind(pattern)
+                .push(")");
+        }
+    }
+}
+
+/// What drivers get to see of a lot, without the owner or keeper details.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PublicParkingLot {
+    pub id: Uuid,
+    pub area_name: String,
+    pub address: String,
+    pub image_url: String,
+    pub car_cost: f64,
+    pub motor_cost: f64,
+    pub price: f64,
+    pub lost_ticket_fee: f64,
+    pub overstay_fee: f64,
+    pub max_stay_hours: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: String,
+    pub is_open: Option<bool>,
+}
+
+impl PublicParkingLot {
+    pub async fn directory(executor: impl PgExecutor<'_>, payload: DirectoryQuery) -> Result<Vec<PublicParkingLot>> {
+        let mut query = QueryBuilder::new(
+            "select pl.id, pl.area_name, pl.address, pl.image_url, pl.car_cost, pl.motor_cost, ",
+        );
+        payload.push_price(&mut query);
+        query.push(
+            r#" as price,
+                pl.lost_ticket_fee,
+                pl.overstay_fee,
+                pl.max_stay_hours,
+                pl.latitude,
+                pl.longitude,
+                pl.timezone,
+                parking_lot_is_open(pl.id, now() at time zone 'UTC') as is_open"#,
+        );
+        payload.push_from(&mut query);
+        query
+            .push(payload.sort.order_by())
+            .push(" limit ")
+            .push_bind(payload.take)
+            .push(" offset ")
+            .push_bind(payload.skip);
+
+        let parking_lot = query
+            .build_query_as::<PublicParkingLot>()
+            .fetch_all(executor)
+            .await?;
+
+        Ok(parking_lot)
+    }
+
+    pub async fn count(executor: impl PgExecutor<'_>, payload: DirectoryQuery) -> Result<SqlxCount> {
+        let data = payload
+            .count()
+            .build_query_scalar::<i64>()
+            .fetch_one(executor)
+            .await?;
+
+        Ok(SqlxCount { data: Some(data) })
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateParkingLot {
@@ -142,10 +646,16 @@ pub struct UpdateParkingLot {
     pub owner_id: Option<Uuid>,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub lost_ticket_fee: Option<f64>,
+    pub overstay_fee: Option<f64>,
+    pub max_stay_hours: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: Option<String>,
 }
 
 impl UpdateParkingLot {
-    pub async fn update(self, id: Uuid, pool: &Pool<Postgres>) -> Result<ParkingLot> {
+    pub async fn update(self, id: Uuid, executor: impl PgExecutor<'_>) -> Result<ParkingLot> {
         let user = sqlx::query_as!(
             ParkingLot, 
             r#"
@@ -157,9 +667,15 @@ impl UpdateParkingLot {
                         motor_cost = coalesce($5, "parking_lot".motor_cost), 
                         owner_id = coalesce($6, "parking_lot".owner_id), 
                         created_at = coalesce($7, "parking_lot".created_at), 
-                        updated_at = coalesce($8, "parking_lot".updated_at)
-                    where id = $9
-                    returning id, area_name, address, image_url, car_cost, motor_cost, owner_id, created_at, updated_at
+                        updated_at = coalesce($8, "parking_lot".updated_at),
+                        lost_ticket_fee = coalesce($9, "parking_lot".lost_ticket_fee),
+                        overstay_fee = coalesce($10, "parking_lot".overstay_fee),
+                        max_stay_hours = coalesce($11, "parking_lot".max_stay_hours),
+                        latitude = coalesce($12, "parking_lot".latitude),
+                        longitude = coalesce($13, "parking_lot".longitude),
+                        timezone = coalesce($14, "parking_lot".timezone)
+                    where id = $15 and deleted_at is null
+                    returning id, area_name, address, image_url, car_cost, motor_cost, owner_id, created_at, updated_at, lost_ticket_fee, overstay_fee, max_stay_hours, latitude, longitude, timezone, deleted_at
             "#,  
             self.area_name,
             self.address,
@@ -169,9 +685,15 @@ impl UpdateParkingLot {
             self.owner_id,
             self.created_at,
             self.updated_at,
+            self.lost_ticket_fee,
+            self.overstay_fee,
+            self.max_stay_hours,
+            self.latitude,
+            self.longitude,
+            self.timezone,
             id
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
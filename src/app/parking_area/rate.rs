@@ -0,0 +1,168 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::error::aggregate::Result;
+
+/// The price of one vehicle category under one tariff version.
+#[derive(Debug, Serialize)]
+pub struct ParkingLotRate {
+    pub id: Uuid,
+    pub tariff_id: Uuid,
+    pub vehicle_category_id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub cost: f64,
+}
+
+/// A category the lot lets in, `cost` is empty when the tariff in force has
+/// no rate for it.
+#[derive(Debug, Serialize)]
+pub struct AcceptedCategory {
+    pub vehicle_category_id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub cost: Option<f64>,
+}
+
+impl ParkingLotRate {
+    /// Writes the rates of a freshly saved tariff, `rates` pairs a category
+    /// with its cost.
+    pub async fn save_for_tariff(
+        tariff_id: Uuid,
+        parking_lot_id: Uuid,
+        rates: &[(Uuid, f64)],
+        created_at: NaiveDateTime,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<ParkingLotRate>> {
+        for (vehicle_category_id, cost) in rates {
+            sqlx::query!(
+                r#"
+                    insert into "parking_lot_rate" (id, tariff_id, parking_lot_id, vehicle_category_id, cost, created_at)
+                    values ($1, $2, $3, $4, $5, $6)
+                "#,
+                Uuid::new_v4(),
+                tariff_id,
+                parking_lot_id,
+                vehicle_category_id,
+                cost,
+                created_at
+            )
+                .execute(&mut *connection)
+                .await?;
+        }
+
+        Self::find_by_tariffs(&[tariff_id], &mut *connection).await
+    }
+
+    pub async fn find_by_tariffs(tariff_ids: &[Uuid], executor: impl PgExecutor<'_>) -> Result<Vec<ParkingLotRate>> {
+        let rate = sqlx::query_as!(
+            ParkingLotRate,
+            r#"
+                select r.id, r.tariff_id, r.vehicle_category_id, c.code, c.name, r.cost
+                from "parking_lot_rate" r
+                join "vehicle_category" c on c.id = r.vehicle_category_id
+                where r.tariff_id = any($1)
+                order by c.name
+            "#,
+            tariff_ids
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(rate)
+    }
+
+    pub async fn find_one(tariff_id: Uuid, vehicle_category_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Option<ParkingLotRate>> {
+        let rate = sqlx::query_as!(
+            ParkingLotRate,
+            r#"
+                select r.id, r.tariff_id, r.vehicle_category_id, c.code, c.name, r.cost
+                from "parking_lot_rate" r
+                join "vehicle_category" c on c.id = r.vehicle_category_id
+                where r.tariff_id = $1 and r.vehicle_category_id = $2
+            "#,
+            tariff_id,
+            vehicle_category_id
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        Ok(rate)
+    }
+}
+
+impl AcceptedCategory {
+    pub async fn find_by_parking_lot(parking_lot_id: Uuid, at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Vec<AcceptedCategory>> {
+        let category = sqlx::query_as!(
+            AcceptedCategory,
+            r#"
+                select c.id as vehicle_category_id, c.code, c.name, r.cost as "cost?"
+                from "parking_lot_vehicle_category" pc
+                join "vehicle_category" c on c.id = pc.vehicle_category_id
+                left join "parking_lot_rate" r on r.vehicle_category_id = c.id and r.tariff_id = (
+                    select t.id
+                    from "parking_lot_tariff" t
+                    where t.parking_lot_id = $1 and t.effective_from <= $2
+                    order by t.effective_from desc
+                    limit 1
+                )
+                where pc.parking_lot_id = $1
+                order by c.name
+            "#,
+            parking_lot_id,
+            at
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(category)
+    }
+
+    pub async fn is_accepted(parking_lot_id: Uuid, vehicle_category_id: Uuid, executor: impl PgExecutor<'_>) -> Result<bool> {
+        let accepted = sqlx::query_scalar!(
+            r#"
+                select exists(
+                    select 1 from "parking_lot_vehicle_category"
+                    where parking_lot_id = $1 and vehicle_category_id = $2
+                ) as "accepted!"
+            "#,
+            parking_lot_id,
+            vehicle_category_id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(accepted)
+    }
+
+    /// The given categories become everything the lot lets in.
+    pub async fn replace(
+        parking_lot_id: Uuid,
+        vehicle_category_ids: &[Uuid],
+        at: NaiveDateTime,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<AcceptedCategory>> {
+        sqlx::query!(
+            r#"delete from "parking_lot_vehicle_category" where parking_lot_id = $1"#,
+            parking_lot_id
+        )
+            .execute(&mut *connection)
+            .await?;
+
+        sqlx::query!(
+            r#"
+                insert into "parking_lot_vehicle_category" (parking_lot_id, vehicle_category_id, created_at)
+                select $1, id, $3 from unnest($2::uuid[]) as id
+            "#,
+            parking_lot_id,
+            vehicle_category_ids,
+            at
+        )
+            .execute(&mut *connection)
+            .await?;
+
+        Self::find_by_parking_lot(parking_lot_id, at, &mut *connection).await
+    }
+}
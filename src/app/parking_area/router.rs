@@ -1,59 +1,154 @@
-use std::path::Path as StdPath;
-
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     middleware,
-    routing::{get, patch, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
-use chrono::{NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Pool, Postgres};
+use sqlx::{PgConnection, PgPool, Pool, Postgres};
 use tracing::debug;
 use uuid::Uuid;
 
 use crate::{
-    app::user::{Role, User},
+    app::{
+        file_upload::FileUpload,
+        keeper_shift::{KeeperShift, ShiftQuery, ShiftStatus},
+        parking_history::{ParkingHistory, VehicleType},
+        report::{validate_timezone, DEFAULT_TIMEZONE},
+        user::{Role, User},
+        vehicle::category::VehicleCategory,
+    },
     error::aggregate::{Error, Result},
-    extractor::{app_body::Body, app_json::AppSuccess},
+    extractor::{app_body::Body, app_json::AppSuccess, current_user::CurrentUser},
     middleware::base::print_request_body,
+    query_filter::tolerate_typos,
+    unit_of_work::UnitOfWork,
 };
 
-use super::{ParkingLot, ParkingLotWithCountOfKeeper, UpdateParkingLot};
+use super::{
+    assignment::{KeeperAssignment, KeeperAssignmentHistory},
+    gallery::{GalleryImage, ParkingLotImage, MAX_GALLERY_IMAGE},
+    layout::{ParkingSpot, ParkingZone, SpotAllocation, SpotKind, SpotStatus, UpdateParkingSpot, ZoneOccupancy},
+    schedule::{is_open, Closure, OpeningHour, SpecialDay},
+    rate::{AcceptedCategory, ParkingLotRate},
+    tariff::{Tariff, TariffStatus},
+    DirectoryQuery, DirectorySort, NearbyParkingLot, NearbyQuery, ParkingLot, ParkingLotSearchQuery, ParkingLotWithCountOfKeeper,
+    PublicParkingLot, SearchedParkingLot, UpdateParkingLot,
+};
 
 pub fn build(pool: Pool<Postgres>) -> Router {
     let router = Router::new()
-        .route("/", post(create))
+        .route("/", post(create).get(directory))
         .route("/:id", patch(update).get(detail))
         .route("/owner/:id", get(get_by_owner))
+        .route("/search", get(search))
+        .route("/nearby", get(nearby))
+        .route("/:id/images", get(gallery).put(replace_gallery))
+        .route("/:id/keepers", post(add_keepers))
+        .route("/:id/keepers/history", get(keeper_history))
+        .route("/:id/keepers/:keeper_id", delete(remove_keeper))
+        .route("/:id/tariffs", get(tariffs).post(create_tariff))
+        .route("/:id/tariffs/:tariff_id", delete(delete_tariff))
+        .route("/:id/vehicle-categories", get(vehicle_categories).put(replace_vehicle_categories))
+        .route("/:id/zones", get(zones).post(create_zone))
+        .route("/:id/zones/:zone_id/spots", get(spots).post(create_spots))
+        .route("/:id/spots/:spot_id", patch(update_spot))
+        .route("/:id/schedule", get(schedule))
+        .route("/:id/opening-hours", put(replace_opening_hours))
+        .route("/:id/special-day", post(create_special_day))
+        .route("/:id/special-day/:date", delete(delete_special_day))
+        .route("/:id/closure", post(create_closure))
+        .route("/:id/closure/:closure_id", delete(delete_closure))
+        .route("/:id/archive", post(archive))
+        .route("/:id/restore", post(restore))
         .layer(middleware::from_fn(print_request_body))
         .with_state(pool);
 
     Router::new().nest("/parking-lot", router)
 }
 
+/// Categories every tariff prices, through `car_cost` and `motor_cost`.
+const BASE_CATEGORY: [&str; 2] = ["car", "motor"];
+
+fn validate_penalty(
+    lost_ticket_fee: Option<f64>,
+    overstay_fee: Option<f64>,
+    max_stay_hours: Option<i32>,
+) -> Result<()> {
+    if lost_ticket_fee.is_some_and(|fee| fee < 0.0) || overstay_fee.is_some_and(|fee| fee < 0.0) {
+        return Err(Error::BadRequest("Penalty fee cannot be negative".to_string()));
+    }
+
+    if max_stay_hours.is_some_and(|hours| hours < 1) {
+        return Err(Error::BadRequest(
+            "Maximum stay must be at least one hour".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_coordinates(latitude: Option<f64>, longitude: Option<f64>) -> Result<()> {
+    match (latitude, longitude) {
+        (None, None) => Ok(()),
+        (Some(latitude), Some(longitude)) => {
+            if !(-90.0..=90.0).contains(&latitude) {
+                return Err(Error::BadRequest(
+                    "Latitude must be between -90 and 90".to_string(),
+                ));
+            }
+            if !(-180.0..=180.0).contains(&longitude) {
+                return Err(Error::BadRequest(
+                    "Longitude must be between -180 and 180".to_string(),
+                ));
+            }
+            Ok(())
+        }
+        _ => Err(Error::BadRequest(
+            "Latitude and longitude must be provided together".to_string(),
+        )),
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct CreateParkingLotPayload {
     area_name: String,
     address: String,
-    file_name: Option<String>,
+    image_ids: Option<Vec<Uuid>>,
+    cover_image_id: Option<Uuid>,
     car_cost: f64,
     motor_cost: f64,
+    rates: Option<Vec<RatePayload>>,
+    vehicle_categories: Option<Vec<String>>,
     owner_id: Uuid,
+    lost_ticket_fee: Option<f64>,
+    overstay_fee: Option<f64>,
+    max_stay_hours: Option<i32>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    timezone: Option<String>,
 }
 
 impl CreateParkingLotPayload {
-    fn into_parking_lot(self) -> ParkingLot {
+    fn into_parking_lot(self, image_url: String) -> ParkingLot {
         ParkingLot {
             id: Uuid::new_v4(),
             area_name: self.area_name,
             address: self.address,
-            image_url: "some url".to_string(),
+            image_url,
             car_cost: self.car_cost,
             motor_cost: self.motor_cost,
             owner_id: self.owner_id,
             created_at: Some(Utc::now().naive_utc()),
             updated_at: None,
+            lost_ticket_fee: self.lost_ticket_fee.unwrap_or(0.0),
+            overstay_fee: self.overstay_fee.unwrap_or(0.0),
+            max_stay_hours: self.max_stay_hours,
+            latitude: self.latitude,
+            longitude: self.longitude,
+            timezone: self.timezone.unwrap_or_else(|| String::from(DEFAULT_TIMEZONE)),
+            deleted_at: None,
         }
     }
 }
@@ -69,40 +164,131 @@ async fn create(
         ));
     }
 
-    // let dir = format!("./public/files/{}", payload.file_name);
-    // let path = StdPath::new(&dir);
-    // if !path.exists() {
-    //     return Err(Error::BadRequest("Image not found".to_string()));
-    // }
+    validate_penalty(
+        payload.lost_ticket_fee,
+        payload.overstay_fee,
+        payload.max_stay_hours,
+    )?;
+    validate_coordinates(payload.latitude, payload.longitude)?;
+    validate_costs(Some(payload.car_cost), Some(payload.motor_cost))?;
+    if let Some(timezone) = &payload.timezone {
+        validate_timezone(timezone, &pool).await?;
+    }
+
+    let image_ids = payload.image_ids.clone().unwrap_or_default();
+    let (file, cover_id) = resolve_gallery(&image_ids, payload.cover_image_id, &pool).await?;
+
+    let rates = resolve_rates(None, Some(payload.rates.as_deref().unwrap_or_default()), &pool).await?;
+    let rates = full_rates(payload.car_cost, payload.motor_cost, &rates, &pool).await?;
+    let accepted = payload
+        .vehicle_categories
+        .clone()
+        .unwrap_or_else(|| BASE_CATEGORY.map(String::from).to_vec());
+    let accepted = resolve_accepted(&accepted, &rates, &pool).await?;
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let parking_lot = payload.into_parking_lot(cover_url(&file, cover_id));
+    let parking_lot = parking_lot.save(uow.connection()).await?;
+    ParkingLotImage::replace(
+        parking_lot.id,
+        &image_ids,
+        cover_id,
+        Utc::now().naive_utc(),
+        uow.connection(),
+    )
+    .await?;
+    let tariff = new_tariff(&parking_lot, parking_lot.car_cost, parking_lot.motor_cost, None);
+    save_tariff(tariff, &rates, uow.connection()).await?;
+    AcceptedCategory::replace(parking_lot.id, &accepted, Utc::now().naive_utc(), uow.connection())
+        .await?;
+    uow.commit().await?;
 
-    let parking_lot = payload.into_parking_lot();
-    let parking_lot = parking_lot.save(&pool).await?;
     Ok(AppSuccess(parking_lot))
 }
 
+/// Checks the photos of a gallery and picks its cover, the first photo unless
+/// another one is named.
+async fn resolve_gallery(
+    image_ids: &[Uuid],
+    cover_image_id: Option<Uuid>,
+    pool: &PgPool,
+) -> Result<(Vec<FileUpload>, Option<Uuid>)> {
+    if image_ids.len() > MAX_GALLERY_IMAGE {
+        return Err(Error::BadRequest(format!(
+            "A parking lot can have at most {} images",
+            MAX_GALLERY_IMAGE
+        )));
+    }
+    for (index, id) in image_ids.iter().enumerate() {
+        if image_ids[..index].contains(id) {
+            return Err(Error::BadRequest(format!(
+                "Image {} is given more than once",
+                id
+            )));
+        }
+    }
+
+    let file = FileUpload::find_many(image_ids, pool).await?;
+    if let Some(file) = file.iter().find(|file| !file.is_image()) {
+        return Err(Error::BadRequest(format!("File {} is not an image", file.id)));
+    }
+
+    let cover_id = match cover_image_id {
+        Some(id) if !image_ids.contains(&id) => {
+            return Err(Error::BadRequest(
+                "Cover image must be one of the images".to_string(),
+            ));
+        }
+        Some(id) => Some(id),
+        None => image_ids.first().copied(),
+    };
+
+    Ok((file, cover_id))
+}
+
+fn cover_url(file: &[FileUpload], cover_id: Option<Uuid>) -> String {
+    file.iter()
+        .find(|file| Some(file.id) == cover_id)
+        .map(FileUpload::url)
+        .unwrap_or_default()
+}
+
 #[derive(Serialize, Deserialize)]
 struct UpdateParkingLotPayload {
     area_name: Option<String>,
     address: Option<String>,
-    file_name: Option<String>,
+    image_ids: Option<Vec<Uuid>>,
+    cover_image_id: Option<Uuid>,
     car_cost: Option<f64>,
     motor_cost: Option<f64>,
     owner_id: Option<Uuid>,
     park_keeper_ids: Option<Vec<Uuid>>,
+    lost_ticket_fee: Option<f64>,
+    overstay_fee: Option<f64>,
+    max_stay_hours: Option<i32>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    timezone: Option<String>,
 }
 
 impl UpdateParkingLotPayload {
-    fn into_update_parking_lot(self) -> UpdateParkingLot {
+    fn into_update_parking_lot(self, image_url: Option<String>) -> UpdateParkingLot {
         UpdateParkingLot {
             id: None,
             area_name: self.area_name,
             address: self.address,
-            image_url: self.file_name,
+            image_url,
             car_cost: self.car_cost,
             motor_cost: self.motor_cost,
             owner_id: self.owner_id,
             created_at: None,
             updated_at: Some(Utc::now().naive_utc()),
+            lost_ticket_fee: self.lost_ticket_fee,
+            overstay_fee: self.overstay_fee,
+            max_stay_hours: self.max_stay_hours,
+            latitude: self.latitude,
+            longitude: self.longitude,
+            timezone: self.timezone,
         }
     }
 }
@@ -124,29 +310,102 @@ async fn update(
         None => {}
     }
 
-    match &payload.file_name {
-        Some(file_name) => {
-            let dir = format!("./public/files/{}", *file_name);
-            let path = StdPath::new(&dir);
-            if !path.exists() {
-                return Err(Error::BadRequest("Image not found".to_string()));
-            }
+    validate_penalty(
+        payload.lost_ticket_fee,
+        payload.overstay_fee,
+        payload.max_stay_hours,
+    )?;
+    validate_coordinates(payload.latitude, payload.longitude)?;
+    validate_costs(payload.car_cost, payload.motor_cost)?;
+    if let Some(timezone) = &payload.timezone {
+        validate_timezone(timezone, &pool).await?;
+    }
+
+    // Naming only a new cover keeps the photos as they are.
+    let gallery = match (&payload.image_ids, payload.cover_image_id) {
+        (Some(image_ids), cover_image_id) => Some((image_ids.clone(), cover_image_id)),
+        (None, Some(cover_image_id)) => {
+            let image_ids = ParkingLotImage::find_by_parking_lot(id, &pool)
+                .await?
+                .into_iter()
+                .map(|image| image.file_upload_id)
+                .collect();
+            Some((image_ids, Some(cover_image_id)))
         }
-        None => {}
+        (None, None) => None,
+    };
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+
+    let mut image_url = None;
+    if let Some((image_ids, cover_image_id)) = gallery {
+        ParkingLot::find_one(id, uow.connection()).await?;
+        let (file, cover_id) = resolve_gallery(&image_ids, cover_image_id, &pool).await?;
+        ParkingLotImage::replace(id, &image_ids, cover_id, Utc::now().naive_utc(), uow.connection())
+            .await?;
+        image_url = Some(cover_url(&file, cover_id));
     }
 
+    // The given keepers become the whole crew of the lot.
     match &payload.park_keeper_ids {
         Some(ids) => {
-            if ids.len() > 0 {
-                User::remove_parking_lot(id, &pool).await?;
-                User::update_parking_lot(id, ids.clone(), &pool).await?;
+            if !ids.is_empty() {
+                let parking_lot = ParkingLot::find_one_for_update(id, uow.connection()).await?;
+                let owner_id = payload.owner_id.unwrap_or(parking_lot.owner_id);
+                let keeper = validate_keepers(ids, owner_id, uow.connection()).await?;
+
+                let current: Vec<Uuid> = ParkingLot::detail(id, uow.connection())
+                    .await?
+                    .into_iter()
+                    .filter_map(|item| item.keeper_id)
+                    .collect();
+                let leaving: Vec<Uuid> = current
+                    .iter()
+                    .filter(|keeper_id| !ids.contains(keeper_id))
+                    .copied()
+                    .collect();
+                let joining: Vec<Uuid> = keeper
+                    .iter()
+                    .filter(|keeper| keeper.parking_lot_id != Some(id))
+                    .map(|keeper| keeper.id)
+                    .collect();
+
+                let moving: Vec<Uuid> = keeper
+                    .iter()
+                    .filter(|keeper| keeper.parking_lot_id.is_some_and(|lot| lot != id))
+                    .map(|keeper| keeper.id)
+                    .chain(leaving.iter().copied())
+                    .collect();
+                ensure_off_shift(owner_id, &moving, uow.connection()).await?;
+
+                let now = Utc::now().naive_utc();
+                release_keepers(id, &leaving, now, uow.connection()).await?;
+                assign_keepers(id, owner_id, &joining, now, uow.connection()).await?;
             }
         }
         None => {}
     }
 
-    let parking_lot = payload.into_update_parking_lot();
-    let parking_lot = parking_lot.update(id, &pool).await?;
+    // New prices start a new tariff version right away, tickets already
+    // checked in keep the one they were pinned to.
+    if payload.car_cost.is_some() || payload.motor_cost.is_some() {
+        let parking_lot = ParkingLot::find_one(id, uow.connection()).await?;
+        let tariff = new_tariff(
+            &parking_lot,
+            payload.car_cost.unwrap_or(parking_lot.car_cost),
+            payload.motor_cost.unwrap_or(parking_lot.motor_cost),
+            payload.owner_id,
+        );
+        let rates = resolve_rates(Some(id), None, &pool).await?;
+        let rates = full_rates(tariff.car_cost, tariff.motor_cost, &rates, &pool).await?;
+        save_tariff(tariff, &rates, uow.connection()).await?;
+    }
+
+    let parking_lot = payload.into_update_parking_lot(image_url);
+    let parking_lot = parking_lot.update(id, uow.connection()).await?;
+
+    uow.commit().await?;
+
     Ok(AppSuccess(parking_lot))
 }
 
@@ -161,6 +420,14 @@ pub struct DetailParkingLot {
     pub owner_id: Option<Uuid>,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub lost_ticket_fee: Option<f64>,
+    pub overstay_fee: Option<f64>,
+    pub max_stay_hours: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: Option<String>,
+    pub is_open: Option<bool>,
+    pub images: Vec<GalleryImage>,
     pub keepers: Option<Vec<KeeperOnDetailParkingLot>>,
 }
 
@@ -201,6 +468,14 @@ async fn detail(
         owner_id: None,
         created_at: None,
         updated_at: None,
+        lost_ticket_fee: None,
+        overstay_fee: None,
+        max_stay_hours: None,
+        latitude: None,
+        longitude: None,
+        timezone: None,
+        is_open: None,
+        images: Vec::new(),
         keepers: Some(Vec::new()), // Initialize keepers as an empty Vec
     };
 
@@ -215,10 +490,26 @@ async fn detail(
             owner_id: Some(data.owner_id),
             created_at: data.created_at,
             updated_at: data.updated_at,
+            lost_ticket_fee: Some(data.lost_ticket_fee),
+            overstay_fee: Some(data.overstay_fee),
+            max_stay_hours: data.max_stay_hours,
+            latitude: data.latitude,
+            longitude: data.longitude,
+            timezone: Some(data.timezone),
+            is_open: data.is_open,
+            images: Vec::new(),
             keepers: Some(Vec::new()), // Initialize keepers as an empty Vec
         };
     }
 
+    if let Some(id) = detail.id {
+        detail.images = ParkingLotImage::find_by_parking_lot(id, &pool)
+            .await?
+            .into_iter()
+            .map(ParkingLotImage::into_gallery_image)
+            .collect();
+    }
+
     // Set the `keepers` field to `keeper_info` if it's not empty
     if !keeper_info.is_empty() {
         detail.keepers = Some(keeper_info);
@@ -236,3 +527,1247 @@ async fn get_by_owner(
     let parking_lot = ParkingLot::find_by_owner(owner_id, &pool).await?;
     Ok(AppSuccess(parking_lot))
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ParkingLotSearchPayload {
+    pub q: String,
+    pub take: Option<i64>,
+    pub skip: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct SearchAggregate {
+    meta: SearchMeta,
+    parking_lot: Vec<SearchedParkingLot>,
+}
+
+#[derive(Serialize)]
+struct SearchMeta {
+    total_data: i64,
+    query: ParkingLotSearchPayload,
+}
+
+/// Drivers search every lot by area name or street. Owners and keepers only see
+/// the lots of their own business.
+async fn search(
+    State(pool): State<PgPool>,
+    current_user: CurrentUser,
+    Query(payload): Query<ParkingLotSearchPayload>,
+) -> Result<AppSuccess<SearchAggregate>> {
+    let search = payload.q.trim().to_string();
+    if search.chars().count() < 2 {
+        return Err(Error::BadRequest(
+            "Search must be at least 2 characters".to_string(),
+        ));
+    }
+
+    let caller = User::find_one(current_user.sub, &pool).await?;
+    let owner_id = match caller.role {
+        Role::ParkOwner => Some(caller.id),
+        Role::ParkKeeper => Some(caller.owner_id.ok_or(Error::Unauthorize(
+            "Keeper is not assigned to an owner".to_string(),
+        ))?),
+        Role::Easypark | Role::Admin | Role::Default => None,
+    };
+
+    let query = ParkingLotSearchQuery {
+        search,
+        owner_id,
+        take: Some(payload.take.unwrap_or(20).clamp(1, 100)),
+        skip: payload.skip,
+    };
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    tolerate_typos(uow.connection()).await?;
+    let (total_data, parking_lot) = SearchedParkingLot::search(&query, uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(SearchAggregate {
+        meta: SearchMeta {
+            total_data,
+            query: payload,
+        },
+        parking_lot,
+    }))
+}
+
+#[derive(Deserialize)]
+struct NearbyPayload {
+    lat: f64,
+    lng: f64,
+    radius: Option<f64>,
+    take: Option<i64>,
+}
+
+/// Radius is in meters, 2 km by default and at most 50 km.
+async fn nearby(
+    State(pool): State<PgPool>,
+    Query(payload): Query<NearbyPayload>,
+) -> Result<AppSuccess<Vec<NearbyParkingLot>>> {
+    validate_coordinates(Some(payload.lat), Some(payload.lng))?;
+
+    let radius = payload.radius.unwrap_or(2000.0);
+    if radius <= 0.0 || radius > 50_000.0 {
+        return Err(Error::BadRequest(
+            "Radius must be between 0 and 50000 meters".to_string(),
+        ));
+    }
+
+    let query = NearbyQuery {
+        latitude: payload.lat,
+        longitude: payload.lng,
+        radius,
+        take: payload.take.unwrap_or(20).clamp(1, 100),
+    };
+    let parking_lot = NearbyParkingLot::find(&query, &pool).await?;
+
+    Ok(AppSuccess(parking_lot))
+}
+
+async fn gallery(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<Vec<GalleryImage>>> {
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let image = ParkingLotImage::find_by_parking_lot(parking_lot.id, &pool).await?;
+
+    Ok(AppSuccess(
+        image
+            .into_iter()
+            .map(ParkingLotImage::into_gallery_image)
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ReplaceGalleryPayload {
+    image_ids: Vec<Uuid>,
+    cover_image_id: Option<Uuid>,
+}
+
+/// Images are uploaded through `/file-upload` first and referenced by id here,
+/// in the order they should be shown. An empty list removes every photo.
+async fn replace_gallery(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<ReplaceGalleryPayload>,
+) -> Result<AppSuccess<Vec<GalleryImage>>> {
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let (file, cover_id) =
+        resolve_gallery(&payload.image_ids, payload.cover_image_id, &pool).await?;
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let image = ParkingLotImage::replace(
+        parking_lot.id,
+        &payload.image_ids,
+        cover_id,
+        Utc::now().naive_utc(),
+        uow.connection(),
+    )
+    .await?;
+    ParkingLot::update_image_url(parking_lot.id, cover_url(&file, cover_id), uow.connection())
+        .await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(
+        image
+            .into_iter()
+            .map(ParkingLotImage::into_gallery_image)
+            .collect(),
+    ))
+}
+
+#[derive(Serialize)]
+struct Schedule {
+    timezone: String,
+    is_open: bool,
+    opening_hours: Vec<OpeningHour>,
+    special_days: Vec<SpecialDay>,
+    closures: Vec<Closure>,
+}
+
+async fn schedule(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<Schedule>> {
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let now = Utc::now().naive_utc();
+
+    Ok(AppSuccess(Schedule {
+        is_open: is_open(parking_lot.id, now, &pool).await?,
+        opening_hours: OpeningHour::find_by_parking_lot(parking_lot.id, &pool).await?,
+        special_days: SpecialDay::find_upcoming(parking_lot.id, &parking_lot.timezone, &pool).await?,
+        closures: Closure::find_upcoming(parking_lot.id, now, &pool).await?,
+        timezone: parking_lot.timezone,
+    }))
+}
+
+#[derive(Deserialize)]
+struct OpeningHourPayload {
+    day_of_week: i16,
+    open_time: NaiveTime,
+    close_time: NaiveTime,
+}
+
+#[derive(Deserialize)]
+struct ReplaceOpeningHoursPayload {
+    opening_hours: Vec<OpeningHourPayload>,
+}
+
+/// Times are local to the lot. An empty list opens the lot around the clock.
+async fn replace_opening_hours(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<ReplaceOpeningHoursPayload>,
+) -> Result<AppSuccess<Vec<OpeningHour>>> {
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+
+    let mut days = Vec::with_capacity(payload.opening_hours.len());
+    for hour in &payload.opening_hours {
+        if !(1..=7).contains(&hour.day_of_week) {
+            return Err(Error::BadRequest(
+                "day_of_week must be between 1 (Monday) and 7 (Sunday)".to_string(),
+            ));
+        }
+        if days.contains(&hour.day_of_week) {
+            return Err(Error::BadRequest(format!(
+                "day_of_week {} is given more than once",
+                hour.day_of_week
+            )));
+        }
+        days.push(hour.day_of_week);
+    }
+
+    let now = Utc::now().naive_utc();
+    let opening_hours = payload
+        .opening_hours
+        .into_iter()
+        .map(|hour| OpeningHour {
+            id: Uuid::new_v4(),
+            parking_lot_id: parking_lot.id,
+            day_of_week: hour.day_of_week,
+            open_time: hour.open_time,
+            close_time: hour.close_time,
+            created_at: Some(now),
+        })
+        .collect();
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let opening_hours = OpeningHour::replace(parking_lot.id, opening_hours, uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(opening_hours))
+}
+
+#[derive(Deserialize)]
+struct SpecialDayPayload {
+    date: NaiveDate,
+    open_time: Option<NaiveTime>,
+    close_time: Option<NaiveTime>,
+    note: Option<String>,
+}
+
+/// Without `open_time` and `close_time` the lot is closed the whole date.
+async fn create_special_day(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<SpecialDayPayload>,
+) -> Result<AppSuccess<SpecialDay>> {
+    if payload.open_time.is_some() != payload.close_time.is_some() {
+        return Err(Error::BadRequest(
+            "open_time and close_time must be provided together".to_string(),
+        ));
+    }
+
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let special_day = SpecialDay {
+        id: Uuid::new_v4(),
+        parking_lot_id: parking_lot.id,
+        date: payload.date,
+        open_time: payload.open_time,
+        close_time: payload.close_time,
+        note: payload.note,
+        created_at: Some(Utc::now().naive_utc()),
+    };
+    let special_day = special_day.save(&pool).await?;
+
+    Ok(AppSuccess(special_day))
+}
+
+async fn delete_special_day(
+    State(pool): State<PgPool>,
+    Path((id, date)): Path<(Uuid, NaiveDate)>,
+) -> Result<AppSuccess<SpecialDay>> {
+    let special_day = SpecialDay::delete(id, date, &pool).await?;
+    Ok(AppSuccess(special_day))
+}
+
+#[derive(Deserialize)]
+struct ClosurePayload {
+    start_at: DateTime<Utc>,
+    end_at: DateTime<Utc>,
+    reason: Option<String>,
+}
+
+async fn create_closure(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<ClosurePayload>,
+) -> Result<AppSuccess<Closure>> {
+    if payload.start_at >= payload.end_at {
+        return Err(Error::BadRequest(
+            "start_at must be before end_at".to_string(),
+        ));
+    }
+
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let closure = Closure {
+        id: Uuid::new_v4(),
+        parking_lot_id: parking_lot.id,
+        start_at: payload.start_at.naive_utc(),
+        end_at: payload.end_at.naive_utc(),
+        reason: payload.reason,
+        created_at: Some(Utc::now().naive_utc()),
+    };
+    let closure = closure.save(&pool).await?;
+
+    Ok(AppSuccess(closure))
+}
+
+async fn delete_closure(
+    State(pool): State<PgPool>,
+    Path((id, closure_id)): Path<(Uuid, Uuid)>,
+) -> Result<AppSuccess<Closure>> {
+    let closure = Closure::delete(id, closure_id, &pool).await?;
+    Ok(AppSuccess(closure))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DirectoryPayload {
+    pub vehicle_type: Option<VehicleType>,
+    pub vehicle_category: Option<String>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub open_now: Option<bool>,
+    pub area: Option<String>,
+    pub sort: Option<DirectorySort>,
+    pub take: Option<i64>,
+    pub skip: Option<i64>,
+}
+
+impl DirectoryPayload {
+    fn into_directory_query(self, vehicle_category_id: Option<Uuid>) -> Result<DirectoryQuery> {
+        if self.min_price.is_some_and(|price| price < 0.0)
+            || self.max_price.is_some_and(|price| price < 0.0)
+        {
+            return Err(Error::BadRequest("Price cannot be negative".to_string()));
+        }
+        if let (Some(min_price), Some(max_price)) = (self.min_price, self.max_price) {
+            if min_price > max_price {
+                return Err(Error::BadRequest(
+                    "min_price must not be greater than max_price".to_string(),
+                ));
+            }
+        }
+
+        Ok(DirectoryQuery {
+            vehicle_category_id,
+            min_price: self.min_price,
+            max_price: self.max_price,
+            open_now: self.open_now,
+            area: self
+                .area
+                .map(|area| area.trim().to_string())
+                .filter(|area| !area.is_empty()),
+            sort: self.sort.unwrap_or_default(),
+            take: Some(self.take.unwrap_or(20).clamp(1, 100)),
+            skip: self.skip,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Directory {
+    meta: DirectoryMeta,
+    parking_lot: Vec<PublicParkingLot>,
+}
+
+#[derive(Serialize)]
+struct DirectoryMeta {
+    total_data: i64,
+    query: DirectoryPayload,
+}
+
+/// Public listing for drivers, no owner id needed. A `vehicle_category` code
+/// wins over the older `vehicle_type`, which stands for its car or motor
+/// category.
+async fn directory(
+    State(pool): State<PgPool>,
+    Query(payload): Query<DirectoryPayload>,
+) -> Result<AppSuccess<Directory>> {
+    let category = match (&payload.vehicle_category, &payload.vehicle_type) {
+        (Some(code), _) => Some(VehicleCategory::find_by_code(code, &pool).await?),
+        (None, Some(vehicle_type)) if *vehicle_type != VehicleType::Default => {
+            Some(VehicleCategory::find_by_vehicle_type(vehicle_type, &pool).await?)
+        }
+        _ => None,
+    };
+    let query = payload
+        .clone()
+        .into_directory_query(category.map(|category| category.id))?;
+    let total_data = PublicParkingLot::count(&pool, query.clone()).await?;
+    let parking_lot = PublicParkingLot::directory(&pool, query).await?;
+
+    Ok(AppSuccess(Directory {
+        meta: DirectoryMeta {
+            total_data: total_data.data.unwrap_or(0),
+            query: payload,
+        },
+        parking_lot,
+    }))
+}
+
+#[derive(Deserialize)]
+struct ArchivePayload {
+    owner_id: Uuid,
+}
+
+/// A lot is only archived once it is empty: no ticket waiting or parked and
+/// no keeper on shift. Its keepers are released to be assigned elsewhere.
+async fn archive(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<ArchivePayload>,
+) -> Result<AppSuccess<ParkingLot>> {
+    let parking_lot = owned_parking_lot(id, payload.owner_id, &pool).await?;
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let parking_lot = ParkingLot::find_one_for_update(parking_lot.id, uow.connection()).await?;
+
+    if ParkingHistory::count_active(Some(parking_lot.id), None, uow.connection()).await? > 0 {
+        return Err(Error::Conflict(
+            "Parking lot still has active tickets".to_string(),
+        ));
+    }
+
+    let open_shift = KeeperShift::aggregate(
+        ShiftQuery {
+            owner_id: Some(parking_lot.owner_id),
+            keeper_id: None,
+            parking_lot_id: Some(parking_lot.id),
+            shift_status: Some(ShiftStatus::Open),
+        },
+        uow.connection(),
+    )
+    .await?;
+    if !open_shift.is_empty() {
+        return Err(Error::Conflict(
+            "Parking lot still has a keeper on shift".to_string(),
+        ));
+    }
+
+    let parking_lot = ParkingLot::archive(
+        parking_lot.id,
+        parking_lot.owner_id,
+        Utc::now().naive_utc(),
+        uow.connection(),
+    )
+    .await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(parking_lot))
+}
+
+async fn restore(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<ArchivePayload>,
+) -> Result<AppSuccess<ParkingLot>> {
+    let owner = User::find_one_by_id(payload.owner_id, &pool).await?;
+    if owner.role != Role::ParkOwner {
+        return Err(Error::BadRequest(
+            "Provided owner id is not having ParkOwner role".to_string(),
+        ));
+    }
+
+    let parking_lot = ParkingLot::restore(id, owner.id, Utc::now().naive_utc(), &pool).await?;
+
+    Ok(AppSuccess(parking_lot))
+}
+
+/// Every id has to be a live `ParkKeeper` working for `owner_id`. The keepers
+/// stay locked until the transaction ends, a shift cannot open in between.
+async fn validate_keepers(keeper_ids: &[Uuid], owner_id: Uuid, connection: &mut PgConnection) -> Result<Vec<User>> {
+    if keeper_ids.is_empty() {
+        return Err(Error::BadRequest("keeper_ids cannot be empty".to_string()));
+    }
+    for (index, id) in keeper_ids.iter().enumerate() {
+        if keeper_ids[..index].contains(id) {
+            return Err(Error::BadRequest(format!(
+                "Keeper {} is given more than once",
+                id
+            )));
+        }
+    }
+
+    let keeper = User::find_many_for_update(keeper_ids, connection).await?;
+    if keeper.len() != keeper_ids.len() {
+        return Err(Error::NotFoundRejection("Keeper is not found".to_string()));
+    }
+    if let Some(keeper) = keeper.iter().find(|keeper| keeper.role != Role::ParkKeeper) {
+        return Err(Error::BadRequest(format!(
+            "User {} is not having ParkKeeper role",
+            keeper.id
+        )));
+    }
+    if let Some(keeper) = keeper.iter().find(|keeper| keeper.owner_id != Some(owner_id)) {
+        return Err(Error::BadRequest(format!(
+            "Keeper {} is not working for the owner of the parking lot",
+            keeper.id
+        )));
+    }
+
+    Ok(keeper)
+}
+
+/// A keeper cannot change lots in the middle of a shift, the shift belongs to
+/// the lot it was opened at.
+async fn ensure_off_shift(owner_id: Uuid, keeper_ids: &[Uuid], connection: &mut PgConnection) -> Result<()> {
+    if keeper_ids.is_empty() {
+        return Ok(());
+    }
+
+    let open_shift = KeeperShift::aggregate(
+        ShiftQuery {
+            owner_id: Some(owner_id),
+            keeper_id: None,
+            parking_lot_id: None,
+            shift_status: Some(ShiftStatus::Open),
+        },
+        connection,
+    )
+    .await?;
+    if let Some(shift) = open_shift
+        .iter()
+        .find(|shift| keeper_ids.contains(&shift.keeper_id))
+    {
+        return Err(Error::Conflict(format!(
+            "Keeper {} is still on shift",
+            shift.keeper_id
+        )));
+    }
+
+    Ok(())
+}
+
+async fn assign_keepers(
+    parking_lot_id: Uuid,
+    owner_id: Uuid,
+    keeper_ids: &[Uuid],
+    at: NaiveDateTime,
+    connection: &mut PgConnection,
+) -> Result<Vec<User>> {
+    let keeper = User::update_parking_lot(parking_lot_id, keeper_ids, at, &mut *connection).await?;
+    KeeperAssignment::start(parking_lot_id, owner_id, keeper_ids, at, connection).await?;
+
+    Ok(keeper)
+}
+
+async fn release_keepers(
+    parking_lot_id: Uuid,
+    keeper_ids: &[Uuid],
+    at: NaiveDateTime,
+    connection: &mut PgConnection,
+) -> Result<Vec<User>> {
+    let keeper = User::remove_parking_lot(parking_lot_id, keeper_ids, at, &mut *connection).await?;
+    let released: Vec<Uuid> = keeper.iter().map(|keeper| keeper.id).collect();
+    KeeperAssignment::end(&released, at, connection).await?;
+
+    Ok(keeper)
+}
+
+#[derive(Deserialize)]
+struct KeepersPayload {
+    owner_id: Uuid,
+    keeper_ids: Vec<Uuid>,
+}
+
+/// Checks the owner and returns the lot, which has to be theirs.
+pub(crate) async fn owned_parking_lot(id: Uuid, owner_id: Uuid, pool: &PgPool) -> Result<ParkingLot> {
+    let owner = User::find_one_by_id(owner_id, pool).await?;
+    if owner.role != Role::ParkOwner {
+        return Err(Error::BadRequest(
+            "Provided owner id is not having ParkOwner role".to_string(),
+        ));
+    }
+
+    let parking_lot = ParkingLot::find_one(id, pool).await?;
+    if parking_lot.owner_id != owner.id {
+        return Err(Error::BadRequest(
+            "Parking lot is not owned by the provided owner id".to_string(),
+        ));
+    }
+
+    Ok(parking_lot)
+}
+
+/// Adds keepers to the lot next to the ones already there. Keepers coming from
+/// another lot of the owner are moved.
+async fn add_keepers(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<KeepersPayload>,
+) -> Result<AppSuccess<Vec<User>>> {
+    let parking_lot = owned_parking_lot(id, payload.owner_id, &pool).await?;
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let parking_lot = ParkingLot::find_one_for_update(parking_lot.id, uow.connection()).await?;
+    let keeper = validate_keepers(&payload.keeper_ids, parking_lot.owner_id, uow.connection()).await?;
+
+    let moving: Vec<Uuid> = keeper
+        .iter()
+        .filter(|keeper| keeper.parking_lot_id.is_some_and(|lot| lot != parking_lot.id))
+        .map(|keeper| keeper.id)
+        .collect();
+    ensure_off_shift(parking_lot.owner_id, &moving, uow.connection()).await?;
+
+    let joining: Vec<Uuid> = keeper
+        .iter()
+        .filter(|keeper| keeper.parking_lot_id != Some(parking_lot.id))
+        .map(|keeper| keeper.id)
+        .collect();
+
+    let keeper = assign_keepers(
+        parking_lot.id,
+        parking_lot.owner_id,
+        &joining,
+        Utc::now().naive_utc(),
+        uow.connection(),
+    )
+    .await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(keeper))
+}
+
+#[derive(Deserialize)]
+struct RemoveKeeperQuery {
+    owner_id: Uuid,
+}
+
+/// Takes one keeper off the lot, `DELETE /parking-lot/:id/keepers/:keeper_id`.
+async fn remove_keeper(
+    State(pool): State<PgPool>,
+    Path((id, keeper_id)): Path<(Uuid, Uuid)>,
+    Query(payload): Query<RemoveKeeperQuery>,
+) -> Result<AppSuccess<Vec<User>>> {
+    let parking_lot = owned_parking_lot(id, payload.owner_id, &pool).await?;
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let parking_lot = ParkingLot::find_one_for_update(parking_lot.id, uow.connection()).await?;
+    let keeper = validate_keepers(&[keeper_id], parking_lot.owner_id, uow.connection()).await?;
+
+    if let Some(keeper) = keeper
+        .iter()
+        .find(|keeper| keeper.parking_lot_id != Some(parking_lot.id))
+    {
+        return Err(Error::BadRequest(format!(
+            "Keeper {} is not assigned to the parking lot",
+            keeper.id
+        )));
+    }
+    ensure_off_shift(parking_lot.owner_id, &[keeper_id], uow.connection()).await?;
+
+    let keeper = release_keepers(
+        parking_lot.id,
+        &[keeper_id],
+        Utc::now().naive_utc(),
+        uow.connection(),
+    )
+    .await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(keeper))
+}
+
+async fn keeper_history(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<Vec<KeeperAssignmentHistory>>> {
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let history = KeeperAssignment::find_by_parking_lot(parking_lot.id, &pool).await?;
+
+    Ok(AppSuccess(history))
+}
+
+fn validate_costs(car_cost: Option<f64>, motor_cost: Option<f64>) -> Result<()> {
+    if car_cost.is_some_and(|cost| cost < 0.0) || motor_cost.is_some_and(|cost| cost < 0.0) {
+        return Err(Error::BadRequest(
+            "car_cost and motor_cost cannot be negative".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// A tariff of the lot in force from now on.
+fn new_tariff(parking_lot: &ParkingLot, car_cost: f64, motor_cost: f64, created_by: Option<Uuid>) -> Tariff {
+    let now = Utc::now().naive_utc();
+    Tariff {
+        id: Uuid::new_v4(),
+        parking_lot_id: parking_lot.id,
+        version: 0,
+        car_cost,
+        motor_cost,
+        effective_from: now,
+        note: None,
+        created_by: created_by.or(Some(parking_lot.owner_id)),
+        created_at: Some(now),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RatePayload {
+    category: String,
+    cost: f64,
+}
+
+/// Rates of a new tariff for the categories other than car and motor, which
+/// are priced by `car_cost` and `motor_cost`. Without `rates` the ones of the
+/// tariff in force carry over.
+async fn resolve_rates(
+    parking_lot_id: Option<Uuid>,
+    rates: Option<&[RatePayload]>,
+    pool: &PgPool,
+) -> Result<Vec<(Uuid, f64)>> {
+    let Some(rates) = rates else {
+        let Some(parking_lot_id) = parking_lot_id else {
+            return Ok(Vec::new());
+        };
+        let tariff = Tariff::in_force(parking_lot_id, Utc::now().naive_utc(), pool).await?;
+        let rate = ParkingLotRate::find_by_tariffs(&[tariff.id], pool)
+            .await?
+            .into_iter()
+            .filter(|rate| !BASE_CATEGORY.contains(&rate.code.as_str()))
+            .map(|rate| (rate.vehicle_category_id, rate.cost))
+            .collect();
+        return Ok(rate);
+    };
+
+    let codes: Vec<String> = rates.iter().map(|rate| rate.category.clone()).collect();
+    for (index, code) in codes.iter().enumerate() {
+        if codes[..index].contains(code) {
+            return Err(Error::BadRequest(format!(
+                "Vehicle category {} is given more than once",
+                code
+            )));
+        }
+        if BASE_CATEGORY.contains(&code.as_str()) {
+            return Err(Error::BadRequest(
+                "Car and motor are priced by car_cost and motor_cost".to_string(),
+            ));
+        }
+    }
+    if rates.iter().any(|rate| rate.cost < 0.0) {
+        return Err(Error::BadRequest("Rate cost cannot be negative".to_string()));
+    }
+
+    let category = VehicleCategory::find_by_codes(&codes, pool).await?;
+    Ok(category
+        .iter()
+        .zip(rates)
+        .map(|(category, rate)| (category.id, rate.cost))
+        .collect())
+}
+
+/// Every rate of a tariff, car and motor first.
+async fn full_rates(car_cost: f64, motor_cost: f64, rates: &[(Uuid, f64)], pool: &PgPool) -> Result<Vec<(Uuid, f64)>> {
+    let base = BASE_CATEGORY.map(String::from);
+    let base = VehicleCategory::find_by_codes(&base, pool).await?;
+
+    let mut rate = vec![(base[0].id, car_cost), (base[1].id, motor_cost)];
+    rate.extend_from_slice(rates);
+    Ok(rate)
+}
+
+async fn save_tariff(tariff: Tariff, rates: &[(Uuid, f64)], connection: &mut PgConnection) -> Result<TariffDetail> {
+    let tariff = tariff.save(&mut *connection).await?;
+    let rates = ParkingLotRate::save_for_tariff(
+        tariff.id,
+        tariff.parking_lot_id,
+        rates,
+        Utc::now().naive_utc(),
+        connection,
+    )
+    .await?;
+
+    let now = Utc::now().naive_utc();
+    let status = if tariff.effective_from > now {
+        TariffStatus::Scheduled
+    } else {
+        TariffStatus::Current
+    };
+
+    Ok(TariffDetail {
+        tariff,
+        rates,
+        status,
+    })
+}
+
+/// Checks the lot has a rate for every category it lets in.
+async fn resolve_accepted(codes: &[String], rates: &[(Uuid, f64)], pool: &PgPool) -> Result<Vec<Uuid>> {
+    if codes.is_empty() {
+        return Err(Error::BadRequest(
+            "A parking lot must accept at least one vehicle category".to_string(),
+        ));
+    }
+
+    let category = VehicleCategory::find_by_codes(codes, pool).await?;
+    let mut accepted: Vec<Uuid> = Vec::with_capacity(category.len());
+    for category in category {
+        if !rates.iter().any(|(id, _)| *id == category.id) {
+            return Err(Error::BadRequest(format!(
+                "Parking lot has no rate for {}",
+                category.name
+            )));
+        }
+        if !accepted.contains(&category.id) {
+            accepted.push(category.id);
+        }
+    }
+
+    Ok(accepted)
+}
+
+#[derive(Serialize)]
+struct TariffDetail {
+    #[serde(flatten)]
+    tariff: Tariff,
+    rates: Vec<ParkingLotRate>,
+    status: TariffStatus,
+}
+
+/// Every tariff of the lot, scheduled ones first.
+async fn tariffs(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<Vec<TariffDetail>>> {
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let tariff = Tariff::find_by_parking_lot(parking_lot.id, &pool).await?;
+    let tariff_ids: Vec<Uuid> = tariff.iter().map(|tariff| tariff.id).collect();
+    let mut rates = ParkingLotRate::find_by_tariffs(&tariff_ids, &pool).await?;
+
+    let now = Utc::now().naive_utc();
+    let in_force_id = tariff
+        .iter()
+        .find(|tariff| tariff.effective_from <= now)
+        .map(|tariff| tariff.id);
+    let history = tariff
+        .into_iter()
+        .map(|tariff| {
+            let (rate, rest) = rates.drain(..).partition(|rate| rate.tariff_id == tariff.id);
+            rates = rest;
+            TariffDetail {
+                status: tariff.status(in_force_id, now),
+                rates: rate,
+                tariff,
+            }
+        })
+        .collect();
+
+    Ok(AppSuccess(history))
+}
+
+#[derive(Deserialize)]
+struct TariffPayload {
+    owner_id: Uuid,
+    car_cost: f64,
+    motor_cost: f64,
+    rates: Option<Vec<RatePayload>>,
+    effective_from: Option<DateTime<Utc>>,
+    note: Option<String>,
+}
+
+/// Schedules new prices for the lot, in force right away without
+/// `effective_from`.
+async fn create_tariff(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<TariffPayload>,
+) -> Result<AppSuccess<TariffDetail>> {
+    let parking_lot = owned_parking_lot(id, payload.owner_id, &pool).await?;
+    validate_costs(Some(payload.car_cost), Some(payload.motor_cost))?;
+
+    let now = Utc::now().naive_utc();
+    let effective_from = match payload.effective_from {
+        Some(effective_from) if effective_from.naive_utc() < now => {
+            return Err(Error::BadRequest(
+                "effective_from cannot be in the past".to_string(),
+            ));
+        }
+        Some(effective_from) => effective_from.naive_utc(),
+        None => now,
+    };
+
+    let rates = resolve_rates(Some(parking_lot.id), payload.rates.as_deref(), &pool).await?;
+    let rates = full_rates(payload.car_cost, payload.motor_cost, &rates, &pool).await?;
+    let accepted: Vec<String> = AcceptedCategory::find_by_parking_lot(parking_lot.id, now, &pool)
+        .await?
+        .into_iter()
+        .map(|category| category.code)
+        .collect();
+    resolve_accepted(&accepted, &rates, &pool).await?;
+
+    let mut tariff = new_tariff(&parking_lot, payload.car_cost, payload.motor_cost, Some(payload.owner_id));
+    tariff.effective_from = effective_from;
+    tariff.note = payload.note;
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let tariff = save_tariff(tariff, &rates, uow.connection()).await?;
+    Tariff::sync_prices(Some(parking_lot.id), now, uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(tariff))
+}
+
+#[derive(Deserialize)]
+struct DeleteTariffPayload {
+    owner_id: Uuid,
+}
+
+async fn delete_tariff(
+    State(pool): State<PgPool>,
+    Path((id, tariff_id)): Path<(Uuid, Uuid)>,
+    Body(payload): Body<DeleteTariffPayload>,
+) -> Result<AppSuccess<Tariff>> {
+    let parking_lot = owned_parking_lot(id, payload.owner_id, &pool).await?;
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let tariff = Tariff::delete_scheduled(parking_lot.id, tariff_id, Utc::now().naive_utc(), uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(tariff))
+}
+
+async fn vehicle_categories(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<Vec<AcceptedCategory>>> {
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let category = AcceptedCategory::find_by_parking_lot(parking_lot.id, Utc::now().naive_utc(), &pool).await?;
+
+    Ok(AppSuccess(category))
+}
+
+#[derive(Deserialize)]
+struct VehicleCategoriesPayload {
+    owner_id: Uuid,
+    categories: Vec<String>,
+}
+
+/// The given categories become everything the lot lets in, each needs a rate
+/// in the tariff in force.
+async fn replace_vehicle_categories(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<VehicleCategoriesPayload>,
+) -> Result<AppSuccess<Vec<AcceptedCategory>>> {
+    let parking_lot = owned_parking_lot(id, payload.owner_id, &pool).await?;
+
+    let now = Utc::now().naive_utc();
+    let tariff = Tariff::in_force(parking_lot.id, now, &pool).await?;
+    let rates: Vec<(Uuid, f64)> = ParkingLotRate::find_by_tariffs(&[tariff.id], &pool)
+        .await?
+        .into_iter()
+        .map(|rate| (rate.vehicle_category_id, rate.cost))
+        .collect();
+    let accepted = resolve_accepted(&payload.categories, &rates, &pool).await?;
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let category = AcceptedCategory::replace(parking_lot.id, &accepted, now, uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(category))
+}
+
+/// Every zone of the lot with how many of its spots are taken.
+async fn zones(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<Vec<ZoneOccupancy>>> {
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let zone = ParkingZone::occupancy(parking_lot.id, &pool).await?;
+
+    Ok(AppSuccess(zone))
+}
+
+#[derive(Deserialize)]
+struct ZonePayload {
+    owner_id: Uuid,
+    name: String,
+    floor: Option<String>,
+}
+
+async fn create_zone(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<ZonePayload>,
+) -> Result<AppSuccess<ParkingZone>> {
+    let parking_lot = owned_parking_lot(id, payload.owner_id, &pool).await?;
+    if payload.name.trim().is_empty() {
+        return Err(Error::BadRequest("Zone name is required".to_string()));
+    }
+
+    let zone = ParkingZone {
+        id: Uuid::new_v4(),
+        parking_lot_id: parking_lot.id,
+        name: payload.name.trim().to_string(),
+        floor: payload.floor,
+        created_at: Some(Utc::now().naive_utc()),
+    };
+    let zone = zone.save(&pool).await?;
+
+    Ok(AppSuccess(zone))
+}
+
+async fn spots(
+    State(pool): State<PgPool>,
+    Path((id, zone_id)): Path<(Uuid, Uuid)>,
+) -> Result<AppSuccess<Vec<SpotStatus>>> {
+    let zone = ParkingZone::find_one(id, zone_id, &pool).await?;
+    let spot = ParkingSpot::find_by_zone(zone.id, &pool).await?;
+
+    Ok(AppSuccess(spot))
+}
+
+#[derive(Deserialize)]
+struct SpotPayload {
+    number: String,
+    kind: Option<SpotKind>,
+}
+
+#[derive(Deserialize)]
+struct SpotsPayload {
+    owner_id: Uuid,
+    spots: Vec<SpotPayload>,
+}
+
+async fn create_spots(
+    State(pool): State<PgPool>,
+    Path((id, zone_id)): Path<(Uuid, Uuid)>,
+    Body(payload): Body<SpotsPayload>,
+) -> Result<AppSuccess<Vec<ParkingSpot>>> {
+    let parking_lot = owned_parking_lot(id, payload.owner_id, &pool).await?;
+    let zone = ParkingZone::find_one(parking_lot.id, zone_id, &pool).await?;
+
+    if payload.spots.is_empty() {
+        return Err(Error::BadRequest("No spot is given".to_string()));
+    }
+    let mut spots: Vec<(String, SpotKind)> = Vec::with_capacity(payload.spots.len());
+    for spot in payload.spots {
+        let number = spot.number.trim().to_uppercase();
+        if number.is_empty() {
+            return Err(Error::BadRequest("Spot number is required".to_string()));
+        }
+        if spots.iter().any(|(given, _)| *given == number) {
+            return Err(Error::BadRequest(format!(
+                "Spot {} is given more than once",
+                number
+            )));
+        }
+        spots.push((number, spot.kind.unwrap_or(SpotKind::Regular)));
+    }
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let spot = ParkingSpot::save_many(&zone, &spots, Utc::now().naive_utc(), uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(spot))
+}
+
+#[derive(Deserialize)]
+struct UpdateSpotPayload {
+    owner_id: Uuid,
+    kind: Option<SpotKind>,
+    is_active: Option<bool>,
+}
+
+/// Changes the kind of a spot or takes it out of use, e.g. for repairs.
+async fn update_spot(
+    State(pool): State<PgPool>,
+    Path((id, spot_id)): Path<(Uuid, Uuid)>,
+    Body(payload): Body<UpdateSpotPayload>,
+) -> Result<AppSuccess<ParkingSpot>> {
+    let parking_lot = owned_parking_lot(id, payload.owner_id, &pool).await?;
+    let spot = ParkingSpot::find_one(parking_lot.id, spot_id, &pool).await?;
+
+    if payload.is_active == Some(false) && SpotAllocation::is_occupied(spot.id, &pool).await? {
+        return Err(Error::Conflict(
+            "Spot is taken, it can be deactivated once the vehicle leaves".to_string(),
+        ));
+    }
+
+    let spot = UpdateParkingSpot {
+        kind: payload.kind,
+        is_active: payload.is_active,
+        updated_at: Some(Utc::now().naive_utc()),
+    };
+    let spot = spot.update(parking_lot.id, spot_id, &pool).await?;
+
+    Ok(AppSuccess(spot))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body as AxumBody},
+        http::{Request, StatusCode},
+    };
+    use futures::future::join_all;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use super::build;
+    use crate::app::fixture::{seed, EASYPARK_ID, KEEPER_ID, OWNER_ID, PARKING_LOT_ID};
+
+    fn archive_lot() -> Request<AxumBody> {
+        let body = serde_json::json!({ "owner_id": OWNER_ID }).to_string();
+
+        Request::post(format!("/parking-lot/{}/archive", PARKING_LOT_ID))
+            .header("content-type", "application/json")
+            .body(AxumBody::from(body))
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn lot_is_archived_only_once_its_tickets_are_closed(pool: PgPool) {
+        seed(&pool).await;
+
+        let ticket_id = Uuid::new_v4();
+        sqlx::query("insert into transaction_history (id) values ($1)")
+            .bind(ticket_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+                insert into parking_history (id, ticket_status, vehicle_type, payment, amount, parking_lot_id,
+                    easypark_id, keeper_id, owner_id, transaction_id, created_at, check_in_date)
+                values ($1, 'active', 'car', 'cash', 5000, $2, $3, $4, $5, $1, now(), now())
+            "#,
+        )
+        .bind(ticket_id)
+        .bind(PARKING_LOT_ID)
+        .bind(EASYPARK_ID)
+        .bind(KEEPER_ID)
+        .bind(OWNER_ID)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let response = build(pool.clone()).oneshot(archive_lot()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        sqlx::query("update parking_history set ticket_status = 'not_active', check_out_date = now() where id = $1")
+            .bind(ticket_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let response = build(pool.clone()).oneshot(archive_lot()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let keeper_lot: Option<Uuid> = sqlx::query_scalar(r#"select parking_lot_id from "user" where id = $1"#)
+            .bind(KEEPER_ID)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(keeper_lot, None);
+    }
+
+    #[sqlx::test]
+    async fn parallel_tariffs_take_consecutive_versions(pool: PgPool) {
+        seed(&pool).await;
+
+        let app = build(pool.clone());
+        let requests = (1..=4).map(|day| {
+            let body = serde_json::json!({
+                "owner_id": OWNER_ID,
+                "car_cost": 5000 + day * 1000,
+                "motor_cost": 2000,
+                "effective_from": format!("2099-01-0{}T00:00:00Z", day),
+            })
+            .to_string();
+            let request = Request::post(format!("/parking-lot/{}/tariffs", PARKING_LOT_ID))
+                .header("content-type", "application/json")
+                .body(AxumBody::from(body))
+                .unwrap();
+            tokio::spawn(app.clone().oneshot(request))
+        });
+
+        for response in join_all(requests).await {
+            assert_eq!(response.unwrap().unwrap().status(), StatusCode::OK);
+        }
+
+        let versions: Vec<i32> = sqlx::query_scalar(
+            "select version from parking_lot_tariff where parking_lot_id = $1 order by version",
+        )
+        .bind(PARKING_LOT_ID)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(versions, vec![1, 2, 3, 4, 5]);
+    }
+
+    /// Prices of the lots the directory lists for the query string.
+    async fn directory_prices(pool: &PgPool, query: &str) -> Vec<f64> {
+        let response = build(pool.clone())
+            .oneshot(Request::get(format!("/parking-lot?{}", query)).body(AxumBody::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        body["data"]["parking_lot"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|lot| lot["price"].as_f64().unwrap())
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn directory_lists_lots_by_accepted_category_at_the_rate_in_force(pool: PgPool) {
+        seed(&pool).await;
+
+        // The lot still has a motor cost from before categories, but only
+        // accepts cars.
+        assert_eq!(directory_prices(&pool, "vehicle_type=Car").await, vec![5000.0]);
+        assert!(directory_prices(&pool, "vehicle_type=Motor").await.is_empty());
+        assert!(directory_prices(&pool, "vehicle_category=truck").await.is_empty());
+
+        sqlx::query(
+            r#"
+                with truck as (select id from vehicle_category where code = 'truck'),
+                accepted as (
+                    insert into parking_lot_vehicle_category (parking_lot_id, vehicle_category_id)
+                    select $1, truck.id from truck
+                )
+                insert into parking_lot_rate (id, tariff_id, parking_lot_id, vehicle_category_id, cost)
+                select gen_random_uuid(), t.id, $1, truck.id, 0
+                from parking_lot_tariff t, truck
+                where t.parking_lot_id = $1
+            "#,
+        )
+        .bind(PARKING_LOT_ID)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // Trucks park for free, and that is now the cheapest rate of the lot.
+        assert_eq!(directory_prices(&pool, "vehicle_category=truck").await, vec![0.0]);
+        assert_eq!(directory_prices(&pool, "max_price=0").await, vec![0.0]);
+    }
+}
@@ -0,0 +1,236 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::error::aggregate::{Error, Result};
+
+/// Whether the lot takes vehicles at `at`, a UTC timestamp. Closures win over
+/// special days, special days over the weekly hours, and a lot without weekly
+/// hours is open around the clock.
+pub async fn is_open(parking_lot_id: Uuid, at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<bool> {
+    let is_open = sqlx::query_scalar!(
+        r#"select parking_lot_is_open($1, $2) as "is_open!""#,
+        parking_lot_id,
+        at
+    )
+        .fetch_one(executor)
+        .await?;
+
+    Ok(is_open)
+}
+
+/// Weekly hours in the lot's own timezone, `day_of_week` 1 is Monday. A
+/// `close_time` at or before `open_time` runs past midnight, equal times mean
+/// the whole day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpeningHour {
+    pub id: Uuid,
+    pub parking_lot_id: Uuid,
+    pub day_of_week: i16,
+    pub open_time: NaiveTime,
+    pub close_time: NaiveTime,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl OpeningHour {
+    pub async fn find_by_parking_lot(parking_lot_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<OpeningHour>> {
+        let opening_hour = sqlx::query_as!(
+            OpeningHour,
+            r#"
+                select id, parking_lot_id, day_of_week, open_time, close_time, created_at
+                from "parking_lot_opening_hour"
+                where parking_lot_id = $1
+                order by day_of_week
+            "#,
+            parking_lot_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(opening_hour)
+    }
+
+    /// Swaps the whole week at once, an empty list leaves the lot open around
+    /// the clock.
+    pub async fn replace(parking_lot_id: Uuid, opening_hour: Vec<OpeningHour>, connection: &mut PgConnection) -> Result<Vec<OpeningHour>> {
+        sqlx::query!(
+            r#"delete from "parking_lot_opening_hour" where parking_lot_id = $1"#,
+            parking_lot_id
+        )
+            .execute(&mut *connection)
+            .await?;
+
+        let mut saved = Vec::with_capacity(opening_hour.len());
+        for hour in opening_hour {
+            let hour = sqlx::query_as!(
+                OpeningHour,
+                r#"
+                    insert into "parking_lot_opening_hour" (id, parking_lot_id, day_of_week, open_time, close_time, created_at)
+                    values ($1, $2, $3, $4, $5, $6)
+                    returning id, parking_lot_id, day_of_week, open_time, close_time, created_at
+                "#,
+                hour.id,
+                parking_lot_id,
+                hour.day_of_week,
+                hour.open_time,
+                hour.close_time,
+                hour.created_at
+            )
+                .fetch_one(&mut *connection)
+                .await?;
+            saved.push(hour);
+        }
+
+        Ok(saved)
+    }
+}
+
+/// Replaces the weekly hours on one local date, e.g. a public holiday. Without
+/// times the lot stays closed that day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecialDay {
+    pub id: Uuid,
+    pub parking_lot_id: Uuid,
+    pub date: NaiveDate,
+    pub open_time: Option<NaiveTime>,
+    pub close_time: Option<NaiveTime>,
+    pub note: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl SpecialDay {
+    /// Setting the same date twice overwrites the previous hours.
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<SpecialDay> {
+        let special_day = sqlx::query_as!(
+            SpecialDay,
+            r#"
+                insert into "parking_lot_special_day" (id, parking_lot_id, date, open_time, close_time, note, created_at)
+                values ($1, $2, $3, $4, $5, $6, $7)
+                on conflict (parking_lot_id, date) do update
+                set open_time = excluded.open_time,
+                    close_time = excluded.close_time,
+                    note = excluded.note
+                returning id, parking_lot_id, date, open_time, close_time, note, created_at
+            "#,
+            self.id,
+            self.parking_lot_id,
+            self.date,
+            self.open_time,
+            self.close_time,
+            self.note,
+            self.created_at
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(special_day)
+    }
+
+    /// Today and later, where today is the local date in `timezone`.
+    pub async fn find_upcoming(parking_lot_id: Uuid, timezone: &str, executor: impl PgExecutor<'_>) -> Result<Vec<SpecialDay>> {
+        let special_day = sqlx::query_as!(
+            SpecialDay,
+            r#"
+                select id, parking_lot_id, date, open_time, close_time, note, created_at
+                from "parking_lot_special_day"
+                where parking_lot_id = $1 and date >= cast(now() at time zone $2 as date)
+                order by date
+            "#,
+            parking_lot_id,
+            timezone
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(special_day)
+    }
+
+    pub async fn delete(parking_lot_id: Uuid, date: NaiveDate, executor: impl PgExecutor<'_>) -> Result<SpecialDay> {
+        let special_day = sqlx::query_as!(
+            SpecialDay,
+            r#"
+                delete from "parking_lot_special_day"
+                where parking_lot_id = $1 and date = $2
+                returning id, parking_lot_id, date, open_time, close_time, note, created_at
+            "#,
+            parking_lot_id,
+            date
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        special_day.ok_or(Error::NotFoundRejection("Special day is not found".to_string()))
+    }
+}
+
+/// A temporary closure between two UTC timestamps, e.g. for maintenance or an
+/// event taking over the lot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Closure {
+    pub id: Uuid,
+    pub parking_lot_id: Uuid,
+    pub start_at: NaiveDateTime,
+    pub end_at: NaiveDateTime,
+    pub reason: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl Closure {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<Closure> {
+        let closure = sqlx::query_as!(
+            Closure,
+            r#"
+                insert into "parking_lot_closure" (id, parking_lot_id, start_at, end_at, reason, created_at)
+                values ($1, $2, $3, $4, $5, $6)
+                returning id, parking_lot_id, start_at, end_at, reason, created_at
+            "#,
+            self.id,
+            self.parking_lot_id,
+            self.start_at,
+            self.end_at,
+            self.reason,
+            self.created_at
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(closure)
+    }
+
+    /// Closures that have not ended yet, including the one in effect.
+    pub async fn find_upcoming(parking_lot_id: Uuid, now: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Vec<Closure>> {
+        let closure = sqlx::query_as!(
+            Closure,
+            r#"
+                select id, parking_lot_id, start_at, end_at, reason, created_at
+                from "parking_lot_closure"
+                where parking_lot_id = $1 and end_at > $2
+                order by start_at
+            "#,
+            parking_lot_id,
+            now
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(closure)
+    }
+
+    pub async fn delete(parking_lot_id: Uuid, id: Uuid, executor: impl PgExecutor<'_>) -> Result<Closure> {
+        let closure = sqlx::query_as!(
+            Closure,
+            r#"
+                delete from "parking_lot_closure"
+                where parking_lot_id = $1 and id = $2
+                returning id, parking_lot_id, start_at, end_at, reason, created_at
+            "#,
+            parking_lot_id,
+            id
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        closure.ok_or(Error::NotFoundRejection("Closure is not found".to_string()))
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::error::aggregate::{Error, Result};
+
+use super::ParkingLot;
+
+/// One version of a lot's prices, in force from `effective_from` until the
+/// next version of the same lot takes over. Every category is priced by a
+/// `ParkingLotRate` of the version, `car_cost` and `motor_cost` repeat the car
+/// and motorcycle rates.
+#[derive(Debug, Serialize)]
+pub struct Tariff {
+    pub id: Uuid,
+    pub parking_lot_id: Uuid,
+    pub version: i32,
+    pub car_cost: f64,
+    pub motor_cost: f64,
+    pub effective_from: NaiveDateTime,
+    pub note: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub enum TariffStatus {
+    Past,
+    Current,
+    Scheduled,
+}
+
+impl Tariff {
+    /// Takes the next version number of the lot, `self.version` is ignored.
+    /// The lot stays locked until the transaction ends so two tariffs saved at
+    /// once cannot read the same latest version.
+    pub async fn save(self, connection: &mut PgConnection) -> Result<Tariff> {
+        ParkingLot::find_one_for_update(self.parking_lot_id, &mut *connection).await?;
+
+        let tariff = sqlx::query_as!(
+            Tariff,
+            r#"
+                insert into "parking_lot_tariff" (id, parking_lot_id, version, car_cost, motor_cost, effective_from, note, created_by, created_at)
+                select $1, $2, coalesce(max(version), 0) + 1, $3, $4, $5, $6, $7, $8
+                from "parking_lot_tariff"
+                where parking_lot_id = $2
+                returning id, parking_lot_id, version, car_cost, motor_cost, effective_from, note, created_by, created_at
+            "#,
+            self.id,
+            self.parking_lot_id,
+            self.car_cost,
+            self.motor_cost,
+            self.effective_from,
+            self.note,
+            self.created_by,
+            self.created_at
+        )
+            .fetch_one(connection)
+            .await
+            .map_err(|err| match &err {
+                sqlx::Error::Database(dbx) if dbx.is_unique_violation() => {
+                    Error::Conflict("Another tariff of the parking lot starts at the same time".to_string())
+                }
+                _ => Error::from(err),
+            })?;
+
+        Ok(tariff)
+    }
+
+    pub async fn in_force(parking_lot_id: Uuid, at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Tariff> {
+        let tariff = sqlx::query_as!(
+            Tariff,
+            r#"
+                select id, parking_lot_id, version, car_cost, motor_cost, effective_from, note, created_by, created_at
+                from "parking_lot_tariff"
+                where parking_lot_id = $1 and effective_from <= $2
+                order by effective_from desc
+                limit 1
+            "#,
+            parking_lot_id,
+            at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        tariff.ok_or(Error::NotFoundRejection("Parking lot has no tariff in force".to_string()))
+    }
+
+    /// Scheduled versions first, then the one in force and the older ones.
+    pub async fn find_by_parking_lot(parking_lot_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<Tariff>> {
+        let tariff = sqlx::query_as!(
+            Tariff,
+            r#"
+                select id, parking_lot_id, version, car_cost, motor_cost, effective_from, note, created_by, created_at
+                from "parking_lot_tariff"
+                where parking_lot_id = $1
+                order by effective_from desc
+            "#,
+            parking_lot_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(tariff)
+    }
+
+    /// Only a version that has not started yet can be cancelled, tickets may
+    /// already be pinned to the others. Its rates go with it.
+    pub async fn delete_scheduled(parking_lot_id: Uuid, id: Uuid, now: NaiveDateTime, connection: &mut PgConnection) -> Result<Tariff> {
+        sqlx::query!(
+            r#"
+                delete from "parking_lot_rate"
+                where tariff_id in (
+                    select id from "parking_lot_tariff"
+                    where parking_lot_id = $1 and id = $2 and effective_from > $3
+                )
+            "#,
+            parking_lot_id,
+            id,
+            now
+        )
+            .execute(&mut *connection)
+            .await?;
+
+        let tariff = sqlx::query_as!(
+            Tariff,
+            r#"
+                delete from "parking_lot_tariff"
+                where parking_lot_id = $1 and id = $2 and effective_from > $3
+                returning id, parking_lot_id, version, car_cost, motor_cost, effective_from, note, created_by, created_at
+            "#,
+            parking_lot_id,
+            id,
+            now
+        )
+            .fetch_optional(connection)
+            .await?;
+
+        tariff.ok_or(Error::NotFoundRejection("Scheduled tariff is not found".to_string()))
+    }
+
+    /// Copies the prices in force at `now` onto the lots, so listings keep
+    /// reading `car_cost` and `motor_cost` from the lot itself. Runs on every
+    /// scheduler tick and right after a tariff change, `parking_lot_id` limits
+    /// it to one lot.
+    pub async fn sync_prices(parking_lot_id: Option<Uuid>, now: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+                update "parking_lot" pl
+                set car_cost = t.car_cost,
+                    motor_cost = t.motor_cost
+                from (
+                    select distinct on (parking_lot_id) parking_lot_id, car_cost, motor_cost
+                    from "parking_lot_tariff"
+                    where effective_from <= $2 and ($1::uuid is null or parking_lot_id = $1)
+                    order by parking_lot_id, effective_from desc
+                ) t
+                where pl.id = t.parking_lot_id and
+                    (pl.car_cost <> t.car_cost or pl.motor_cost <> t.motor_cost)
+            "#,
+            parking_lot_id,
+            now
+        )
+            .execute(executor)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub fn status(&self, in_force_id: Option<Uuid>, now: NaiveDateTime) -> TariffStatus {
+        if self.effective_from > now {
+            TariffStatus::Scheduled
+        } else if Some(self.id) == in_force_id {
+            TariffStatus::Current
+        } else {
+            TariffStatus::Past
+        }
+    }
+}
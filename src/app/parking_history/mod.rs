@@ -1,11 +1,34 @@
+pub mod receipt;
 pub mod router;
 
+use async_stream::try_stream;
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use futures::{Stream, TryStreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres};
+use sqlx::{PgExecutor, PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
-use crate::{error::aggregate::Result as ResultApp, types::count::SqlxCount};
+use crate::{
+    error::aggregate::{Error, Result as ResultApp},
+    query_filter::QueryFilter,
+    types::{
+        count::SqlxCount,
+        pagination::{push_page, Cursor, Sort},
+    },
+};
+
+const ACTIVE_TICKET_CONSTRAINT: &str = "parking_history_easypark_id_active_key";
+
+/// Turns a violation of the one-active-ticket-per-easypark index into a domain
+/// error; any other database error is passed through untouched.
+fn map_active_ticket_violation(error: sqlx::Error) -> Error {
+    match &error {
+        sqlx::Error::Database(dbx) if dbx.constraint() == Some(ACTIVE_TICKET_CONSTRAINT) => {
+            Error::Conflict("Ticket already issue".to_string())
+        }
+        _ => Error::from(error),
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct ParkingHistory {
@@ -23,6 +46,11 @@ pub struct ParkingHistory {
     pub updated_at: Option<NaiveDateTime>,
     pub check_in_date: Option<NaiveDateTime>,
     pub check_out_date: Option<NaiveDateTime>,
+    pub vehicle_id: Option<Uuid>,
+    pub plate_number: Option<String>,
+    pub penalty_amount: f64,
+    pub penalty_reason: Option<PenaltyReason>,
+    pub tariff_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize)]
@@ -42,9 +70,40 @@ pub struct ParkingHistoryWithTotalAmount {
     pub updated_at: Option<NaiveDateTime>,
     pub check_in_date: Option<NaiveDateTime>,
     pub check_out_date: Option<NaiveDateTime>,
+    pub vehicle_id: Option<Uuid>,
+    pub plate_number: Option<String>,
+    pub penalty_amount: f64,
+    pub penalty_reason: Option<PenaltyReason>,
 }
 
-#[derive(Debug, Serialize)]
+impl ParkingHistoryWithTotalAmount {
+    /// A ticket closed without a payment, there was nothing to charge.
+    pub fn free(parking_history: ParkingHistory) -> ParkingHistoryWithTotalAmount {
+        ParkingHistoryWithTotalAmount {
+            id: parking_history.id,
+            ticket_status: parking_history.ticket_status,
+            vehicle_type: parking_history.vehicle_type,
+            payment: parking_history.payment,
+            amount: parking_history.amount,
+            total_amount: Some(0.0),
+            parking_lot_id: parking_history.parking_lot_id,
+            easypark_id: parking_history.easypark_id,
+            owner_id: parking_history.owner_id,
+            keeper_id: parking_history.keeper_id,
+            transaction_id: parking_history.transaction_id,
+            created_at: parking_history.created_at,
+            updated_at: parking_history.updated_at,
+            check_in_date: parking_history.check_in_date,
+            check_out_date: parking_history.check_out_date,
+            vehicle_id: parking_history.vehicle_id,
+            plate_number: parking_history.plate_number,
+            penalty_amount: parking_history.penalty_amount,
+            penalty_reason: parking_history.penalty_reason,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
 pub struct HistoryFromQuery {
     pub id: Uuid,
     pub ticket_status: TicketStatus,
@@ -60,6 +119,9 @@ pub struct HistoryFromQuery {
     pub total_amount: Option<f64>,
     pub check_in_date: Option<NaiveDateTime>,
     pub check_out_date: Option<NaiveDateTime>,
+    pub plate_number: Option<String>,
+    pub penalty_amount: f64,
+    pub penalty_reason: Option<PenaltyReason>,
     pub easypark_name: String,
 }
 
@@ -105,6 +167,9 @@ impl HistoryFromQuery {
             updated_at,
             check_in_date,
             check_out_date,
+            plate_number: self.plate_number,
+            penalty_amount: self.penalty_amount,
+            penalty_reason: self.penalty_reason,
             parking_lot: RelatedParkingLot {
                 area_name: self.area_name,
                 address: self.address,
@@ -130,6 +195,9 @@ pub struct RelatedParkingHistory {
     pub updated_at: Option<DateTime<Utc>>,
     pub check_in_date: Option<DateTime<Utc>>,
     pub check_out_date: Option<DateTime<Utc>>,
+    pub plate_number: Option<String>,
+    pub penalty_amount: f64,
+    pub penalty_reason: Option<PenaltyReason>,
     pub parking_lot: RelatedParkingLot,
     pub easypark: RelatedEasypark
 }
@@ -157,6 +225,50 @@ pub struct AggregateQuery {
     pub created_at_end_filter: Option<NaiveDateTime>,
     pub take: Option<i64>,
     pub skip: Option<i64>,
+    pub cursor: Option<Cursor>,
+    pub sort: Sort,
+}
+
+impl QueryFilter for AggregateQuery {
+    const FROM: &'static str = r#"
+        from parking_history ph
+        join parking_lot pl on pl.id = ph.parking_lot_id
+        join transaction_history tx on tx.id = ph.transaction_id
+        join "user" u on u.id = ph.easypark_id
+    "#;
+
+    fn push_filters(&self, builder: &mut QueryBuilder<'static, Postgres>) {
+        builder.push(" and ph.deleted_at is null");
+        if let Some(start) = self.created_at_start_filter {
+            builder.push(" and ph.created_at >= ").push_bind(start);
+        }
+        if let Some(end) = self.created_at_end_filter {
+            builder.push(" and ph.created_at <= ").push_bind(end);
+        }
+
+        // Any of the three user ids widens the list to that user's tickets.
+        let users = [
+            ("ph.easypark_id", self.easypark_id),
+            ("ph.owner_id", self.owner_id),
+            ("ph.keeper_id", self.keeper_id),
+        ];
+        if users.iter().any(|(_, id)| id.is_some()) {
+            builder.push(" and (false");
+            for (column, id) in users {
+                if let Some(id) = id {
+                    builder.push(format!(" or {column} = ")).push_bind(id);
+                }
+            }
+            builder.push(")");
+        }
+
+        if let Some(status) = self.ticket_status.filter(|status| *status != TicketStatus::Default) {
+            builder.push(" and ph.ticket_status = ").push_bind(status);
+        }
+        if let Some(payment) = self.payment_type.clone().filter(|payment| *payment != PaymentType::Default) {
+            builder.push(" and ph.payment = ").push_bind(payment);
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone, Serialize)]
@@ -178,12 +290,45 @@ pub struct MonthlyRecord {
     pub total_history: Option<i64>
 }
 
+#[derive(Debug, Serialize)]
+pub struct StaleTicket {
+    pub id: Uuid,
+    pub plate_number: Option<String>,
+    pub area_name: String,
+    pub easypark_phone_number: String,
+    pub keeper_phone_number: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct CalcHistory {
     pub sum_all: Option<f64>,
     pub total_history: Option<i64>
 }
 
+/// One line of the parking history export, dates are already formatted in the
+/// requested timezone.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ExportRow {
+    pub id: Uuid,
+    pub created_at: Option<String>,
+    pub check_in_date: Option<String>,
+    pub check_out_date: Option<String>,
+    pub duration_minutes: Option<i64>,
+    pub area_name: String,
+    pub address: String,
+    pub easypark_name: String,
+    pub easypark_phone_number: String,
+    pub keeper_name: String,
+    pub plate_number: Option<String>,
+    pub vehicle_type: VehicleType,
+    pub payment: PaymentType,
+    pub ticket_status: TicketStatus,
+    pub amount: f64,
+    pub penalty_amount: f64,
+    pub penalty_reason: Option<PenaltyReason>,
+    pub settled_amount: Option<f64>,
+}
+
 #[derive(Clone, Debug, PartialEq, PartialOrd, sqlx::Type, Deserialize, Serialize)]
 #[sqlx(type_name = "vehicle_type", rename_all = "snake_case")] 
 pub enum VehicleType {
@@ -205,16 +350,28 @@ pub enum PaymentType {
 pub enum TicketStatus {
     Default,
     Active,
-    NotActive
+    NotActive,
+    Expired
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, sqlx::Type, Deserialize, Serialize)]
+#[sqlx(type_name = "penalty_reason", rename_all = "snake_case")]
+pub enum PenaltyReason {
+    Default,
+    LostTicket,
+    Overstay
 }
 
 impl ParkingHistory {
-    pub async fn save(self, pool: &Pool<Postgres>) -> ResultApp<ParkingHistory> {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> ResultApp<ParkingHistory> {
         let data = sqlx::query_as!(
             ParkingHistory, 
             r#"
-                insert into "parking_history" 
-                values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) 
+                insert into "parking_history" (
+                    id, ticket_status, vehicle_type, payment, amount, parking_lot_id, easypark_id, keeper_id, owner_id, transaction_id,
+                    created_at, updated_at, check_in_date, check_out_date, vehicle_id, plate_number, penalty_amount, penalty_reason, tariff_id
+                )
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19) 
                 returning id, 
                     ticket_status as "ticket_status!: TicketStatus", 
                     vehicle_type as "vehicle_type!: VehicleType", 
@@ -228,7 +385,12 @@ impl ParkingHistory {
                     created_at, 
                     updated_at,
                     check_in_date,
-                    check_out_date
+                    check_out_date,
+                    vehicle_id,
+                    plate_number,
+                    penalty_amount,
+                    penalty_reason as "penalty_reason: PenaltyReason",
+                    tariff_id
             "#,  
             self.id,
             self.ticket_status as TicketStatus,
@@ -244,14 +406,20 @@ impl ParkingHistory {
             self.updated_at,
             self.check_in_date,
             self.check_out_date,
+            self.vehicle_id,
+            self.plate_number,
+            self.penalty_amount,
+            self.penalty_reason as Option<PenaltyReason>,
+            self.tariff_id,
         )
-            .fetch_one(pool)
-            .await?;
+            .fetch_one(executor)
+            .await
+            .map_err(map_active_ticket_violation)?;
 
         Ok(data)
     }
 
-    pub async fn find_one(id: Uuid, pool: &Pool<Postgres>) -> ResultApp<ParkingHistory> {
+    pub async fn find_one(id: Uuid, executor: impl PgExecutor<'_>) -> ResultApp<ParkingHistory> {
         let data = sqlx::query_as!(
             ParkingHistory, 
             r#"
@@ -268,18 +436,125 @@ impl ParkingHistory {
                     created_at, 
                     updated_at,
                     check_in_date,
-                    check_out_date
+                    check_out_date,
+                    vehicle_id,
+                    plate_number,
+                    penalty_amount,
+                    penalty_reason as "penalty_reason: PenaltyReason",
+                    tariff_id
                 from "parking_history" 
-                where id = $1"#,  
+                where id = $1 and deleted_at is null"#,  
             id
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(data)
     }
+
+    /// Tickets still in use, either waiting for check in or parked, in a lot or
+    /// issued by a keeper. These keep the lot and the keeper from being archived.
+    pub async fn count_active(parking_lot_id: Option<Uuid>, keeper_id: Option<Uuid>, executor: impl PgExecutor<'_>) -> ResultApp<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+                select count(*) as "count!"
+                from "parking_history"
+                where ticket_status in ('active', 'default') and
+                    deleted_at is null and
+                    ($1::uuid is null or parking_lot_id = $1) and
+                    ($2::uuid is null or keeper_id = $2)
+            "#,
+            parking_lot_id,
+            keeper_id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Only finished tickets can be archived, an active one still has to be
+    /// checked out or expire first.
+    pub async fn archive(id: Uuid, owner_id: Uuid, deleted_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> ResultApp<ParkingHistory> {
+        let data = sqlx::query_as!(
+            ParkingHistory,
+            r#"
+                update "parking_history"
+                set deleted_at = $3,
+                    updated_at = $3
+                where id = $1 and
+                    owner_id = $2 and
+                    deleted_at is null and
+                    ticket_status in ('not_active', 'expired')
+                returning id, 
+                    ticket_status as "ticket_status!: TicketStatus", 
+                    vehicle_type as "vehicle_type!: VehicleType", 
+                    payment as "payment!: PaymentType", 
+                    amount,
+                    parking_lot_id,
+                    easypark_id,
+                    keeper_id,
+                    owner_id,
+                    transaction_id,
+                    created_at, 
+                    updated_at,
+                    check_in_date,
+                    check_out_date,
+                    vehicle_id,
+                    plate_number,
+                    penalty_amount,
+                    penalty_reason as "penalty_reason: PenaltyReason",
+                    tariff_id
+            "#,
+            id,
+            owner_id,
+            deleted_at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        data.ok_or(Error::Conflict("Only a finished ticket of your own lot can be archived".to_string()))
+    }
+
+    pub async fn restore(id: Uuid, owner_id: Uuid, updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> ResultApp<ParkingHistory> {
+        let data = sqlx::query_as!(
+            ParkingHistory,
+            r#"
+                update "parking_history"
+                set deleted_at = null,
+                    updated_at = $3
+                where id = $1 and owner_id = $2 and deleted_at is not null
+                returning id, 
+                    ticket_status as "ticket_status!: TicketStatus", 
+                    vehicle_type as "vehicle_type!: VehicleType", 
+                    payment as "payment!: PaymentType", 
+                    amount,
+                    parking_lot_id,
+                    easypark_id,
+                    keeper_id,
+                    owner_id,
+                    transaction_id,
+                    created_at, 
+                    updated_at,
+                    check_in_date,
+                    check_out_date,
+                    vehicle_id,
+                    plate_number,
+                    penalty_amount,
+                    penalty_reason as "penalty_reason: PenaltyReason",
+                    tariff_id
+            "#,
+            id,
+            owner_id,
+            updated_at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        data.ok_or(Error::NotFoundRejection("Archived ticket is not found".to_string()))
+    }
     
-    pub async fn update_transaction_id(old_transaction_id: Uuid, new_transaction_id: Uuid, pool: &Pool<Postgres>) -> ResultApp<ParkingHistoryWithTotalAmount> {
+    pub async fn update_transaction_id(old_transaction_id: Uuid, new_transaction_id: Uuid, executor: impl PgExecutor<'_>) -> ResultApp<ParkingHistoryWithTotalAmount> {
         let user = sqlx::query_as!(
             ParkingHistoryWithTotalAmount, 
             r#"
@@ -301,6 +576,10 @@ impl ParkingHistory {
                         updated_at,
                         check_in_date,
                         check_out_date,
+                        vehicle_id,
+                        plate_number,
+                        penalty_amount,
+                        penalty_reason as "penalty_reason: PenaltyReason",
                         ceil(extract(epoch from (now() - check_in_date)) / 3600) * amount as total_amount
                 ),
                 update_transaction as (
@@ -313,39 +592,108 @@ impl ParkingHistory {
             old_transaction_id,
             new_transaction_id
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
     }
 
-    async fn count(payload: AggregateQuery, pool: &Pool<Postgres>) -> ResultApp<SqlxCount> {
-        let data = sqlx::query_as!(
-            SqlxCount, 
+    async fn count(payload: AggregateQuery, executor: impl PgExecutor<'_>) -> ResultApp<SqlxCount> {
+        let data = payload
+            .count()
+            .build_query_scalar::<i64>()
+            .fetch_one(executor)
+            .await?;
+
+        Ok(SqlxCount { data: Some(data) })
+    }
+
+    async fn aggregate(payload: AggregateQuery, executor: impl PgExecutor<'_>) -> ResultApp<Vec<RelatedParkingHistory>> {
+        let mut query = payload.select(
             r#"
-                select count(*) as data
-                from parking_history ph
-                join parking_lot pl on pl.id = ph.parking_lot_id
-                where ($1::timestamp is null or ph.created_at >= $1) and ($2::timestamp is null or ph.created_at <= $2) and
-                    (($3::uuid is not null and ph.easypark_id = $3) or ($4::uuid is not null and ph.owner_id = $4) or ($5::uuid is not null and ph.keeper_id = $5) or ($3::uuid is null and $4::uuid is null and $5::uuid is null)) and
-                    ($6 = 'default' or ph.ticket_status = $6::ticket_status) and
-                    ($7 = 'default' or ph.payment = $7::payment_type)
+                ph.id,
+                ph.ticket_status,
+                ph.vehicle_type,
+                ph.payment,
+                ph.amount,
+                ph.created_at,
+                ph.updated_at,
+                pl.area_name,
+                pl.address,
+                pl.image_url,
+                CEIL(EXTRACT(EPOCH FROM (NOW() - ph.check_in_date)) / 3600) * ph.amount AS forecast_amount,
+                cast (tx.gross_amount as float) as total_amount,
+                ph.check_in_date,
+                ph.check_out_date,
+                ph.plate_number,
+                ph.penalty_amount,
+                ph.penalty_reason,
+                u."name" as easypark_name
             "#,
-            payload.created_at_start_filter,
-            payload.created_at_end_filter,
-            payload.easypark_id,
-            payload.owner_id,
-            payload.keeper_id,
-            payload.ticket_status.unwrap_or(TicketStatus::Default) as TicketStatus,
-            payload.payment_type.unwrap_or(PaymentType::Default) as PaymentType,
-        )
-            .fetch_one(pool)
+        );
+        push_page(&mut query, "ph", payload.cursor, payload.sort, payload.take, payload.skip);
+
+        let data = query
+            .build_query_as::<HistoryFromQuery>()
+            .fetch_all(executor)
             .await?;
 
+        let data: Vec<RelatedParkingHistory> = data.into_iter()
+            .map(HistoryFromQuery::into_related_parking_history)
+            .collect();
+
         Ok(data)
     }
 
-    async fn aggregate(payload: AggregateQuery, pool: &Pool<Postgres>) -> ResultApp<Vec<RelatedParkingHistory>> {
+    /// Same filters as `aggregate` without `take`/`skip`. Rows are streamed so an
+    /// export of the whole history never sits in memory at once.
+    pub fn export(
+        payload: AggregateQuery,
+        timezone: String,
+        pool: PgPool,
+    ) -> impl Stream<Item = ResultApp<ExportRow>> {
+        try_stream! {
+            let mut query = QueryBuilder::new("select ");
+            query
+                .push(r#"
+                    ph.id,
+                    to_char((ph.created_at at time zone 'UTC') at time zone "#)
+                .push_bind(timezone.clone())
+                .push(r#", 'DD/MM/YYYY HH24:MI') as created_at,
+                    to_char((ph.check_in_date at time zone 'UTC') at time zone "#)
+                .push_bind(timezone.clone())
+                .push(r#", 'DD/MM/YYYY HH24:MI') as check_in_date,
+                    to_char((ph.check_out_date at time zone 'UTC') at time zone "#)
+                .push_bind(timezone)
+                .push(r#", 'DD/MM/YYYY HH24:MI') as check_out_date,
+                    cast(extract(epoch from (ph.check_out_date - ph.check_in_date)) / 60 as bigint) as duration_minutes,
+                    pl.area_name,
+                    pl.address,
+                    u."name" as easypark_name,
+                    u.phone_number as easypark_phone_number,
+                    ku."name" as keeper_name,
+                    ph.plate_number,
+                    ph.vehicle_type,
+                    ph.payment,
+                    ph.ticket_status,
+                    ph.amount,
+                    ph.penalty_amount,
+                    ph.penalty_reason,
+                    cast(tx.gross_amount as float) as settled_amount
+                "#)
+                .push(AggregateQuery::FROM)
+                .push(r#" join "user" ku on ku.id = ph.keeper_id where true"#);
+            payload.push_filters(&mut query);
+            query.push(" order by ph.created_at, ph.id");
+
+            let mut rows = query.build_query_as::<ExportRow>().fetch(&pool);
+            while let Some(row) = rows.try_next().await? {
+                yield row;
+            }
+        }
+    }
+
+    async fn find_active_ticket(easypark_id: Uuid, executor: impl PgExecutor<'_>) -> ResultApp<Vec<RelatedParkingHistory>> {
         let data = sqlx::query_as!(
             HistoryFromQuery, 
             r#"
@@ -360,32 +708,22 @@ impl ParkingHistory {
                     pl.address,
                     pl.image_url,
                     CEIL(EXTRACT(EPOCH FROM (NOW() - ph.check_in_date)) / 3600) * ph.amount AS forecast_amount,
-                    cast (tx.gross_amount as float) as total_amount,
-                    ph.check_in_date, 
+                    cast(tx.gross_amount as float) as total_amount,
+                    ph.check_in_date,
                     ph.check_out_date,
+                    ph.plate_number,
+                    ph.penalty_amount,
+                    ph.penalty_reason as "penalty_reason: PenaltyReason",
                     u."name" as easypark_name
                 from parking_history ph
                 join parking_lot pl on pl.id = ph.parking_lot_id
                 join transaction_history tx on tx.id = ph.transaction_id
                 join "user" u on u.id = ph.easypark_id
-                where ($1::timestamp is null or ph.created_at >= $1) and ($2::timestamp is null or ph.created_at <= $2) and
-                    (($3::uuid is not null and ph.easypark_id = $3) or ($4::uuid is not null and ph.owner_id = $4) or ($5::uuid is not null and ph.keeper_id = $5) or ($3::uuid is null and $4::uuid is null and $5::uuid is null)) and
-                    ($6 = 'default' or ph.ticket_status = $6::ticket_status) and
-                    ($7 = 'default' or ph.payment = $7::payment_type)
-                limit $8
-                offset $9
+                where ph.easypark_id = $1 and ph.ticket_status in ('active', 'default') and ph.deleted_at is null
             "#,
-            payload.created_at_start_filter,
-            payload.created_at_end_filter,
-            payload.easypark_id,
-            payload.owner_id,
-            payload.keeper_id,
-            payload.ticket_status.unwrap_or(TicketStatus::Default) as TicketStatus,
-            payload.payment_type.unwrap_or(PaymentType::Default) as PaymentType,
-            payload.take,
-            payload.skip,
+            easypark_id
         )
-            .fetch_all(pool)
+            .fetch_all(executor)
             .await?;
 
         let data: Vec<RelatedParkingHistory> = data.into_iter()
@@ -394,17 +732,17 @@ impl ParkingHistory {
 
         Ok(data)
     }
-    
-    async fn find_active_ticket(easypark_id: Uuid, pool: &Pool<Postgres>) -> ResultApp<Vec<RelatedParkingHistory>> {
+
+    async fn search_active_ticket_by_plate(parking_lot_id: Uuid, plate_number: String, executor: impl PgExecutor<'_>) -> ResultApp<Vec<RelatedParkingHistory>> {
         let data = sqlx::query_as!(
-            HistoryFromQuery, 
+            HistoryFromQuery,
             r#"
-                select ph.id, 
-                    ph.ticket_status as "ticket_status!: TicketStatus", 
-                    ph.vehicle_type as "vehicle_type!: VehicleType", 
-                    ph.payment as "payment!: PaymentType", 
+                select ph.id,
+                    ph.ticket_status as "ticket_status!: TicketStatus",
+                    ph.vehicle_type as "vehicle_type!: VehicleType",
+                    ph.payment as "payment!: PaymentType",
                     ph.amount,
-                    ph.created_at, 
+                    ph.created_at,
                     ph.updated_at,
                     pl.area_name,
                     pl.address,
@@ -413,16 +751,208 @@ impl ParkingHistory {
                     cast(tx.gross_amount as float) as total_amount,
                     ph.check_in_date,
                     ph.check_out_date,
+                    ph.plate_number,
+                    ph.penalty_amount,
+                    ph.penalty_reason as "penalty_reason: PenaltyReason",
                     u."name" as easypark_name
                 from parking_history ph
                 join parking_lot pl on pl.id = ph.parking_lot_id
                 join transaction_history tx on tx.id = ph.transaction_id
                 join "user" u on u.id = ph.easypark_id
-                where ph.easypark_id = $1 and ph.ticket_status in ('active', 'default')
+                where ph.parking_lot_id = $1 and
+                    ph.ticket_status in ('active', 'default') and
+                    ph.deleted_at is null and
+                    replace(ph.plate_number, ' ', '') like '%' || $2 || '%'
+                order by ph.created_at desc
+            "#,
+            parking_lot_id,
+            plate_number
+        )
+            .fetch_all(executor)
+            .await?;
+
+        let data: Vec<RelatedParkingHistory> = data.into_iter()
+            .map(HistoryFromQuery::into_related_parking_history)
+            .collect();
+
+        Ok(data)
+    }
+
+    async fn find_active_ticket_in_lot(parking_lot_id: Uuid, plate_number: Option<String>, easypark_id: Option<Uuid>, executor: impl PgExecutor<'_>) -> ResultApp<Vec<ParkingHistory>> {
+        let data = sqlx::query_as!(
+            ParkingHistory,
+            r#"
+                select id,
+                    ticket_status as "ticket_status!: TicketStatus",
+                    vehicle_type as "vehicle_type!: VehicleType",
+                    payment as "payment!: PaymentType",
+                    amount,
+                    parking_lot_id,
+                    easypark_id,
+                    keeper_id,
+                    owner_id,
+                    transaction_id,
+                    created_at,
+                    updated_at,
+                    check_in_date,
+                    check_out_date,
+                    vehicle_id,
+                    plate_number,
+                    penalty_amount,
+                    penalty_reason as "penalty_reason: PenaltyReason",
+                    tariff_id
+                from "parking_history"
+                where parking_lot_id = $1 and
+                    ticket_status in ('active', 'default') and
+                    deleted_at is null and
+                    ($2::text is null or plate_number = $2) and
+                    ($3::uuid is null or easypark_id = $3)
             "#,
+            parking_lot_id,
+            plate_number,
             easypark_id
         )
-            .fetch_all(pool)
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+
+    async fn apply_penalty(id: Uuid, penalty_amount: f64, penalty_reason: PenaltyReason, updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> ResultApp<ParkingHistory> {
+        let data = sqlx::query_as!(
+            ParkingHistory,
+            r#"
+                update "parking_history"
+                set penalty_amount = $1,
+                    penalty_reason = $2,
+                    updated_at = $3
+                where id = $4 and penalty_reason is null
+                returning id,
+                    ticket_status as "ticket_status!: TicketStatus",
+                    vehicle_type as "vehicle_type!: VehicleType",
+                    payment as "payment!: PaymentType",
+                    amount,
+                    parking_lot_id,
+                    easypark_id,
+                    keeper_id,
+                    owner_id,
+                    transaction_id,
+                    created_at,
+                    updated_at,
+                    check_in_date,
+                    check_out_date,
+                    vehicle_id,
+                    plate_number,
+                    penalty_amount,
+                    penalty_reason as "penalty_reason: PenaltyReason",
+                    tariff_id
+            "#,
+            penalty_amount,
+            penalty_reason as PenaltyReason,
+            updated_at,
+            id
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        data.ok_or_else(|| Error::Conflict("Penalty already applied".to_string()))
+    }
+
+    pub async fn expire_unconfirmed(created_before: NaiveDateTime, updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> ResultApp<Vec<StaleTicket>> {
+        let data = sqlx::query_as!(
+            StaleTicket,
+            r#"
+                with expired as (
+                    update parking_history
+                    set ticket_status = 'expired',
+                        updated_at = $2
+                    where ticket_status = 'default' and created_at < $1
+                    returning id, plate_number, parking_lot_id, easypark_id, keeper_id
+                )
+                select e.id,
+                    e.plate_number,
+                    pl.area_name,
+                    eu.phone_number as easypark_phone_number,
+                    ku.phone_number as keeper_phone_number
+                from expired e
+                join parking_lot pl on pl.id = e.parking_lot_id
+                join "user" eu on eu.id = e.easypark_id
+                join "user" ku on ku.id = e.keeper_id
+            "#,
+            created_before,
+            updated_at
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+
+    pub async fn flag_for_review(checked_in_before: NaiveDateTime, flagged_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> ResultApp<Vec<StaleTicket>> {
+        let data = sqlx::query_as!(
+            StaleTicket,
+            r#"
+                with flagged as (
+                    update parking_history
+                    set flagged_for_review_at = $2
+                    where ticket_status = 'active' and
+                        flagged_for_review_at is null and
+                        check_in_date < $1
+                    returning id, plate_number, parking_lot_id, easypark_id, keeper_id
+                )
+                select f.id,
+                    f.plate_number,
+                    pl.area_name,
+                    eu.phone_number as easypark_phone_number,
+                    ku.phone_number as keeper_phone_number
+                from flagged f
+                join parking_lot pl on pl.id = f.parking_lot_id
+                join "user" eu on eu.id = f.easypark_id
+                join "user" ku on ku.id = f.keeper_id
+            "#,
+            checked_in_before,
+            flagged_at
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+
+    async fn find_flagged_for_review(parking_lot_id: Uuid, executor: impl PgExecutor<'_>) -> ResultApp<Vec<RelatedParkingHistory>> {
+        let data = sqlx::query_as!(
+            HistoryFromQuery,
+            r#"
+                select ph.id,
+                    ph.ticket_status as "ticket_status!: TicketStatus",
+                    ph.vehicle_type as "vehicle_type!: VehicleType",
+                    ph.payment as "payment!: PaymentType",
+                    ph.amount,
+                    ph.created_at,
+                    ph.updated_at,
+                    pl.area_name,
+                    pl.address,
+                    pl.image_url,
+                    CEIL(EXTRACT(EPOCH FROM (NOW() - ph.check_in_date)) / 3600) * ph.amount AS forecast_amount,
+                    cast(tx.gross_amount as float) as total_amount,
+                    ph.check_in_date,
+                    ph.check_out_date,
+                    ph.plate_number,
+                    ph.penalty_amount,
+                    ph.penalty_reason as "penalty_reason: PenaltyReason",
+                    u."name" as easypark_name
+                from parking_history ph
+                join parking_lot pl on pl.id = ph.parking_lot_id
+                join transaction_history tx on tx.id = ph.transaction_id
+                join "user" u on u.id = ph.easypark_id
+                where ph.parking_lot_id = $1 and
+                    ph.ticket_status = 'active' and
+                    ph.flagged_for_review_at is not null
+                order by ph.flagged_for_review_at
+            "#,
+            parking_lot_id
+        )
+            .fetch_all(executor)
             .await?;
 
         let data: Vec<RelatedParkingHistory> = data.into_iter()
@@ -431,8 +961,8 @@ impl ParkingHistory {
 
         Ok(data)
     }
-    
-    async fn monthly_record(owner_id: Uuid, pool: &Pool<Postgres>) -> ResultApp<Vec<MonthlyRecord>> {
+
+    async fn monthly_record(owner_id: Uuid, executor: impl PgExecutor<'_>) -> ResultApp<Vec<MonthlyRecord>> {
         let data = sqlx::query_as!(
             MonthlyRecord, 
             r#"
@@ -442,6 +972,7 @@ impl ParkingHistory {
                 FROM
                     parking_history
                 WHERE owner_id = $1 and
+                    deleted_at is null and
                     EXTRACT(YEAR FROM created_at) = EXTRACT(YEAR FROM CURRENT_DATE)
                 GROUP BY
                     month
@@ -450,13 +981,13 @@ impl ParkingHistory {
             "#,
             owner_id
         )
-            .fetch_all(pool)
+            .fetch_all(executor)
             .await?;
 
         Ok(data)
     }
 
-    async fn filtered_calc(payload: CalcQuery, pool: &Pool<Postgres>) -> ResultApp<CalcHistory> {
+    pub async fn filtered_calc(payload: CalcQuery, executor: impl PgExecutor<'_>) -> ResultApp<CalcHistory> {
         let data = sqlx::query_as!(
             CalcHistory, 
             r#"
@@ -465,6 +996,7 @@ impl ParkingHistory {
                 from parking_history ph
                 join transaction_history th ON ph.transaction_id = th.id 
                 where ticket_status = 'not_active' and 
+                    ph.deleted_at is null and
                     ph.created_at >= $1 and 
                     ph.created_at <= $2 and 
                     ($3::uuid is null or ph.owner_id = $3) and
@@ -475,7 +1007,7 @@ impl ParkingHistory {
             payload.owner_id,
             payload.keeper_id,
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(data)
@@ -499,10 +1031,11 @@ pub struct UpdateParkingHistory {
     pub updated_at: Option<NaiveDateTime>,
     pub check_in_date: Option<NaiveDateTime>,
     pub check_out_date: Option<NaiveDateTime>,
+    pub tariff_id: Option<Uuid>,
 }
 
 impl UpdateParkingHistory {
-    pub async fn update(self, id: Uuid, pool: &Pool<Postgres>) -> ResultApp<ParkingHistory> {
+    pub async fn update(self, id: Uuid, executor: impl PgExecutor<'_>) -> ResultApp<ParkingHistory> {
         let user = sqlx::query_as!(
             ParkingHistory, 
             r#"
@@ -519,8 +1052,9 @@ impl UpdateParkingHistory {
                     created_at = coalesce($10, "parking_history".created_at),                     
                     updated_at = coalesce($11, "parking_history".updated_at),
                     check_in_date = coalesce($12, "parking_history".check_in_date),                     
-                    check_out_date = coalesce($13, "parking_history".check_out_date)
-                where id = $14
+                    check_out_date = coalesce($13, "parking_history".check_out_date),
+                    tariff_id = coalesce($15, "parking_history".tariff_id)
+                where id = $14 and deleted_at is null
                 returning id, 
                     ticket_status as "ticket_status!: TicketStatus", 
                     vehicle_type as "vehicle_type!: VehicleType", 
@@ -534,11 +1068,16 @@ impl UpdateParkingHistory {
                     created_at, 
                     updated_at,
                     check_in_date,
-                    check_out_date
+                    check_out_date,
+                    vehicle_id,
+                    plate_number,
+                    penalty_amount,
+                    penalty_reason as "penalty_reason: PenaltyReason",
+                    tariff_id
             "#,
-            self.ticket_status.unwrap_or(TicketStatus::Default) as TicketStatus,
-            self.vehicle_type.unwrap_or(VehicleType::Default) as VehicleType,
-            self.payment.unwrap_or(PaymentType::Default) as PaymentType,
+            self.ticket_status as Option<TicketStatus>,
+            self.vehicle_type as Option<VehicleType>,
+            self.payment as Option<PaymentType>,
             self.amount,
             self.parking_lot_id,
             self.easypark_id,
@@ -549,15 +1088,17 @@ impl UpdateParkingHistory {
             self.updated_at,
             self.check_in_date,
             self.check_out_date,
-            id
+            id,
+            self.tariff_id
         )
-            .fetch_one(pool)
-            .await?;
+            .fetch_one(executor)
+            .await
+            .map_err(map_active_ticket_violation)?;
 
         Ok(user)
     }
     
-    pub async fn update_ticket_status(transaction_id: Uuid, status: TicketStatus, check_out_date: NaiveDateTime, pool: &Pool<Postgres>) -> ResultApp<ParkingHistory> {
+    pub async fn update_ticket_status(transaction_id: Uuid, status: TicketStatus, check_out_date: NaiveDateTime, executor: impl PgExecutor<'_>) -> ResultApp<ParkingHistory> {
         let user = sqlx::query_as!(
             ParkingHistory, 
             r#"
@@ -578,13 +1119,18 @@ impl UpdateParkingHistory {
                     created_at, 
                     updated_at,
                     check_in_date,
-                    check_out_date
+                    check_out_date,
+                    vehicle_id,
+                    plate_number,
+                    penalty_amount,
+                    penalty_reason as "penalty_reason: PenaltyReason",
+                    tariff_id
             "#,
             status as TicketStatus,
             check_out_date,
             transaction_id
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
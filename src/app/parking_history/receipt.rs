@@ -0,0 +1,281 @@
+use chrono::NaiveDateTime;
+use printpdf::{BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfLayerReference, Point};
+use rand::{rngs::OsRng, Rng};
+use serde::Serialize;
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::error::aggregate::{Error, Result};
+
+use super::{PaymentType, PenaltyReason, VehicleType};
+
+/// Letters and digits that cannot be misread for each other when a code is
+/// typed back from a printed receipt.
+const RECEIPT_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const RECEIPT_CODE_LENGTH: usize = 10;
+
+pub fn generate_receipt_code() -> String {
+    let mut rng = OsRng;
+    (0..RECEIPT_CODE_LENGTH)
+        .map(|_| RECEIPT_CODE_ALPHABET[rng.gen_range(0..RECEIPT_CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// A completed parking session as printed on the receipt. Check-in and
+/// check-out are already converted to the requested timezone.
+#[derive(Debug, Serialize)]
+pub struct Receipt {
+    pub id: Uuid,
+    pub receipt_code: String,
+    pub vehicle_type: VehicleType,
+    pub payment: PaymentType,
+    pub plate_number: Option<String>,
+    pub area_name: String,
+    pub address: String,
+    pub check_in_at: Option<NaiveDateTime>,
+    pub check_out_at: Option<NaiveDateTime>,
+    pub duration_minutes: Option<i64>,
+    pub billed_hours: Option<i64>,
+    pub hourly_rate: f64,
+    pub penalty_amount: f64,
+    pub penalty_reason: Option<PenaltyReason>,
+    pub midtrans_transaction_id: Option<String>,
+    pub midtrans_payment_type: Option<String>,
+    pub gross_amount: Option<String>,
+}
+
+impl Receipt {
+    /// Gives the ticket its verification code the first time a receipt is
+    /// printed, later calls return the same code.
+    pub async fn assign_code(id: Uuid, executor: impl PgExecutor<'_>) -> Result<String> {
+        let code = sqlx::query_scalar!(
+            r#"
+                update "parking_history"
+                set receipt_code = coalesce(receipt_code, $2)
+                where id = $1 and deleted_at is null
+                returning receipt_code as "receipt_code!"
+            "#,
+            id,
+            generate_receipt_code()
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(code)
+    }
+
+    pub async fn find_by_code(code: &str, timezone: &str, executor: impl PgExecutor<'_>) -> Result<Receipt> {
+        let receipt = sqlx::query_as!(
+            Receipt,
+            r#"
+                select ph.id,
+                    ph.receipt_code as "receipt_code!",
+                    ph.vehicle_type as "vehicle_type!: VehicleType",
+                    ph.payment as "payment!: PaymentType",
+                    ph.plate_number,
+                    pl.area_name,
+                    pl.address,
+                    (ph.check_in_date at time zone 'UTC') at time zone $2 as check_in_at,
+                    (ph.check_out_date at time zone 'UTC') at time zone $2 as check_out_at,
+                    cast(floor(extract(epoch from (ph.check_out_date - ph.check_in_date)) / 60) as bigint) as duration_minutes,
+                    cast(ceil(extract(epoch from (ph.check_out_date - ph.check_in_date)) / 3600) as bigint) as billed_hours,
+                    ph.amount as hourly_rate,
+                    ph.penalty_amount,
+                    ph.penalty_reason as "penalty_reason: PenaltyReason",
+                    th.transaction_id as "midtrans_transaction_id?",
+                    th.payment_type as "midtrans_payment_type?",
+                    th.gross_amount as "gross_amount?"
+                from parking_history ph
+                join parking_lot pl on pl.id = ph.parking_lot_id
+                left join transaction_history th on th.id = ph.transaction_id
+                where ph.receipt_code = $1 and ph.ticket_status = 'not_active' and ph.deleted_at is null
+            "#,
+            code,
+            timezone
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        receipt.ok_or(Error::NotFoundRejection("Receipt is not found".to_string()))
+    }
+
+    pub fn parking_fee(&self) -> f64 {
+        self.billed_hours.unwrap_or(0) as f64 * self.hourly_rate
+    }
+
+    /// What the driver paid: the settled Midtrans amount when the ticket went
+    /// through the gateway, otherwise the fee the keeper collected in cash.
+    pub fn total(&self) -> f64 {
+        self.gross_amount
+            .as_deref()
+            .and_then(|gross_amount| gross_amount.parse().ok())
+            .unwrap_or_else(|| self.parking_fee() + self.penalty_amount)
+    }
+
+    pub fn render_pdf(&self, timezone: &str) -> Result<Vec<u8>> {
+        let (document, page, layer) =
+            PdfDocument::new(format!("Receipt {}", self.receipt_code), Mm(105.0), Mm(180.0), "Receipt");
+        let regular = document
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(|err| Error::InternalServerError(err.to_string()))?;
+        let bold = document
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(|err| Error::InternalServerError(err.to_string()))?;
+
+        let mut sheet = Sheet {
+            layer: document.get_page(page).get_layer(layer),
+            regular,
+            bold,
+            y: 165.0,
+        };
+
+        sheet.title("EasyPark");
+        sheet.text("Parking receipt", 10.0);
+        sheet.gap(4.0);
+        sheet.heading(&self.area_name);
+        sheet.text(&self.address, 8.0);
+        sheet.rule();
+
+        sheet.row("Plate number", self.plate_number.as_deref().unwrap_or("-"));
+        sheet.row("Vehicle", vehicle_label(&self.vehicle_type));
+        sheet.row("Check in", &format_time(self.check_in_at));
+        sheet.row("Check out", &format_time(self.check_out_at));
+        sheet.row("Duration", &format_duration(self.duration_minutes));
+        sheet.row("Timezone", timezone);
+        sheet.rule();
+
+        sheet.row(
+            &format!("Parking {} h x {}", self.billed_hours.unwrap_or(0), format_rupiah(self.hourly_rate)),
+            &format_rupiah(self.parking_fee()),
+        );
+        if self.penalty_amount > 0.0 {
+            sheet.row(
+                &format!("Penalty ({})", penalty_label(self.penalty_reason)),
+                &format_rupiah(self.penalty_amount),
+            );
+        }
+        sheet.total_row("Total paid", &format_rupiah(self.total()));
+        sheet.rule();
+
+        sheet.row("Payment", payment_label(&self.payment, self.midtrans_payment_type.as_deref()));
+        sheet.row("Transaction id", self.midtrans_transaction_id.as_deref().unwrap_or("-"));
+        sheet.row("Ticket id", &self.id.to_string());
+        sheet.rule();
+
+        sheet.text("Verification code", 8.0);
+        sheet.title(&self.receipt_code);
+        sheet.text("Show this code to verify the receipt with EasyPark.", 7.0);
+
+        document
+            .save_to_bytes()
+            .map_err(|err| Error::InternalServerError(err.to_string()))
+    }
+}
+
+/// Writes the receipt top to bottom, `y` is the baseline of the next line.
+struct Sheet {
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    y: f32,
+}
+
+impl Sheet {
+    const LEFT: f32 = 8.0;
+    const VALUE: f32 = 52.0;
+    const RIGHT: f32 = 97.0;
+
+    fn gap(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn title(&mut self, text: &str) {
+        self.layer.use_text(text, 16.0, Mm(Self::LEFT), Mm(self.y), &self.bold);
+        self.gap(7.0);
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.layer.use_text(text, 11.0, Mm(Self::LEFT), Mm(self.y), &self.bold);
+        self.gap(5.0);
+    }
+
+    fn text(&mut self, text: &str, size: f32) {
+        self.layer.use_text(text, size, Mm(Self::LEFT), Mm(self.y), &self.regular);
+        self.gap(size * 0.5 + 1.0);
+    }
+
+    fn row(&mut self, label: &str, value: &str) {
+        self.layer.use_text(label, 8.0, Mm(Self::LEFT), Mm(self.y), &self.regular);
+        self.layer.use_text(value, 8.0, Mm(Self::VALUE), Mm(self.y), &self.regular);
+        self.gap(5.0);
+    }
+
+    fn total_row(&mut self, label: &str, value: &str) {
+        self.layer.use_text(label, 10.0, Mm(Self::LEFT), Mm(self.y), &self.bold);
+        self.layer.use_text(value, 10.0, Mm(Self::VALUE), Mm(self.y), &self.bold);
+        self.gap(6.0);
+    }
+
+    fn rule(&mut self) {
+        self.gap(1.0);
+        self.layer.set_outline_thickness(0.5);
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(Self::LEFT), Mm(self.y + 3.0)), false),
+                (Point::new(Mm(Self::RIGHT), Mm(self.y + 3.0)), false),
+            ],
+            is_closed: false,
+        });
+        self.gap(4.0);
+    }
+}
+
+fn format_time(time: Option<NaiveDateTime>) -> String {
+    time.map(|time| time.format("%d %b %Y %H:%M").to_string())
+        .unwrap_or_else(|| String::from("-"))
+}
+
+fn format_duration(minutes: Option<i64>) -> String {
+    match minutes {
+        Some(minutes) => format!("{}h {}m", minutes / 60, minutes % 60),
+        None => String::from("-"),
+    }
+}
+
+/// Rupiah without decimals and with dots between thousands, e.g. `Rp 12.500`.
+fn format_rupiah(amount: f64) -> String {
+    let digits = (amount.round() as i64).abs().to_string();
+    let mut grouped = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push('.');
+        }
+        grouped.push(digit);
+    }
+    let sign = if amount.round() < 0.0 { "-" } else { "" };
+    format!("{}Rp {}", sign, grouped)
+}
+
+fn vehicle_label(vehicle_type: &VehicleType) -> &'static str {
+    match vehicle_type {
+        VehicleType::Car => "Car",
+        VehicleType::Motor => "Motorcycle",
+        VehicleType::Default => "-",
+    }
+}
+
+fn penalty_label(reason: Option<PenaltyReason>) -> &'static str {
+    match reason {
+        Some(PenaltyReason::LostTicket) => "lost ticket",
+        Some(PenaltyReason::Overstay) => "overstay",
+        _ => "other",
+    }
+}
+
+fn payment_label<'a>(payment: &PaymentType, midtrans_payment_type: Option<&'a str>) -> &'a str {
+    match payment {
+        PaymentType::Cash => "Cash",
+        PaymentType::Qr => midtrans_payment_type.unwrap_or("QR"),
+        PaymentType::Default => "-",
+    }
+}
@@ -1,28 +1,46 @@
 use axum::{
+    body::{Body as ResponseBody, Bytes},
     extract::{Path, Query, State},
+    http::header,
     middleware,
+    response::{IntoResponse, Response},
     routing::{get, patch, post},
     Router,
 };
 use chrono::{DateTime, NaiveDateTime, Utc};
+use futures::{stream, StreamExt, TryStreamExt};
+use rust_xlsxwriter::{Workbook, XlsxError};
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Pool, Postgres};
+use sqlx::{PgConnection, PgPool, Pool, Postgres};
 use uuid::Uuid;
 
 use crate::{
     app::{
-        parking_area::ParkingLot,
+        analytics::refresh_hourly_stat,
+        parking_area::{
+            layout::{ParkingSpot, SpotAllocation, SpotKind},
+            rate::{AcceptedCategory, ParkingLotRate},
+            schedule::is_open,
+            tariff::Tariff,
+            ParkingLot,
+        },
+        parking_pass::ParkingPass,
         payment::TransactionHistory,
-        user::{Role, User},
+        report::{validate_timezone, DEFAULT_TIMEZONE},
+        user::{permit::DisabledPermit, Role, User},
+        vehicle::{category::VehicleCategory, normalize_plate_number, Vehicle},
     },
     error::aggregate::{Error, Result},
-    extractor::{app_body::Body, app_json::AppSuccess},
+    extractor::{app_body::Body, app_json::AppSuccess, current_user::CurrentUser},
     middleware::base::print_request_body,
+    types::pagination::{next_cursor, resolve_page, Cursor, Sort},
+    unit_of_work::UnitOfWork,
 };
 
 use super::{
-    AggregateQuery, CalcHistory, CalcQuery, MonthlyRecord, ParkingHistory, PaymentType,
-    RelatedParkingHistory, TicketStatus, UpdateParkingHistory, VehicleType,
+    receipt::Receipt,
+    AggregateQuery, CalcHistory, CalcQuery, ExportRow, MonthlyRecord, ParkingHistory, PaymentType,
+    PenaltyReason, RelatedParkingHistory, TicketStatus, UpdateParkingHistory, VehicleType,
 };
 
 pub fn build(pool: Pool<Postgres>) -> Router {
@@ -30,7 +48,15 @@ pub fn build(pool: Pool<Postgres>) -> Router {
         .route("/", post(create))
         .route("/:id", patch(update).get(detail))
         .route("/aggregate", get(aggregate))
+        .route("/export", get(export))
+        .route("/:id/receipt", get(receipt))
+        .route("/:id/archive", post(archive))
+        .route("/:id/restore", post(restore))
+        .route("/receipt/:code", get(verify_receipt))
         .route("/active-ticket/:id", get(get_active_ticket))
+        .route("/active-ticket/search", get(search_active_ticket))
+        .route("/penalty", post(apply_penalty))
+        .route("/review", get(get_review_ticket))
         .route("/monthly", get(get_monthly_history))
         .route("/filtered-calc", get(get_filtered_calc))
         .layer(middleware::from_fn(print_request_body))
@@ -41,7 +67,7 @@ pub fn build(pool: Pool<Postgres>) -> Router {
 
 #[derive(Serialize, Deserialize)]
 struct CreateParkingHistoryPayload {
-    vehicle_type: VehicleType,
+    vehicle_id: Uuid,
     payment: PaymentType,
     parking_lot_id: Uuid,
     easypark_id: Uuid,
@@ -54,11 +80,11 @@ struct CreateParkingHistoryPayload {
 }
 
 impl CreateParkingHistoryPayload {
-    fn into_parking_history(self) -> ParkingHistory {
+    fn into_parking_history(self, vehicle: Vehicle) -> ParkingHistory {
         ParkingHistory {
             id: Uuid::new_v4(),
             ticket_status: TicketStatus::Default,
-            vehicle_type: self.vehicle_type,
+            vehicle_type: vehicle.vehicle_type,
             payment: self.payment,
             amount: 0.0,
             parking_lot_id: self.parking_lot_id,
@@ -70,10 +96,68 @@ impl CreateParkingHistoryPayload {
             updated_at: None,
             check_in_date: None,
             check_out_date: None,
+            vehicle_id: Some(vehicle.id),
+            plate_number: Some(vehicle.plate_number),
+            penalty_amount: 0.0,
+            penalty_reason: None,
+            tariff_id: None,
         }
     }
 }
 
+/// The amount and tariff of a ticket for the category at the lot, priced by
+/// the tariff in force at `at`. A valid pass of the vehicle takes its discount
+/// off the rate.
+async fn price_ticket(
+    parking_lot_id: Uuid,
+    category: &VehicleCategory,
+    vehicle_id: Option<Uuid>,
+    at: NaiveDateTime,
+    pool: &PgPool,
+) -> Result<(f64, Uuid)> {
+    if !AcceptedCategory::is_accepted(parking_lot_id, category.id, pool).await? {
+        return Err(Error::BadRequest(format!(
+            "Parking lot does not accept {}",
+            category.name
+        )));
+    }
+
+    let tariff = Tariff::in_force(parking_lot_id, at, pool).await?;
+    let rate = ParkingLotRate::find_one(tariff.id, category.id, pool)
+        .await?
+        .ok_or_else(|| Error::BadRequest(format!("Parking lot has no rate for {}", category.name)))?;
+
+    let pass = match vehicle_id {
+        Some(vehicle_id) => ParkingPass::find_valid(parking_lot_id, vehicle_id, at, pool).await?,
+        None => None,
+    };
+    let amount = match pass {
+        Some(pass) => rate.cost * (100.0 - pass.discount_percent) / 100.0,
+        None => rate.cost,
+    };
+
+    Ok((amount, tariff.id))
+}
+
+/// Reserved spots only take the vehicles they are meant for.
+fn validate_spot(spot: &ParkingSpot, category: &VehicleCategory, disabled_permit: bool) -> Result<()> {
+    if !spot.is_active {
+        return Err(Error::BadRequest("Spot is not in use".to_string()));
+    }
+
+    match spot.kind {
+        SpotKind::Regular => Ok(()),
+        SpotKind::Disabled if disabled_permit => Ok(()),
+        SpotKind::Disabled => Err(Error::BadRequest(
+            "Spot is reserved for disabled drivers".to_string(),
+        )),
+        SpotKind::EvCharging if category.code == "ev" => Ok(()),
+        SpotKind::EvCharging => Err(Error::BadRequest(
+            "Spot is reserved for electric vehicles".to_string(),
+        )),
+    }
+}
+
 #[derive(Serialize)]
 struct History {
     parking_history: ParkingHistory,
@@ -82,9 +166,18 @@ struct History {
 
 async fn create(
     State(pool): State<PgPool>,
+    current_user: Option<CurrentUser>,
     Body(payload): Body<CreateParkingHistoryPayload>,
 ) -> Result<AppSuccess<History>> {
-    let mut parking_history = payload.into_parking_history();
+    let vehicle = Vehicle::find_one(payload.vehicle_id, &pool).await?;
+    if vehicle.user_id != payload.easypark_id {
+        return Err(Error::BadRequest(
+            "Provided vehicle is not belong to provided easypark".to_string(),
+        ));
+    }
+
+    let category = VehicleCategory::find_by_vehicle(&vehicle, &pool).await?;
+    let mut parking_history = payload.into_parking_history(vehicle);
 
     let easypark = User::find_one_by_id(parking_history.easypark_id, &pool).await?;
     if easypark.role != Role::Easypark {
@@ -95,8 +188,8 @@ async fn create(
 
     let related_history = ParkingHistory::find_active_ticket(easypark.id, &pool).await?;
 
-    if related_history.len() >= 1 {
-        return Err(Error::BadRequest("Ticket already issue".to_string()));
+    if !related_history.is_empty() {
+        return Err(Error::Conflict("Ticket already issue".to_string()));
     }
 
     let keeper = User::find_one_by_id(parking_history.keeper_id, &pool).await?;
@@ -113,11 +206,29 @@ async fn create(
         ));
     }
 
-    parking_history.amount = match parking_history.vehicle_type {
-        VehicleType::Default => parking_lot.car_cost,
-        VehicleType::Car => parking_lot.car_cost,
-        VehicleType::Motor => parking_lot.motor_cost,
-    };
+    // Outside opening hours only the owner of the lot, signed in, can let a
+    // vehicle in.
+    if !is_open(parking_lot.id, Utc::now().naive_utc(), &pool).await? {
+        let current_user = current_user
+            .ok_or(Error::BadRequest("Parking lot is closed".to_string()))?;
+        let caller = User::find_one(current_user.sub, &pool).await?;
+        if caller.role != Role::ParkOwner || caller.id != parking_lot.owner_id {
+            return Err(Error::Unauthorize(
+                "Only the owner of the parking lot can override its opening hours".to_string(),
+            ));
+        }
+    }
+
+    let (amount, tariff_id) = price_ticket(
+        parking_lot.id,
+        &category,
+        parking_history.vehicle_id,
+        Utc::now().naive_utc(),
+        &pool,
+    )
+    .await?;
+    parking_history.amount = amount;
+    parking_history.tariff_id = Some(tariff_id);
 
     let transaction_history = TransactionHistory {
         id: parking_history.transaction_id,
@@ -136,12 +247,16 @@ async fn create(
         currency: None,
     };
 
-    let transaction_history = transaction_history.save(&pool).await?;
+    let mut uow = UnitOfWork::begin(&pool).await?;
+
+    let transaction_history = transaction_history.save(uow.connection()).await?;
 
     parking_history.transaction_id = transaction_history.id;
     parking_history.owner_id = parking_lot.owner_id;
 
-    let parking_history = parking_history.save(&pool).await?;
+    let parking_history = parking_history.save(uow.connection()).await?;
+
+    uow.commit().await?;
 
     Ok(AppSuccess(History {
         parking_history,
@@ -162,6 +277,7 @@ struct UpdateParkingHistoryPayload {
     updated_at: Option<NaiveDateTime>,
     check_in_date: Option<NaiveDateTime>,
     check_out_date: Option<NaiveDateTime>,
+    spot_id: Option<Uuid>,
 }
 
 impl UpdateParkingHistoryPayload {
@@ -181,6 +297,7 @@ impl UpdateParkingHistoryPayload {
             updated_at: Some(Utc::now().naive_utc()),
             check_in_date: None,
             check_out_date: None,
+            tariff_id: None,
         }
     }
 }
@@ -190,8 +307,15 @@ async fn update(
     Path(id): Path<Uuid>,
     Body(payload): Body<UpdateParkingHistoryPayload>,
 ) -> Result<AppSuccess<History>> {
+    let spot_id = payload.spot_id;
     let mut parking_history = payload.into_update_parking_history();
 
+    if spot_id.is_some() && parking_history.ticket_status != Some(TicketStatus::Active) {
+        return Err(Error::BadRequest(
+            "A spot can only be allocated on check-in".to_string(),
+        ));
+    }
+
     match &parking_history.easypark_id {
         Some(id) => {
             let easypark = User::find_one_by_id(*id, &pool).await?;
@@ -208,7 +332,8 @@ async fn update(
         Some(status) => match status {
             TicketStatus::Default => {}
             TicketStatus::Active => parking_history.check_in_date = Some(Utc::now().naive_utc()),
-            TicketStatus::NotActive => {}
+            TicketStatus::NotActive => parking_history.check_out_date = Some(Utc::now().naive_utc()),
+            TicketStatus::Expired => {}
         },
         None => {}
     }
@@ -254,7 +379,53 @@ async fn update(
         None => {}
     }
 
-    let parking_history = parking_history.update(id, &pool).await?;
+    // A registered vehicle decides its own category, a vehicle_type in the
+    // payload only counts for old tickets issued without one.
+    let ticket = ParkingHistory::find_one(id, &pool).await?;
+    let category = match ticket.vehicle_id {
+        Some(vehicle_id) => {
+            let vehicle = Vehicle::find_one(vehicle_id, &pool).await?;
+            let category = VehicleCategory::find_by_vehicle(&vehicle, &pool).await?;
+            parking_history.vehicle_type = Some(category.vehicle_type.clone());
+            category
+        }
+        None => {
+            let vehicle_type = parking_history.vehicle_type.as_ref().unwrap_or(&ticket.vehicle_type);
+            VehicleCategory::find_by_vehicle_type(vehicle_type, &pool).await?
+        }
+    };
+
+    // The ticket keeps the price in force at check-in, later tariff changes
+    // do not reach it.
+    let mut spot = None;
+    if let Some(check_in_date) = parking_history.check_in_date {
+        let parking_lot_id = parking_history.parking_lot_id.unwrap_or(ticket.parking_lot_id);
+
+        let (amount, tariff_id) =
+            price_ticket(parking_lot_id, &category, ticket.vehicle_id, check_in_date, &pool).await?;
+        parking_history.amount = Some(amount);
+        parking_history.tariff_id = Some(tariff_id);
+
+        if let Some(spot_id) = spot_id {
+            let allocated = ParkingSpot::find_one(parking_lot_id, spot_id, &pool).await?;
+            let easypark_id = parking_history.easypark_id.unwrap_or(ticket.easypark_id);
+            let disabled_permit = DisabledPermit::is_valid(easypark_id, check_in_date, &pool).await?;
+            validate_spot(&allocated, &category, disabled_permit)?;
+            spot = Some(allocated);
+        }
+    }
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let parking_history = parking_history.update(id, uow.connection()).await?;
+    if let (Some(spot), Some(check_in_date)) = (&spot, parking_history.check_in_date) {
+        SpotAllocation::allocate(spot.id, parking_history.id, check_in_date, uow.connection()).await?;
+    }
+    if matches!(parking_history.ticket_status, TicketStatus::NotActive | TicketStatus::Expired) {
+        let released_at = parking_history.check_out_date.unwrap_or_else(|| Utc::now().naive_utc());
+        SpotAllocation::release(parking_history.id, released_at, uow.connection()).await?;
+    }
+    uow.commit().await?;
+
     let transaction_history =
         TransactionHistory::find_one(parking_history.transaction_id, &pool).await?;
 
@@ -279,10 +450,11 @@ async fn detail(
     Path(id): Path<Uuid>,
 ) -> Result<AppSuccess<DetailParkingHistory>> {
     let parking_history = ParkingHistory::find_one(id, &pool).await?;
-    let easypark = User::find_one_by_id(parking_history.easypark_id, &pool).await?;
-    let keeper = User::find_one_by_id(parking_history.keeper_id, &pool).await?;
-    let owner = User::find_one_by_id(parking_history.owner_id, &pool).await?;
-    let parking_lot = ParkingLot::find_one(parking_history.parking_lot_id, &pool).await?;
+    // People and lots may have been archived since, the ticket still shows them.
+    let easypark = User::find_one_by_id_with_archived(parking_history.easypark_id, &pool).await?;
+    let keeper = User::find_one_by_id_with_archived(parking_history.keeper_id, &pool).await?;
+    let owner = User::find_one_by_id_with_archived(parking_history.owner_id, &pool).await?;
+    let parking_lot = ParkingLot::find_one_with_archived(parking_history.parking_lot_id, &pool).await?;
     let transaction = TransactionHistory::find_one(parking_history.transaction_id, &pool).await?;
     Ok(AppSuccess(DetailParkingHistory {
         parking_history,
@@ -294,6 +466,64 @@ async fn detail(
     }))
 }
 
+#[derive(Deserialize)]
+struct ArchivePayload {
+    owner_id: Uuid,
+}
+
+/// The hourly occupancy only counts live tickets, the hours the ticket spent
+/// in the lot are recomputed with it archived or restored.
+async fn refresh_occupancy(parking_history: &ParkingHistory, connection: &mut PgConnection) -> Result<()> {
+    if let Some(check_in_date) = parking_history.check_in_date {
+        refresh_hourly_stat(check_in_date, Some(parking_history.parking_lot_id), connection).await?;
+    }
+
+    Ok(())
+}
+
+/// Hides a finished ticket from lists, reports and receipts of the owner.
+async fn archive(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<ArchivePayload>,
+) -> Result<AppSuccess<ParkingHistory>> {
+    let owner = User::find_one_by_id(payload.owner_id, &pool).await?;
+    if owner.role != Role::ParkOwner {
+        return Err(Error::BadRequest(
+            "Provided owner id is not having ParkOwner role".to_string(),
+        ));
+    }
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let parking_history =
+        ParkingHistory::archive(id, owner.id, Utc::now().naive_utc(), uow.connection()).await?;
+    refresh_occupancy(&parking_history, uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(parking_history))
+}
+
+async fn restore(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<ArchivePayload>,
+) -> Result<AppSuccess<ParkingHistory>> {
+    let owner = User::find_one_by_id(payload.owner_id, &pool).await?;
+    if owner.role != Role::ParkOwner {
+        return Err(Error::BadRequest(
+            "Provided owner id is not having ParkOwner role".to_string(),
+        ));
+    }
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let parking_history =
+        ParkingHistory::restore(id, owner.id, Utc::now().naive_utc(), uow.connection()).await?;
+    refresh_occupancy(&parking_history, uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(parking_history))
+}
+
 #[derive(Deserialize, Clone, Serialize)]
 pub struct AggregatePayload {
     pub payment_type: Option<PaymentType>,
@@ -305,10 +535,12 @@ pub struct AggregatePayload {
     pub created_at_end_filter: Option<DateTime<Utc>>,
     pub take: Option<i64>,
     pub skip: Option<i64>,
+    pub cursor: Option<String>,
+    pub sort: Option<Sort>,
 }
 
 impl AggregatePayload {
-    fn into_aggregate_query(self) -> AggregateQuery {
+    fn into_aggregate_query(self) -> Result<AggregateQuery> {
         let created_at_start_filter = match self.created_at_start_filter {
             Some(date) => Some(date.naive_utc()),
             None => None,
@@ -319,7 +551,9 @@ impl AggregatePayload {
             None => None,
         };
 
-        AggregateQuery {
+        let (cursor, skip) = resolve_page(self.cursor.as_deref(), self.skip)?;
+
+        Ok(AggregateQuery {
             payment_type: self.payment_type,
             ticket_status: self.ticket_status,
             easypark_id: self.easypark_id,
@@ -328,8 +562,10 @@ impl AggregatePayload {
             created_at_start_filter,
             created_at_end_filter,
             take: self.take,
-            skip: self.skip,
-        }
+            skip,
+            cursor,
+            sort: self.sort.unwrap_or_default(),
+        })
     }
 }
 
@@ -342,6 +578,7 @@ struct Aggregate {
 #[derive(Serialize)]
 struct MetaAggregate {
     total_data: i64,
+    next_cursor: Option<String>,
     query: AggregatePayload,
 }
 
@@ -349,20 +586,277 @@ async fn aggregate(
     State(pool): State<PgPool>,
     Query(payload): Query<AggregatePayload>,
 ) -> Result<AppSuccess<Aggregate>> {
-    let query = payload.clone().into_aggregate_query();
+    let query = payload.clone().into_aggregate_query()?;
 
     let count = ParkingHistory::count(query.clone(), &pool).await?;
-    let parking_history = ParkingHistory::aggregate(query.clone(), &pool).await?;
+    let mut parking_history = ParkingHistory::aggregate(
+        AggregateQuery {
+            take: query.take.map(|take| take + 1),
+            ..query.clone()
+        },
+        &pool,
+    )
+    .await?;
+    let next_cursor = next_cursor(&mut parking_history, query.take, |history| {
+        history.created_at.map(|created_at| Cursor {
+            created_at: created_at.naive_utc(),
+            id: history.id,
+        })
+    });
 
     Ok(AppSuccess(Aggregate {
         meta: MetaAggregate {
             total_data: count.data.unwrap_or(0),
+            next_cursor,
             query: payload,
         },
         parking_history,
     }))
 }
 
+#[derive(Deserialize, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+#[derive(Deserialize)]
+struct ExportOptions {
+    format: Option<ExportFormat>,
+    timezone: Option<String>,
+}
+
+const EXPORT_HEADER: [&str; 18] = [
+    "Ticket ID",
+    "Created At",
+    "Check In",
+    "Check Out",
+    "Duration (Minutes)",
+    "Parking Lot",
+    "Address",
+    "Driver",
+    "Driver Phone Number",
+    "Keeper",
+    "Plate Number",
+    "Vehicle Type",
+    "Payment",
+    "Ticket Status",
+    "Parking Amount",
+    "Penalty Amount",
+    "Penalty Reason",
+    "Settled Amount",
+];
+
+enum ExportCell {
+    Text(String),
+    Number(f64),
+    Empty,
+}
+
+impl ExportCell {
+    fn text(value: Option<String>) -> ExportCell {
+        value.map(ExportCell::Text).unwrap_or(ExportCell::Empty)
+    }
+
+    fn label<T: Serialize>(value: &T) -> ExportCell {
+        match serde_json::to_value(value) {
+            Ok(serde_json::Value::String(label)) => ExportCell::Text(label),
+            _ => ExportCell::Empty,
+        }
+    }
+
+    fn to_csv_field(&self) -> String {
+        match self {
+            ExportCell::Text(text) => text.clone(),
+            ExportCell::Number(number) => number.to_string(),
+            ExportCell::Empty => String::new(),
+        }
+    }
+}
+
+fn export_record(row: ExportRow) -> Vec<ExportCell> {
+    vec![
+        ExportCell::Text(row.id.to_string()),
+        ExportCell::text(row.created_at),
+        ExportCell::text(row.check_in_date),
+        ExportCell::text(row.check_out_date),
+        row.duration_minutes
+            .map(|minutes| ExportCell::Number(minutes as f64))
+            .unwrap_or(ExportCell::Empty),
+        ExportCell::Text(row.area_name),
+        ExportCell::Text(row.address),
+        ExportCell::Text(row.easypark_name),
+        ExportCell::Text(row.easypark_phone_number),
+        ExportCell::Text(row.keeper_name),
+        ExportCell::text(row.plate_number),
+        ExportCell::label(&row.vehicle_type),
+        ExportCell::label(&row.payment),
+        ExportCell::label(&row.ticket_status),
+        ExportCell::Number(row.amount),
+        ExportCell::Number(row.penalty_amount),
+        row.penalty_reason
+            .map(|reason| ExportCell::label(&reason))
+            .unwrap_or(ExportCell::Empty),
+        row.settled_amount
+            .map(ExportCell::Number)
+            .unwrap_or(ExportCell::Empty),
+    ]
+}
+
+fn csv_line<I: IntoIterator<Item = String>>(fields: I) -> Result<Bytes> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer
+        .write_record(fields)
+        .map_err(|err| Error::InternalServerError(err.to_string()))?;
+    let line = writer
+        .into_inner()
+        .map_err(|err| Error::InternalServerError(err.to_string()))?;
+    Ok(Bytes::from(line))
+}
+
+fn build_xlsx(records: Vec<Vec<ExportCell>>) -> std::result::Result<Vec<u8>, XlsxError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Parking History")?;
+
+    for (col, header) in EXPORT_HEADER.iter().enumerate() {
+        worksheet.write_string(0, col as u16, *header)?;
+    }
+    for (row, record) in records.iter().enumerate() {
+        let row = row as u32 + 1;
+        for (col, cell) in record.iter().enumerate() {
+            match cell {
+                ExportCell::Text(text) => worksheet.write_string(row, col as u16, text)?,
+                ExportCell::Number(number) => worksheet.write_number(row, col as u16, *number)?,
+                ExportCell::Empty => worksheet,
+            };
+        }
+    }
+
+    workbook.save_to_buffer()
+}
+
+async fn export(
+    State(pool): State<PgPool>,
+    Query(payload): Query<AggregatePayload>,
+    Query(options): Query<ExportOptions>,
+) -> Result<Response> {
+    let timezone = options
+        .timezone
+        .unwrap_or_else(|| String::from(DEFAULT_TIMEZONE));
+    validate_timezone(&timezone, &pool).await?;
+
+    let query = payload.into_aggregate_query()?;
+    let rows = ParkingHistory::export(query, timezone, pool);
+    let file_name = format!("parking-history-{}", Utc::now().format("%Y%m%d%H%M%S"));
+
+    match options.format.unwrap_or(ExportFormat::Csv) {
+        ExportFormat::Csv => {
+            let header = stream::once(async {
+                csv_line(EXPORT_HEADER.iter().map(|header| header.to_string()))
+            });
+            let lines = rows.and_then(|row| async move {
+                csv_line(export_record(row).iter().map(ExportCell::to_csv_field))
+            });
+            let body = ResponseBody::from_stream(
+                header
+                    .chain(lines)
+                    .map_err(|err| std::io::Error::other(format!("{:?}", err))),
+            );
+
+            Ok((
+                [
+                    (header::CONTENT_TYPE, String::from("text/csv; charset=utf-8")),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{}.csv\"", file_name),
+                    ),
+                ],
+                body,
+            )
+                .into_response())
+        }
+        ExportFormat::Xlsx => {
+            let records: Vec<Vec<ExportCell>> = rows.map_ok(export_record).try_collect().await?;
+            let buffer = build_xlsx(records)
+                .map_err(|err| Error::InternalServerError(err.to_string()))?;
+
+            Ok((
+                [
+                    (
+                        header::CONTENT_TYPE,
+                        String::from(
+                            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                        ),
+                    ),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{}.xlsx\"", file_name),
+                    ),
+                ],
+                buffer,
+            )
+                .into_response())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReceiptOptions {
+    timezone: Option<String>,
+}
+
+async fn receipt(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<ReceiptOptions>,
+) -> Result<Response> {
+    let timezone = options
+        .timezone
+        .unwrap_or_else(|| String::from(DEFAULT_TIMEZONE));
+    validate_timezone(&timezone, &pool).await?;
+
+    let parking_history = ParkingHistory::find_one(id, &pool).await?;
+    if parking_history.ticket_status != TicketStatus::NotActive {
+        return Err(Error::BadRequest(
+            "Receipt is only available after check out".to_string(),
+        ));
+    }
+
+    let code = Receipt::assign_code(parking_history.id, &pool).await?;
+    let receipt = Receipt::find_by_code(&code, &timezone, &pool).await?;
+    let buffer = receipt.render_pdf(&timezone)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, String::from("application/pdf")),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"receipt-{}.pdf\"", code),
+            ),
+        ],
+        buffer,
+    )
+        .into_response())
+}
+
+/// Lets whoever reimburses the driver check the code printed on a receipt
+/// against what was actually recorded.
+async fn verify_receipt(
+    State(pool): State<PgPool>,
+    Path(code): Path<String>,
+    Query(options): Query<ReceiptOptions>,
+) -> Result<AppSuccess<Receipt>> {
+    let timezone = options
+        .timezone
+        .unwrap_or_else(|| String::from(DEFAULT_TIMEZONE));
+    validate_timezone(&timezone, &pool).await?;
+
+    let receipt = Receipt::find_by_code(&code.trim().to_uppercase(), &timezone, &pool).await?;
+    Ok(AppSuccess(receipt))
+}
+
 async fn get_active_ticket(
     State(pool): State<PgPool>,
     Path(id): Path<Uuid>,
@@ -375,6 +869,163 @@ async fn get_active_ticket(
     Ok(AppSuccess(active_ticket))
 }
 
+#[derive(Deserialize)]
+struct SearchActiveTicketPayload {
+    keeper_id: Uuid,
+    plate_number: String,
+}
+
+async fn search_active_ticket(
+    State(pool): State<PgPool>,
+    Query(payload): Query<SearchActiveTicketPayload>,
+) -> Result<AppSuccess<Vec<RelatedParkingHistory>>> {
+    let keeper = User::find_one_by_id(payload.keeper_id, &pool).await?;
+    if keeper.role != Role::ParkKeeper {
+        return Err(Error::BadRequest(
+            "Provided keeper id is not having ParkKeeper role".to_string(),
+        ));
+    }
+
+    let parking_lot_id = keeper.parking_lot_id.ok_or_else(|| {
+        Error::BadRequest("Provided keeper is not assigned to any parking lot".to_string())
+    })?;
+
+    // Keepers usually type only part of the plate, so match on the compact form
+    // (`B1234ABC`) instead of requiring a fully valid plate number.
+    let plate_number = payload
+        .plate_number
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect::<String>();
+
+    if plate_number.is_empty() {
+        return Err(Error::BadRequest("Plate number is required".to_string()));
+    }
+
+    let active_ticket =
+        ParkingHistory::search_active_ticket_by_plate(parking_lot_id, plate_number, &pool).await?;
+    Ok(AppSuccess(active_ticket))
+}
+
+#[derive(Deserialize)]
+struct PenaltyPayload {
+    keeper_id: Uuid,
+    reason: PenaltyReason,
+    plate_number: Option<String>,
+    easypark_id: Option<Uuid>,
+}
+
+async fn apply_penalty(
+    State(pool): State<PgPool>,
+    Body(payload): Body<PenaltyPayload>,
+) -> Result<AppSuccess<ParkingHistory>> {
+    let keeper = User::find_one_by_id(payload.keeper_id, &pool).await?;
+    if keeper.role != Role::ParkKeeper {
+        return Err(Error::BadRequest(
+            "Provided keeper id is not having ParkKeeper role".to_string(),
+        ));
+    }
+
+    let parking_lot_id = keeper.parking_lot_id.ok_or_else(|| {
+        Error::BadRequest("Provided keeper is not assigned to any parking lot".to_string())
+    })?;
+
+    if payload.plate_number.is_none() && payload.easypark_id.is_none() {
+        return Err(Error::BadRequest(
+            "Plate number or easypark id is required".to_string(),
+        ));
+    }
+
+    let plate_number = match &payload.plate_number {
+        Some(plate_number) => Some(
+            normalize_plate_number(plate_number)
+                .ok_or_else(|| Error::BadRequest("Plate number is not valid".to_string()))?,
+        ),
+        None => None,
+    };
+
+    let mut active_ticket = ParkingHistory::find_active_ticket_in_lot(
+        parking_lot_id,
+        plate_number,
+        payload.easypark_id,
+        &pool,
+    )
+    .await?;
+
+    if active_ticket.is_empty() {
+        return Err(Error::NotFoundRejection("Ticket is not issue".to_string()));
+    }
+
+    if active_ticket.len() > 1 {
+        return Err(Error::BadRequest(
+            "More than one ticket matches, use the easypark id instead".to_string(),
+        ));
+    }
+
+    let ticket = active_ticket.remove(0);
+    if ticket.ticket_status != TicketStatus::Active {
+        return Err(Error::BadRequest("Ticket is not active".to_string()));
+    }
+
+    let parking_lot = ParkingLot::find_one(parking_lot_id, &pool).await?;
+    let now = Utc::now().naive_utc();
+
+    let penalty_amount = match payload.reason {
+        PenaltyReason::LostTicket => parking_lot.lost_ticket_fee,
+        PenaltyReason::Overstay => {
+            let max_stay_hours = parking_lot.max_stay_hours.ok_or_else(|| {
+                Error::BadRequest("Parking lot is not having maximum stay".to_string())
+            })?;
+            let check_in_date = ticket
+                .check_in_date
+                .ok_or_else(|| Error::BadRequest("Ticket is not checked in".to_string()))?;
+
+            let overstay_minutes =
+                (now - check_in_date).num_minutes() - i64::from(max_stay_hours) * 60;
+            if overstay_minutes <= 0 {
+                return Err(Error::BadRequest(
+                    "Ticket has not exceeded the maximum stay".to_string(),
+                ));
+            }
+
+            (overstay_minutes as f64 / 60.0).ceil() * parking_lot.overstay_fee
+        }
+        PenaltyReason::Default => {
+            return Err(Error::BadRequest("Penalty reason is required".to_string()))
+        }
+    };
+
+    let parking_history =
+        ParkingHistory::apply_penalty(ticket.id, penalty_amount, payload.reason, now, &pool)
+            .await?;
+    Ok(AppSuccess(parking_history))
+}
+
+#[derive(Deserialize)]
+struct ReviewTicketPayload {
+    keeper_id: Uuid,
+}
+
+async fn get_review_ticket(
+    State(pool): State<PgPool>,
+    Query(payload): Query<ReviewTicketPayload>,
+) -> Result<AppSuccess<Vec<RelatedParkingHistory>>> {
+    let keeper = User::find_one_by_id(payload.keeper_id, &pool).await?;
+    if keeper.role != Role::ParkKeeper {
+        return Err(Error::BadRequest(
+            "Provided keeper id is not having ParkKeeper role".to_string(),
+        ));
+    }
+
+    let parking_lot_id = keeper.parking_lot_id.ok_or_else(|| {
+        Error::BadRequest("Provided keeper is not assigned to any parking lot".to_string())
+    })?;
+
+    let review_ticket = ParkingHistory::find_flagged_for_review(parking_lot_id, &pool).await?;
+    Ok(AppSuccess(review_ticket))
+}
+
 #[derive(Deserialize, Clone, Serialize)]
 pub struct MonthlyHistory {
     pub owner_id: Uuid,
@@ -427,3 +1078,292 @@ async fn get_filtered_calc(
         total_history: filtered_calc.total_history.unwrap_or(0)
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body as AxumBody},
+        http::{Request, StatusCode},
+    };
+    use futures::future::join_all;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use super::build;
+    use crate::app::fixture::{
+        bearer, seed, seed_pass, EASYPARK_ID, EASYPARK_PHONE_NUMBER, KEEPER_ID,
+        OWNER_PHONE_NUMBER, PARKING_LOT_ID, VEHICLE_ID,
+    };
+    use crate::app::payment;
+
+    fn create_ticket(authorization: Option<&str>) -> Request<AxumBody> {
+        let body = serde_json::json!({
+            "vehicle_id": VEHICLE_ID,
+            "payment": "Cash",
+            "parking_lot_id": PARKING_LOT_ID,
+            "easypark_id": EASYPARK_ID,
+            "keeper_id": KEEPER_ID,
+        })
+        .to_string();
+
+        let mut request = Request::post("/parking-history").header("content-type", "application/json");
+        if let Some(authorization) = authorization {
+            request = request.header("authorization", authorization);
+        }
+        request.body(AxumBody::from(body)).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn parallel_create_issues_single_active_ticket(pool: PgPool) {
+        seed(&pool).await;
+
+        let app = build(pool.clone());
+        let body = serde_json::json!({
+            "vehicle_id": VEHICLE_ID,
+            "payment": "Cash",
+            "parking_lot_id": PARKING_LOT_ID,
+            "easypark_id": EASYPARK_ID,
+            "keeper_id": KEEPER_ID,
+        })
+        .to_string();
+
+        let requests = (0..8).map(|_| {
+            let request = Request::post("/parking-history")
+                .header("content-type", "application/json")
+                .body(AxumBody::from(body.clone()))
+                .unwrap();
+            tokio::spawn(app.clone().oneshot(request))
+        });
+
+        let statuses: Vec<StatusCode> = join_all(requests)
+            .await
+            .into_iter()
+            .map(|response| response.unwrap().unwrap().status())
+            .collect();
+
+        let created = statuses.iter().filter(|s| **s == StatusCode::OK).count();
+        let conflict = statuses
+            .iter()
+            .filter(|s| **s == StatusCode::CONFLICT)
+            .count();
+        assert_eq!(created, 1, "statuses: {:?}", statuses);
+        assert_eq!(conflict, statuses.len() - 1, "statuses: {:?}", statuses);
+
+        let active: i64 = sqlx::query_scalar(
+            "select count(*) from parking_history where easypark_id = $1 and ticket_status in ('active', 'default')",
+        )
+        .bind(EASYPARK_ID)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(active, 1);
+    }
+
+    #[sqlx::test]
+    async fn closed_lot_only_takes_vehicles_from_its_signed_in_owner(pool: PgPool) {
+        seed(&pool).await;
+        sqlx::query(
+            r#"
+                insert into parking_lot_closure (id, parking_lot_id, start_at, end_at, reason)
+                values (gen_random_uuid(), $1, now() - interval '1 hour', now() + interval '1 day', 'Maintenance')
+            "#,
+        )
+        .bind(PARKING_LOT_ID)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let app = build(pool.clone());
+        let anonymous = app.clone().oneshot(create_ticket(None)).await.unwrap();
+        assert_eq!(anonymous.status(), StatusCode::BAD_REQUEST);
+
+        let easypark = bearer(EASYPARK_PHONE_NUMBER);
+        let driver = app.clone().oneshot(create_ticket(Some(&easypark))).await.unwrap();
+        assert_eq!(driver.status(), StatusCode::UNAUTHORIZED);
+
+        let owner = bearer(OWNER_PHONE_NUMBER);
+        let overridden = app.oneshot(create_ticket(Some(&owner))).await.unwrap();
+        assert_eq!(overridden.status(), StatusCode::OK);
+    }
+
+    /// Turns the seeded car into a truck, priced at 9000 by the lot's tariff.
+    async fn make_truck(pool: &PgPool) {
+        sqlx::query(
+            r#"
+                update vehicle set vehicle_category_id = (select id from vehicle_category where code = 'truck')
+                where id = $1
+            "#,
+        )
+        .bind(VEHICLE_ID)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        sqlx::query(
+            r#"
+                insert into parking_lot_rate (id, tariff_id, parking_lot_id, vehicle_category_id, cost)
+                select gen_random_uuid(), t.id, t.parking_lot_id, c.id, 9000
+                from parking_lot_tariff t, vehicle_category c
+                where t.parking_lot_id = $1 and c.code = 'truck'
+            "#,
+        )
+        .bind(PARKING_LOT_ID)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn ticket(response: axum::response::Response) -> serde_json::Value {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        body["data"]["parking_history"].take()
+    }
+
+    #[sqlx::test]
+    async fn ticket_is_priced_by_the_category_of_its_vehicle(pool: PgPool) {
+        seed(&pool).await;
+        make_truck(&pool).await;
+
+        let app = build(pool.clone());
+        let rejected = app.clone().oneshot(create_ticket(None)).await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+
+        sqlx::query(
+            r#"
+                insert into parking_lot_vehicle_category (parking_lot_id, vehicle_category_id)
+                select $1, id from vehicle_category where code = 'truck'
+            "#,
+        )
+        .bind(PARKING_LOT_ID)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let created = app.clone().oneshot(create_ticket(None)).await.unwrap();
+        assert_eq!(created.status(), StatusCode::OK);
+        let created = ticket(created).await;
+        assert_eq!(created["amount"], 9000.0);
+
+        // A vehicle_type in the payload does not turn the truck into a motorcycle.
+        let id = created["id"].as_str().unwrap();
+        let check_in = serde_json::json!({ "ticket_status": "Active", "vehicle_type": "Motor" });
+        let checked_in = app.oneshot(patch_ticket(id, check_in)).await.unwrap();
+        assert_eq!(checked_in.status(), StatusCode::OK);
+        assert_eq!(ticket(checked_in).await["amount"], 9000.0);
+
+        let vehicle_type: String =
+            sqlx::query_scalar("select vehicle_type::text from parking_history where id = $1::uuid")
+                .bind(id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(vehicle_type, "car");
+    }
+
+    fn patch_ticket(id: &str, body: serde_json::Value) -> Request<AxumBody> {
+        Request::patch(format!("/parking-history/{}", id))
+            .header("content-type", "application/json")
+            .body(AxumBody::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn disabled_spot_is_held_from_check_in_to_check_out_with_a_permit(pool: PgPool) {
+        seed(&pool).await;
+        let spot_id: uuid::Uuid = sqlx::query_scalar(
+            r#"
+                with zone as (
+                    insert into parking_zone (id, parking_lot_id, name) values (gen_random_uuid(), $1, 'Ground')
+                    returning id
+                )
+                insert into parking_spot (id, zone_id, parking_lot_id, number, kind)
+                select gen_random_uuid(), zone.id, $1, 'D1', 'disabled' from zone
+                returning id
+            "#,
+        )
+        .bind(PARKING_LOT_ID)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let app = build(pool.clone());
+        let created = ticket(app.clone().oneshot(create_ticket(None)).await.unwrap()).await;
+        let id = created["id"].as_str().unwrap();
+        let check_in = serde_json::json!({ "ticket_status": "Active", "spot_id": spot_id });
+
+        let refused = app.clone().oneshot(patch_ticket(id, check_in.clone())).await.unwrap();
+        assert_eq!(refused.status(), StatusCode::BAD_REQUEST);
+
+        sqlx::query(
+            r#"
+                insert into disabled_permit (id, user_id, permit_number, valid_until)
+                values (gen_random_uuid(), $1, 'KD-001', now() + interval '1 year')
+            "#,
+        )
+        .bind(EASYPARK_ID)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let checked_in = app.clone().oneshot(patch_ticket(id, check_in)).await.unwrap();
+        assert_eq!(checked_in.status(), StatusCode::OK);
+
+        let held = "select count(*) from spot_allocation where spot_id = $1 and released_at is null";
+        let open: i64 = sqlx::query_scalar(held).bind(spot_id).fetch_one(&pool).await.unwrap();
+        assert_eq!(open, 1);
+
+        let check_out = serde_json::json!({ "ticket_status": "NotActive" });
+        let checked_out = app.oneshot(patch_ticket(id, check_out)).await.unwrap();
+        assert_eq!(checked_out.status(), StatusCode::OK);
+
+        let open: i64 = sqlx::query_scalar(held).bind(spot_id).fetch_one(&pool).await.unwrap();
+        assert_eq!(open, 0);
+    }
+
+    #[sqlx::test]
+    async fn pass_holder_pays_the_discounted_rate(pool: PgPool) {
+        seed(&pool).await;
+        seed_pass(&pool, 50.0).await;
+
+        let app = build(pool.clone());
+        let created = app.clone().oneshot(create_ticket(None)).await.unwrap();
+        assert_eq!(created.status(), StatusCode::OK);
+        let created = ticket(created).await;
+        assert_eq!(created["amount"], 2500.0);
+
+        // The discount is priced again at check in, the pass still covers it.
+        let id = created["id"].as_str().unwrap();
+        let check_in = serde_json::json!({ "ticket_status": "Active" });
+        let checked_in = app.oneshot(patch_ticket(id, check_in)).await.unwrap();
+        assert_eq!(checked_in.status(), StatusCode::OK);
+        assert_eq!(ticket(checked_in).await["amount"], 2500.0);
+    }
+
+    #[sqlx::test]
+    async fn ticket_covered_by_a_pass_is_closed_without_an_order(pool: PgPool) {
+        seed(&pool).await;
+        seed_pass(&pool, 100.0).await;
+
+        let app = build(pool.clone());
+        let created = ticket(app.clone().oneshot(create_ticket(None)).await.unwrap()).await;
+        assert_eq!(created["amount"], 0.0);
+        let id = created["id"].as_str().unwrap();
+        let transaction_id = created["transaction_id"].clone();
+        let check_in = serde_json::json!({ "ticket_status": "Active" });
+        let checked_in = app.oneshot(patch_ticket(id, check_in)).await.unwrap();
+        assert_eq!(checked_in.status(), StatusCode::OK);
+
+        let body = serde_json::json!({ "parking_history_id": id }).to_string();
+        let request = Request::post("/payment/generate")
+            .header("content-type", "application/json")
+            .body(AxumBody::from(body))
+            .unwrap();
+        let paid = payment::router::build(pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(paid.status(), StatusCode::OK);
+        let paid = to_bytes(paid.into_body(), usize::MAX).await.unwrap();
+        let paid: serde_json::Value = serde_json::from_slice(&paid).unwrap();
+        assert!(paid["data"]["transaction"].is_null());
+        assert_eq!(paid["data"]["parking_history"]["ticket_status"], "NotActive");
+        assert_eq!(paid["data"]["parking_history"]["transaction_id"], transaction_id);
+    }
+}
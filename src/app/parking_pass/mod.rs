@@ -0,0 +1,502 @@
+pub mod router;
+
+use chrono::{Duration, Months, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::error::aggregate::{Error, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, sqlx::Type, Deserialize, Serialize)]
+#[sqlx(type_name = "pass_period", rename_all = "snake_case")]
+pub enum PassPeriod {
+    Weekly,
+    Monthly,
+}
+
+impl PassPeriod {
+    pub fn end(&self, starts_at: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            PassPeriod::Weekly => starts_at + Duration::days(7),
+            PassPeriod::Monthly => starts_at
+                .checked_add_months(Months::new(1))
+                .unwrap_or(starts_at + Duration::days(30)),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PassPeriod::Weekly => "Weekly Parking Pass",
+            PassPeriod::Monthly => "Monthly Parking Pass",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, sqlx::Type, Deserialize, Serialize)]
+#[sqlx(type_name = "pass_status", rename_all = "snake_case")]
+pub enum PassStatus {
+    Pending,
+    Active,
+    Cancelled,
+}
+
+/// A pass an owner sells for one lot.
+#[derive(Debug, Serialize)]
+pub struct ParkingPassPlan {
+    pub id: Uuid,
+    pub parking_lot_id: Uuid,
+    pub period: PassPeriod,
+    pub price: f64,
+    pub discount_percent: f64,
+    pub is_active: bool,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+/// A pass bought for one vehicle, valid from `starts_at` until `ends_at` once
+/// its payment settled.
+#[derive(Debug, Serialize)]
+pub struct ParkingPass {
+    pub id: Uuid,
+    pub plan_id: Uuid,
+    pub parking_lot_id: Uuid,
+    pub vehicle_id: Uuid,
+    pub easypark_id: Uuid,
+    pub period: PassPeriod,
+    pub price: f64,
+    pub discount_percent: f64,
+    pub status: PassStatus,
+    pub transaction_id: Uuid,
+    pub starts_at: Option<NaiveDateTime>,
+    pub ends_at: Option<NaiveDateTime>,
+    pub renewed_from_id: Option<Uuid>,
+    pub reminded_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PassHolder {
+    pub id: Uuid,
+    pub easypark_id: Uuid,
+    pub easypark_name: String,
+    pub phone_number: String,
+    pub vehicle_id: Uuid,
+    pub plate_number: String,
+    pub period: PassPeriod,
+    pub starts_at: Option<NaiveDateTime>,
+    pub ends_at: Option<NaiveDateTime>,
+}
+
+/// A pass running out soon whose holder has not bought the next one yet.
+#[derive(Debug)]
+pub struct ExpiringPass {
+    pub id: Uuid,
+    pub area_name: String,
+    pub plate_number: String,
+    pub phone_number: String,
+    pub ends_at: NaiveDateTime,
+}
+
+impl ParkingPassPlan {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<ParkingPassPlan> {
+        let plan = sqlx::query_as!(
+            ParkingPassPlan,
+            r#"
+                insert into "parking_pass_plan" (id, parking_lot_id, period, price, discount_percent, created_at)
+                values ($1, $2, $3, $4, $5, $6)
+                returning id, parking_lot_id, period as "period!: PassPeriod", price, discount_percent, is_active, created_at, updated_at
+            "#,
+            self.id,
+            self.parking_lot_id,
+            self.period as PassPeriod,
+            self.price,
+            self.discount_percent,
+            self.created_at
+        )
+            .fetch_one(executor)
+            .await
+            .map_err(|err| match &err {
+                sqlx::Error::Database(dbx) if dbx.is_unique_violation() => {
+                    Error::Conflict("Parking lot already sells a pass for this period".to_string())
+                }
+                _ => Error::from(err),
+            })?;
+
+        Ok(plan)
+    }
+
+    pub async fn find_one(id: Uuid, executor: impl PgExecutor<'_>) -> Result<ParkingPassPlan> {
+        let plan = sqlx::query_as!(
+            ParkingPassPlan,
+            r#"
+                select id, parking_lot_id, period as "period!: PassPeriod", price, discount_percent, is_active, created_at, updated_at
+                from "parking_pass_plan"
+                where id = $1
+            "#,
+            id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(plan)
+    }
+
+    pub async fn find_by_parking_lot(parking_lot_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<ParkingPassPlan>> {
+        let plan = sqlx::query_as!(
+            ParkingPassPlan,
+            r#"
+                select id, parking_lot_id, period as "period!: PassPeriod", price, discount_percent, is_active, created_at, updated_at
+                from "parking_pass_plan"
+                where parking_lot_id = $1 and is_active
+                order by period
+            "#,
+            parking_lot_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(plan)
+    }
+
+    /// Stops selling the plan, passes already bought stay valid.
+    pub async fn deactivate(id: Uuid, updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<ParkingPassPlan> {
+        let plan = sqlx::query_as!(
+            ParkingPassPlan,
+            r#"
+                update "parking_pass_plan"
+                set is_active = false,
+                    updated_at = $2
+                where id = $1 and is_active
+                returning id, parking_lot_id, period as "period!: PassPeriod", price, discount_percent, is_active, created_at, updated_at
+            "#,
+            id,
+            updated_at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        plan.ok_or(Error::NotFoundRejection("Pass plan is not found".to_string()))
+    }
+}
+
+impl ParkingPass {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<ParkingPass> {
+        let pass = sqlx::query_as!(
+            ParkingPass,
+            r#"
+                insert into "parking_pass" (id, plan_id, parking_lot_id, vehicle_id, easypark_id, period, price, discount_percent, transaction_id, renewed_from_id, created_at)
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                returning id, plan_id, parking_lot_id, vehicle_id, easypark_id,
+                    period as "period!: PassPeriod",
+                    price,
+                    discount_percent,
+                    status as "status!: PassStatus",
+                    transaction_id, starts_at, ends_at, renewed_from_id, reminded_at, created_at, updated_at
+            "#,
+            self.id,
+            self.plan_id,
+            self.parking_lot_id,
+            self.vehicle_id,
+            self.easypark_id,
+            self.period as PassPeriod,
+            self.price,
+            self.discount_percent,
+            self.transaction_id,
+            self.renewed_from_id,
+            self.created_at
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(pass)
+    }
+
+    pub async fn find_one(id: Uuid, executor: impl PgExecutor<'_>) -> Result<ParkingPass> {
+        let pass = sqlx::query_as!(
+            ParkingPass,
+            r#"
+                select id, plan_id, parking_lot_id, vehicle_id, easypark_id,
+                    period as "period!: PassPeriod",
+                    price,
+                    discount_percent,
+                    status as "status!: PassStatus",
+                    transaction_id, starts_at, ends_at, renewed_from_id, reminded_at, created_at, updated_at
+                from "parking_pass"
+                where id = $1
+            "#,
+            id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(pass)
+    }
+
+    pub async fn find_by_transaction(transaction_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Option<ParkingPass>> {
+        let pass = sqlx::query_as!(
+            ParkingPass,
+            r#"
+                select id, plan_id, parking_lot_id, vehicle_id, easypark_id,
+                    period as "period!: PassPeriod",
+                    price,
+                    discount_percent,
+                    status as "status!: PassStatus",
+                    transaction_id, starts_at, ends_at, renewed_from_id, reminded_at, created_at, updated_at
+                from "parking_pass"
+                where transaction_id = $1
+            "#,
+            transaction_id
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        Ok(pass)
+    }
+
+    pub async fn find_by_user(easypark_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<ParkingPass>> {
+        let pass = sqlx::query_as!(
+            ParkingPass,
+            r#"
+                select id, plan_id, parking_lot_id, vehicle_id, easypark_id,
+                    period as "period!: PassPeriod",
+                    price,
+                    discount_percent,
+                    status as "status!: PassStatus",
+                    transaction_id, starts_at, ends_at, renewed_from_id, reminded_at, created_at, updated_at
+                from "parking_pass"
+                where easypark_id = $1
+                order by created_at desc
+            "#,
+            easypark_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(pass)
+    }
+
+    /// The pass covering the vehicle at the lot at `at`, if any.
+    pub async fn find_valid(parking_lot_id: Uuid, vehicle_id: Uuid, at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Option<ParkingPass>> {
+        let pass = sqlx::query_as!(
+            ParkingPass,
+            r#"
+                select id, plan_id, parking_lot_id, vehicle_id, easypark_id,
+                    period as "period!: PassPeriod",
+                    price,
+                    discount_percent,
+                    status as "status!: PassStatus",
+                    transaction_id, starts_at, ends_at, renewed_from_id, reminded_at, created_at, updated_at
+                from "parking_pass"
+                where parking_lot_id = $1 and vehicle_id = $2 and status = 'active' and
+                    starts_at <= $3 and ends_at > $3
+                order by discount_percent desc
+                limit 1
+            "#,
+            parking_lot_id,
+            vehicle_id,
+            at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        Ok(pass)
+    }
+
+    /// Holders of a pass valid now or starting later, the one ending first
+    /// on top.
+    pub async fn find_holders(parking_lot_id: Uuid, now: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Vec<PassHolder>> {
+        let holder = sqlx::query_as!(
+            PassHolder,
+            r#"
+                select p.id,
+                    p.easypark_id,
+                    u.name as easypark_name,
+                    u.phone_number,
+                    p.vehicle_id,
+                    v.plate_number,
+                    p.period as "period!: PassPeriod",
+                    p.starts_at,
+                    p.ends_at
+                from "parking_pass" p
+                join "user" u on u.id = p.easypark_id
+                join "vehicle" v on v.id = p.vehicle_id
+                where p.parking_lot_id = $1 and p.status = 'active' and p.ends_at > $2
+                order by p.ends_at, u.name
+            "#,
+            parking_lot_id,
+            now
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(holder)
+    }
+
+    /// Starts a paid pass. A renewal bought before the current pass ran out
+    /// starts where that one ends.
+    pub async fn activate(id: Uuid, now: NaiveDateTime, connection: &mut PgConnection) -> Result<ParkingPass> {
+        let pass = Self::find_one(id, &mut *connection).await?;
+
+        // Every pass of the vehicle at the lot is locked, so two renewals
+        // settling together cannot both start at the same end.
+        sqlx::query!(
+            r#"
+                select id
+                from "parking_pass"
+                where parking_lot_id = $1 and vehicle_id = $2
+                order by id
+                for update
+            "#,
+            pass.parking_lot_id,
+            pass.vehicle_id
+        )
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let pass = Self::find_one(id, &mut *connection).await?;
+        if pass.status != PassStatus::Pending {
+            return Ok(pass);
+        }
+
+        let current_end = sqlx::query_scalar!(
+            r#"
+                select max(ends_at)
+                from "parking_pass"
+                where parking_lot_id = $1 and vehicle_id = $2 and status = 'active'
+            "#,
+            pass.parking_lot_id,
+            pass.vehicle_id
+        )
+            .fetch_one(&mut *connection)
+            .await?;
+        let starts_at = current_end.filter(|end| *end > now).unwrap_or(now);
+
+        let pass = sqlx::query_as!(
+            ParkingPass,
+            r#"
+                update "parking_pass"
+                set status = 'active',
+                    starts_at = $2,
+                    ends_at = $3,
+                    updated_at = $4
+                where id = $1
+                returning id, plan_id, parking_lot_id, vehicle_id, easypark_id,
+                    period as "period!: PassPeriod",
+                    price,
+                    discount_percent,
+                    status as "status!: PassStatus",
+                    transaction_id, starts_at, ends_at, renewed_from_id, reminded_at, created_at, updated_at
+            "#,
+            id,
+            starts_at,
+            pass.period.end(starts_at),
+            now
+        )
+            .fetch_one(&mut *connection)
+            .await?;
+
+        Ok(pass)
+    }
+
+    /// Points a pending pass at a new payment order.
+    pub async fn update_transaction_id(id: Uuid, transaction_id: Uuid, updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<ParkingPass> {
+        let pass = sqlx::query_as!(
+            ParkingPass,
+            r#"
+                update "parking_pass"
+                set transaction_id = $2,
+                    updated_at = $3
+                where id = $1 and status = 'pending'
+                returning id, plan_id, parking_lot_id, vehicle_id, easypark_id,
+                    period as "period!: PassPeriod",
+                    price,
+                    discount_percent,
+                    status as "status!: PassStatus",
+                    transaction_id, starts_at, ends_at, renewed_from_id, reminded_at, created_at, updated_at
+            "#,
+            id,
+            transaction_id,
+            updated_at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        pass.ok_or(Error::BadRequest("Pass is already paid or cancelled".to_string()))
+    }
+
+    /// Drops a pass whose payment was denied, expired or cancelled. A pass
+    /// that is no longer pending is left as it is.
+    pub async fn cancel(id: Uuid, updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Option<ParkingPass>> {
+        let pass = sqlx::query_as!(
+            ParkingPass,
+            r#"
+                update "parking_pass"
+                set status = 'cancelled',
+                    updated_at = $2
+                where id = $1 and status = 'pending'
+                returning id, plan_id, parking_lot_id, vehicle_id, easypark_id,
+                    period as "period!: PassPeriod",
+                    price,
+                    discount_percent,
+                    status as "status!: PassStatus",
+                    transaction_id, starts_at, ends_at, renewed_from_id, reminded_at, created_at, updated_at
+            "#,
+            id,
+            updated_at
+        )
+            .fetch_optional(executor)
+            .await?;
+
+        Ok(pass)
+    }
+
+    /// Passes ending before `until` whose holder was not reminded yet, passes
+    /// already followed by a paid renewal are skipped.
+    pub async fn find_expiring(now: NaiveDateTime, until: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Vec<ExpiringPass>> {
+        let pass = sqlx::query_as!(
+            ExpiringPass,
+            r#"
+                select p.id, pl.area_name, v.plate_number, u.phone_number, p.ends_at as "ends_at!"
+                from "parking_pass" p
+                join "parking_lot" pl on pl.id = p.parking_lot_id
+                join "vehicle" v on v.id = p.vehicle_id
+                join "user" u on u.id = p.easypark_id
+                where p.status = 'active' and
+                    p.reminded_at is null and
+                    p.ends_at > $1 and
+                    p.ends_at <= $2 and
+                    not exists (
+                        select 1 from "parking_pass" n
+                        where n.parking_lot_id = p.parking_lot_id and
+                            n.vehicle_id = p.vehicle_id and
+                            n.status = 'active' and
+                            n.ends_at > p.ends_at
+                    )
+            "#,
+            now,
+            until
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(pass)
+    }
+
+    /// Records that the holder got the reminder, only called once it was sent.
+    pub async fn mark_reminded(id: Uuid, reminded_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<()> {
+        sqlx::query!(
+            r#"
+                update "parking_pass"
+                set reminded_at = $2
+                where id = $1 and reminded_at is null
+            "#,
+            id,
+            reminded_at
+        )
+            .execute(executor)
+            .await?;
+
+        Ok(())
+    }
+}
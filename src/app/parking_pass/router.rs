@@ -0,0 +1,397 @@
+use axum::{
+    extract::{Path, State},
+    middleware,
+    routing::{delete, get, post},
+    Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::{PgPool, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        parking_area::{rate::AcceptedCategory, router::owned_parking_lot, ParkingLot},
+        payment::TransactionHistory,
+        user::{Role, User},
+        vehicle::{category::VehicleCategory, Vehicle},
+    },
+    error::aggregate::{Error, Result},
+    extractor::{app_body::Body, app_json::AppSuccess, current_user::CurrentUser},
+    middleware::base::print_request_body,
+    unit_of_work::UnitOfWork,
+};
+
+use super::{ParkingPass, ParkingPassPlan, PassHolder, PassPeriod, PassStatus};
+
+pub fn build(pool: Pool<Postgres>) -> Router {
+    let router = Router::new()
+        .route("/", post(create))
+        .route("/:id/renew", post(renew))
+        .route("/user/:id", get(get_by_user))
+        .route("/parking-lot/:id/holders", get(holders))
+        .route("/plan", post(create_plan))
+        .route("/plan/:id", delete(delete_plan))
+        .route("/plan/parking-lot/:id", get(plans))
+        .layer(middleware::from_fn(print_request_body))
+        .with_state(pool);
+
+    Router::new().nest("/parking-pass", router)
+}
+
+#[derive(Deserialize)]
+struct PlanPayload {
+    owner_id: Uuid,
+    parking_lot_id: Uuid,
+    period: PassPeriod,
+    price: f64,
+    discount_percent: Option<f64>,
+}
+
+/// Puts a pass on sale for the lot, tickets of holders are free unless a
+/// smaller `discount_percent` is given.
+async fn create_plan(
+    State(pool): State<PgPool>,
+    Body(payload): Body<PlanPayload>,
+) -> Result<AppSuccess<ParkingPassPlan>> {
+    let parking_lot = owned_parking_lot(payload.parking_lot_id, payload.owner_id, &pool).await?;
+
+    if payload.price < 0.0 {
+        return Err(Error::BadRequest("price cannot be negative".to_string()));
+    }
+    let discount_percent = payload.discount_percent.unwrap_or(100.0);
+    if discount_percent <= 0.0 || discount_percent > 100.0 {
+        return Err(Error::BadRequest(
+            "discount_percent must be above 0 and at most 100".to_string(),
+        ));
+    }
+
+    let plan = ParkingPassPlan {
+        id: Uuid::new_v4(),
+        parking_lot_id: parking_lot.id,
+        period: payload.period,
+        price: payload.price,
+        discount_percent,
+        is_active: true,
+        created_at: Some(Utc::now().naive_utc()),
+        updated_at: None,
+    };
+    let plan = plan.save(&pool).await?;
+
+    Ok(AppSuccess(plan))
+}
+
+async fn plans(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<Vec<ParkingPassPlan>>> {
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    let plan = ParkingPassPlan::find_by_parking_lot(parking_lot.id, &pool).await?;
+
+    Ok(AppSuccess(plan))
+}
+
+#[derive(Deserialize)]
+struct DeletePlanPayload {
+    owner_id: Uuid,
+}
+
+async fn delete_plan(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<DeletePlanPayload>,
+) -> Result<AppSuccess<ParkingPassPlan>> {
+    let plan = ParkingPassPlan::find_one(id, &pool).await?;
+    owned_parking_lot(plan.parking_lot_id, payload.owner_id, &pool).await?;
+    let plan = ParkingPassPlan::deactivate(plan.id, Utc::now().naive_utc(), &pool).await?;
+
+    Ok(AppSuccess(plan))
+}
+
+/// Opens a pending pass with its own payment order, paid through
+/// `/payment/pass`.
+async fn open_pass(
+    plan: &ParkingPassPlan,
+    vehicle_id: Uuid,
+    easypark_id: Uuid,
+    renewed_from_id: Option<Uuid>,
+    pool: &PgPool,
+) -> Result<ParkingPass> {
+    if !plan.is_active {
+        return Err(Error::BadRequest(
+            "Pass is no longer sold for the parking lot".to_string(),
+        ));
+    }
+
+    let easypark = User::find_one_by_id(easypark_id, pool).await?;
+    if easypark.role != Role::Easypark {
+        return Err(Error::BadRequest(
+            "Provided easypark id is not having Easypark role".to_string(),
+        ));
+    }
+
+    let vehicle = Vehicle::find_one(vehicle_id, pool).await?;
+    if vehicle.user_id != easypark.id {
+        return Err(Error::BadRequest(
+            "Provided vehicle is not belong to provided easypark".to_string(),
+        ));
+    }
+
+    let parking_lot = ParkingLot::find_one(plan.parking_lot_id, pool).await?;
+    let category = VehicleCategory::find_by_vehicle(&vehicle, pool).await?;
+    if !AcceptedCategory::is_accepted(parking_lot.id, category.id, pool).await? {
+        return Err(Error::BadRequest(format!(
+            "Parking lot does not accept {}",
+            category.name
+        )));
+    }
+
+    let transaction_history = TransactionHistory {
+        id: Uuid::new_v4(),
+        transaction_time: None,
+        transaction_status: None,
+        transaction_id: None,
+        status_message: None,
+        status_code: None,
+        signature_key: None,
+        settlement_time: None,
+        payment_type: None,
+        order_id: None,
+        merchant_id: None,
+        gross_amount: None,
+        fraud_status: None,
+        currency: None,
+    };
+
+    let mut uow = UnitOfWork::begin(pool).await?;
+    let transaction_history = transaction_history.save(uow.connection()).await?;
+    let pass = ParkingPass {
+        id: Uuid::new_v4(),
+        plan_id: plan.id,
+        parking_lot_id: parking_lot.id,
+        vehicle_id: vehicle.id,
+        easypark_id: easypark.id,
+        period: plan.period,
+        price: plan.price,
+        discount_percent: plan.discount_percent,
+        status: PassStatus::Pending,
+        transaction_id: transaction_history.id,
+        starts_at: None,
+        ends_at: None,
+        renewed_from_id,
+        reminded_at: None,
+        created_at: Some(Utc::now().naive_utc()),
+        updated_at: None,
+    };
+    let pass = pass.save(uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(pass)
+}
+
+#[derive(Deserialize)]
+struct CreatePassPayload {
+    plan_id: Uuid,
+    vehicle_id: Uuid,
+    easypark_id: Uuid,
+}
+
+async fn create(
+    State(pool): State<PgPool>,
+    Body(payload): Body<CreatePassPayload>,
+) -> Result<AppSuccess<ParkingPass>> {
+    let plan = ParkingPassPlan::find_one(payload.plan_id, &pool).await?;
+    let pass = open_pass(&plan, payload.vehicle_id, payload.easypark_id, None, &pool).await?;
+
+    Ok(AppSuccess(pass))
+}
+
+#[derive(Deserialize)]
+struct RenewPassPayload {
+    easypark_id: Uuid,
+}
+
+/// Buys the next period of a pass at today's price of the lot, it starts when
+/// the current one ends.
+async fn renew(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<RenewPassPayload>,
+) -> Result<AppSuccess<ParkingPass>> {
+    let pass = ParkingPass::find_one(id, &pool).await?;
+    if pass.easypark_id != payload.easypark_id {
+        return Err(Error::BadRequest(
+            "Pass is not belong to provided easypark".to_string(),
+        ));
+    }
+    if pass.status != PassStatus::Active {
+        return Err(Error::BadRequest("Only a paid pass can be renewed".to_string()));
+    }
+
+    let plan = ParkingPassPlan::find_by_parking_lot(pass.parking_lot_id, &pool)
+        .await?
+        .into_iter()
+        .find(|plan| plan.period == pass.period)
+        .ok_or(Error::BadRequest(
+            "Pass is no longer sold for the parking lot".to_string(),
+        ))?;
+    let renewal = open_pass(&plan, pass.vehicle_id, pass.easypark_id, Some(pass.id), &pool).await?;
+
+    Ok(AppSuccess(renewal))
+}
+
+async fn get_by_user(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<Vec<ParkingPass>>> {
+    let pass = ParkingPass::find_by_user(id, &pool).await?;
+    Ok(AppSuccess(pass))
+}
+
+/// Who holds a pass of the lot now or from a later date, only shown to the
+/// owner of the lot.
+async fn holders(
+    State(pool): State<PgPool>,
+    current_user: CurrentUser,
+    Path(id): Path<Uuid>,
+) -> Result<AppSuccess<Vec<PassHolder>>> {
+    let caller = User::find_one(current_user.sub, &pool).await?;
+    let parking_lot = ParkingLot::find_one(id, &pool).await?;
+    if caller.role != Role::ParkOwner || parking_lot.owner_id != caller.id {
+        return Err(Error::Unauthorize(
+            "Only the owner of the parking lot can see its pass holders".to_string(),
+        ));
+    }
+
+    let holder = ParkingPass::find_holders(parking_lot.id, Utc::now().naive_utc(), &pool).await?;
+
+    Ok(AppSuccess(holder))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body as AxumBody},
+        http::{Request, StatusCode},
+        response::Response,
+    };
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use super::build;
+    use crate::app::fixture::{
+        bearer, seed, seed_pass, EASYPARK_ID, EASYPARK_PHONE_NUMBER, OWNER_PHONE_NUMBER,
+        PARKING_LOT_ID,
+    };
+    use crate::app::payment;
+
+    async fn data(response: Response) -> serde_json::Value {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        body["data"].take()
+    }
+
+    /// Opens a renewal of the pass and reports its order to the callback with
+    /// the given Midtrans status, returns the id of the renewal.
+    async fn renew_and_settle(pool: &PgPool, pass_id: Uuid, transaction_status: &str) -> Uuid {
+        let body = serde_json::json!({ "easypark_id": EASYPARK_ID }).to_string();
+        let request = Request::post(format!("/parking-pass/{}/renew", pass_id))
+            .header("content-type", "application/json")
+            .body(AxumBody::from(body))
+            .unwrap();
+        let renewed = build(pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(renewed.status(), StatusCode::OK);
+        let renewal = data(renewed).await;
+        assert_eq!(renewal["status"], "Pending");
+
+        let body = serde_json::json!({
+            "order_id": renewal["transaction_id"],
+            "transaction_status": transaction_status,
+        })
+        .to_string();
+        let request = Request::post("/payment/callback")
+            .header("content-type", "application/json")
+            .body(AxumBody::from(body))
+            .unwrap();
+        let called = payment::router::build(pool.clone()).oneshot(request).await.unwrap();
+        assert_eq!(called.status(), StatusCode::OK);
+
+        renewal["id"].as_str().unwrap().parse().unwrap()
+    }
+
+    #[sqlx::test]
+    async fn paid_renewal_starts_where_the_current_pass_ends(pool: PgPool) {
+        seed(&pool).await;
+        let pass_id = seed_pass(&pool, 100.0).await;
+
+        let renewal_id = renew_and_settle(&pool, pass_id, "settlement").await;
+
+        let (status, starts_at_end): (String, bool) = sqlx::query_as(
+            r#"
+                select r.status::text, r.starts_at = p.ends_at and r.ends_at = p.ends_at + interval '7 days'
+                from parking_pass r, parking_pass p
+                where r.id = $1 and p.id = $2
+            "#,
+        )
+        .bind(renewal_id)
+        .bind(pass_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(status, "active");
+        assert!(starts_at_end);
+
+        // A renewal of the renewal follows on from it in turn.
+        let next_id = renew_and_settle(&pool, renewal_id, "settlement").await;
+        let starts_at_end: bool = sqlx::query_scalar(
+            "select n.starts_at = r.ends_at from parking_pass n, parking_pass r where n.id = $1 and r.id = $2",
+        )
+        .bind(next_id)
+        .bind(renewal_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!(starts_at_end);
+    }
+
+    #[sqlx::test]
+    async fn expired_order_cancels_the_pass(pool: PgPool) {
+        seed(&pool).await;
+        let pass_id = seed_pass(&pool, 100.0).await;
+
+        let renewal_id = renew_and_settle(&pool, pass_id, "expire").await;
+
+        let status: String = sqlx::query_scalar("select status::text from parking_pass where id = $1")
+            .bind(renewal_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "cancelled");
+    }
+
+    #[sqlx::test]
+    async fn holders_are_only_shown_to_the_owner_of_the_lot(pool: PgPool) {
+        seed(&pool).await;
+        seed_pass(&pool, 100.0).await;
+
+        let app = build(pool.clone());
+        let holders = |authorization: Option<String>| {
+            let mut request = Request::get(format!("/parking-pass/parking-lot/{}/holders", PARKING_LOT_ID));
+            if let Some(authorization) = authorization {
+                request = request.header("authorization", authorization);
+            }
+            request.body(AxumBody::empty()).unwrap()
+        };
+
+        let anonymous = app.clone().oneshot(holders(None)).await.unwrap();
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+
+        let driver = app.clone().oneshot(holders(Some(bearer(EASYPARK_PHONE_NUMBER)))).await.unwrap();
+        assert_eq!(driver.status(), StatusCode::UNAUTHORIZED);
+
+        let owner = app.oneshot(holders(Some(bearer(OWNER_PHONE_NUMBER)))).await.unwrap();
+        assert_eq!(owner.status(), StatusCode::OK);
+        assert_eq!(data(owner).await.as_array().unwrap().len(), 1);
+    }
+}
@@ -1,7 +1,7 @@
 pub mod router;
 
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres};
+use sqlx::PgExecutor;
 use uuid::Uuid;
 
 use crate::error::aggregate::Result;
@@ -25,7 +25,7 @@ pub struct TransactionHistory {
 }
 
 impl TransactionHistory {
-    pub async fn save(self, pool: &Pool<Postgres>) -> Result<TransactionHistory> {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<TransactionHistory> {
         let user = sqlx::query_as!(
             TransactionHistory,
             r#"
@@ -48,25 +48,25 @@ impl TransactionHistory {
             self.fraud_status,
             self.currency
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await?;
 
         Ok(user)
     }
 
-    pub async fn find_one(id: Uuid, pool: &Pool<Postgres>) -> Result<TransactionHistory> {
+    pub async fn find_one(id: Uuid, executor: impl PgExecutor<'_>) -> Result<TransactionHistory> {
         let user = sqlx::query_as!(
             TransactionHistory,
             r#"select * from transaction_history where id = $1"#,
             id
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await?;
 
         Ok(user)
     }
 
-    pub async fn update(self, pool: &Pool<Postgres>) -> Result<TransactionHistory> {
+    pub async fn update(self, executor: impl PgExecutor<'_>) -> Result<TransactionHistory> {
         let user = sqlx::query_as!(
             TransactionHistory,
             r#"
@@ -102,7 +102,7 @@ impl TransactionHistory {
             self.currency,
             self.id
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await?;
 
         Ok(user)
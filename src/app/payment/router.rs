@@ -5,18 +5,23 @@ use chrono::Utc;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Pool, Postgres};
+use tracing::error;
 use uuid::Uuid;
 
+use crate::app::parking_area::layout::SpotAllocation;
 use crate::app::parking_history::ParkingHistory;
 use crate::app::parking_history::ParkingHistoryWithTotalAmount;
+use crate::app::parking_history::PenaltyReason;
 use crate::app::parking_history::TicketStatus;
 use crate::app::parking_history::UpdateParkingHistory;
+use crate::app::parking_pass::ParkingPass;
 use crate::app::user::User;
 use crate::error::aggregate::Error;
 use crate::{
     error::aggregate::Result,
     extractor::{app_body::Body, app_json::AppSuccess},
     middleware::base::print_request_body,
+    unit_of_work::UnitOfWork,
 };
 
 use super::TransactionHistory;
@@ -24,6 +29,7 @@ use super::TransactionHistory;
 pub fn build(pool: Pool<Postgres>) -> Router {
     let router = Router::new()
         .route("/generate", post(generate))
+        .route("/pass", post(generate_pass))
         .route("/callback", post(callback))
         .layer(middleware::from_fn(print_request_body))
         .with_state(pool);
@@ -102,46 +108,21 @@ struct Action {
 #[derive(Serialize, Debug)]
 struct Payment {
     parking_history: ParkingHistoryWithTotalAmount,
-    transaction: Transaction,
+    transaction: Option<Transaction>,
 }
 
-async fn generate(
-    State(pool): State<PgPool>,
-    Body(payload): Body<TransactionPayload>,
-) -> Result<AppSuccess<Payment>> {
-    let url = std::env::var("MIDTRANS_CHARGE_API").expect("MIDTRANS credential must be set");
-    let server_key = std::env::var("MIDTRANS_SERVER_KEY").expect("MIDTRANS credential must be set");
-
-    let api_key = BASE64_STANDARD.encode(format!("{}:", server_key));
-    let api_key = format!("Basic {}", api_key);
-
-    let TransactionPayload { parking_history_id } = payload;
-
-    let id_transaction = Uuid::new_v4();
-    let parking_history = ParkingHistory::find_one(parking_history_id, &pool).await?;
-    let parking_history = ParkingHistory::update_transaction_id(
-        parking_history.transaction_id,
-        id_transaction,
-        &pool,
-    )
-    .await?;
-    if parking_history.ticket_status != TicketStatus::Active {
-        return Err(Error::BadRequest("Ticket is not active".to_string()));
-    }
-
-    let easypark = User::find_one_by_id(parking_history.easypark_id, &pool).await?;
-
-    let payment_data = PaymentData {
+fn gopay_payment(
+    order_id: Uuid,
+    item_details: Vec<ItemDetail>,
+    easypark: User,
+) -> PaymentData {
+    PaymentData {
         payment_type: "gopay".to_string(),
         transaction_details: TransactionDetails {
-            order_id: parking_history.transaction_id,
-            gross_amount: parking_history.total_amount.unwrap_or(0.0),
+            order_id,
+            gross_amount: item_details.iter().map(|item| item.price).sum(),
         },
-        item_details: vec![ItemDetail {
-            price: parking_history.total_amount.unwrap_or(0.0),
-            quantity: 1,
-            name: "Parking Payment".to_string(),
-        }],
+        item_details,
         customer_details: CustomerDetails {
             first_name: easypark.name,
             phone: easypark.phone_number,
@@ -151,9 +132,17 @@ async fn generate(
             callback_url: "https://ea0d-36-72-17-45.ngrok-free.app/api/payment/callback"
                 .to_string(),
         },
-    };
+    }
+}
+
+async fn charge(payment_data: &PaymentData) -> Result<Transaction> {
+    let url = std::env::var("MIDTRANS_CHARGE_API").expect("MIDTRANS credential must be set");
+    let server_key = std::env::var("MIDTRANS_SERVER_KEY").expect("MIDTRANS credential must be set");
 
-    let json_body = serde_json::to_string(&payment_data).unwrap();
+    let api_key = BASE64_STANDARD.encode(format!("{}:", server_key));
+    let api_key = format!("Basic {}", api_key);
+
+    let json_body = serde_json::to_string(payment_data).unwrap();
 
     let client = Client::new();
     let response = client
@@ -167,9 +156,187 @@ async fn generate(
 
     let data: Transaction = response.json().await?;
 
+    Ok(data)
+}
+
+async fn generate(
+    State(pool): State<PgPool>,
+    Body(payload): Body<TransactionPayload>,
+) -> Result<AppSuccess<Payment>> {
+    let TransactionPayload { parking_history_id } = payload;
+
+    // The new order id is committed before Midtrans is called, so neither the
+    // ticket row nor a pool connection is held while waiting on the network.
+    let mut uow = UnitOfWork::begin(&pool).await?;
+
+    let parking_history = ParkingHistory::find_one(parking_history_id, uow.connection()).await?;
+    if parking_history.ticket_status != TicketStatus::Active {
+        return Err(Error::BadRequest("Ticket is not active".to_string()));
+    }
+
+    // A ticket fully covered by a pass has nothing to charge, Midtrans does
+    // not take an order of zero so it is closed right here.
+    if parking_history.amount <= 0.0 && parking_history.penalty_amount <= 0.0 {
+        let now = Utc::now().naive_utc();
+        let parking_history = UpdateParkingHistory::update_ticket_status(
+            parking_history.transaction_id,
+            TicketStatus::NotActive,
+            now,
+            uow.connection(),
+        )
+        .await?;
+        let released_at = parking_history.check_out_date.unwrap_or(now);
+        SpotAllocation::release(parking_history.id, released_at, uow.connection()).await?;
+
+        uow.commit().await?;
+
+        return Ok(AppSuccess(Payment {
+            parking_history: ParkingHistoryWithTotalAmount::free(parking_history),
+            transaction: None,
+        }));
+    }
+
+    let previous_transaction_id = parking_history.transaction_id;
+    let parking_history = ParkingHistory::update_transaction_id(
+        previous_transaction_id,
+        Uuid::new_v4(),
+        uow.connection(),
+    )
+    .await?;
+    let easypark = User::find_one_by_id(parking_history.easypark_id, uow.connection()).await?;
+
+    uow.commit().await?;
+
+    let parking_amount = parking_history.total_amount.unwrap_or(0.0);
+    let mut item_details = vec![ItemDetail {
+        price: parking_amount,
+        quantity: 1,
+        name: "Parking Payment".to_string(),
+    }];
+
+    if parking_history.penalty_amount > 0.0 {
+        let name = match parking_history.penalty_reason {
+            Some(PenaltyReason::LostTicket) => "Lost Ticket Penalty",
+            Some(PenaltyReason::Overstay) => "Overstay Penalty",
+            _ => "Penalty",
+        };
+        item_details.push(ItemDetail {
+            price: parking_history.penalty_amount,
+            quantity: 1,
+            name: name.to_string(),
+        });
+    }
+
+    let payment_data = gopay_payment(parking_history.transaction_id, item_details, easypark);
+    let data = match charge(&payment_data).await {
+        Ok(data) => data,
+        Err(err) => {
+            // Point the ticket back at the order Midtrans already knows about.
+            if let Err(restore_err) = ParkingHistory::update_transaction_id(
+                parking_history.transaction_id,
+                previous_transaction_id,
+                &pool,
+            )
+            .await
+            {
+                error!(
+                    "Fail to restore order id of ticket {}: {:?}",
+                    parking_history.id, restore_err
+                );
+            }
+            return Err(err);
+        }
+    };
+
     Ok(AppSuccess(Payment {
         parking_history,
-        transaction: data
+        transaction: Some(data)
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct PassTransactionPayload {
+    parking_pass_id: Uuid,
+}
+
+#[derive(Serialize, Debug)]
+struct PassPayment {
+    parking_pass: ParkingPass,
+    transaction: Transaction,
+}
+
+/// Charges a pending pass under a fresh order id, like tickets do.
+async fn generate_pass(
+    State(pool): State<PgPool>,
+    Body(payload): Body<PassTransactionPayload>,
+) -> Result<AppSuccess<PassPayment>> {
+    let PassTransactionPayload { parking_pass_id } = payload;
+
+    // Same as tickets, the new order is committed before Midtrans is called.
+    let mut uow = UnitOfWork::begin(&pool).await?;
+
+    let previous_transaction_id = ParkingPass::find_one(parking_pass_id, uow.connection())
+        .await?
+        .transaction_id;
+    let transaction_history = TransactionHistory {
+        id: Uuid::new_v4(),
+        transaction_time: None,
+        transaction_status: None,
+        transaction_id: None,
+        status_message: None,
+        status_code: None,
+        signature_key: None,
+        settlement_time: None,
+        payment_type: None,
+        order_id: None,
+        merchant_id: None,
+        gross_amount: None,
+        fraud_status: None,
+        currency: None,
+    };
+    let transaction_history = transaction_history.save(uow.connection()).await?;
+    let parking_pass = ParkingPass::update_transaction_id(
+        parking_pass_id,
+        transaction_history.id,
+        Utc::now().naive_utc(),
+        uow.connection(),
+    )
+    .await?;
+
+    let easypark = User::find_one_by_id(parking_pass.easypark_id, uow.connection()).await?;
+
+    uow.commit().await?;
+
+    let item_details = vec![ItemDetail {
+        price: parking_pass.price,
+        quantity: 1,
+        name: parking_pass.period.label().to_string(),
+    }];
+    let payment_data = gopay_payment(parking_pass.transaction_id, item_details, easypark);
+    let data = match charge(&payment_data).await {
+        Ok(data) => data,
+        Err(err) => {
+            // Point the pass back at the order Midtrans already knows about.
+            if let Err(restore_err) = ParkingPass::update_transaction_id(
+                parking_pass.id,
+                previous_transaction_id,
+                Utc::now().naive_utc(),
+                &pool,
+            )
+            .await
+            {
+                error!(
+                    "Fail to restore order id of pass {}: {:?}",
+                    parking_pass.id, restore_err
+                );
+            }
+            return Err(err);
+        }
+    };
+
+    Ok(AppSuccess(PassPayment {
+        parking_pass,
+        transaction: data,
     }))
 }
 
@@ -230,7 +397,8 @@ impl TransactionCallback {
 
 #[derive(Serialize)]
 struct Callback {
-    parking_history: ParkingHistory,
+    parking_history: Option<ParkingHistory>,
+    parking_pass: Option<ParkingPass>,
     transaction_history: TransactionHistory,
 }
 
@@ -246,18 +414,51 @@ async fn callback(
     State(pool): State<PgPool>,
     Body(payload): Body<TransactionCallback>,
 ) -> Result<AppSuccess<Callback>> {
+    let mut uow = UnitOfWork::begin(&pool).await?;
+
     let transaction_history = payload.into_trasaction_history();
-    let transaction_history = transaction_history.update(&pool).await?;
+    let transaction_history = transaction_history.update(uow.connection()).await?;
+
+    // Orders of passes only activate the pass once the money is in, an order
+    // that can no longer be paid cancels it.
+    if let Some(parking_pass) = ParkingPass::find_by_transaction(transaction_history.id, uow.connection()).await? {
+        let now = Utc::now().naive_utc();
+        let parking_pass = match transaction_history.transaction_status.as_deref() {
+            Some("settlement") | Some("capture") => {
+                ParkingPass::activate(parking_pass.id, now, uow.connection()).await?
+            }
+            Some("deny") | Some("expire") | Some("cancel") | Some("failure") => {
+                ParkingPass::cancel(parking_pass.id, now, uow.connection())
+                    .await?
+                    .unwrap_or(parking_pass)
+            }
+            _ => parking_pass,
+        };
+
+        uow.commit().await?;
+
+        return Ok(AppSuccess(Callback {
+            parking_history: None,
+            parking_pass: Some(parking_pass),
+            transaction_history,
+        }));
+    }
+
     let parking_history = UpdateParkingHistory::update_ticket_status(
         transaction_history.id,
         TicketStatus::NotActive,
         Utc::now().naive_utc(),
-        &pool,
+        uow.connection(),
     )
     .await?;
+    let released_at = parking_history.check_out_date.unwrap_or_else(|| Utc::now().naive_utc());
+    SpotAllocation::release(parking_history.id, released_at, uow.connection()).await?;
+
+    uow.commit().await?;
 
     Ok(AppSuccess(Callback {
-        parking_history,
+        parking_history: Some(parking_history),
+        parking_pass: None,
         transaction_history,
     }))
 }
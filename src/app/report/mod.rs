@@ -0,0 +1,272 @@
+pub mod router;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::error::aggregate::{Error, Result};
+
+pub const DEFAULT_TIMEZONE: &str = "Asia/Jakarta";
+
+pub async fn validate_timezone(timezone: &str, executor: impl PgExecutor<'_>) -> Result<()> {
+    let exists = sqlx::query_scalar!(
+        r#"select exists(select 1 from pg_timezone_names where name = $1) as "exists!""#,
+        timezone
+    )
+        .fetch_one(executor)
+        .await?;
+
+    if !exists {
+        return Err(Error::BadRequest(format!("Timezone {} is not valid", timezone)));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevenueBreakdown {
+    pub dimension: String,
+    pub key: Option<String>,
+    pub label: Option<String>,
+    pub revenue: Option<f64>,
+    pub total_ticket: Option<i64>,
+    pub previous_revenue: Option<f64>,
+    pub previous_total_ticket: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct RevenueQuery {
+    pub owner_id: Uuid,
+    pub created_at_start_filter: NaiveDateTime,
+    pub created_at_end_filter: NaiveDateTime,
+    pub previous_start_filter: Option<NaiveDateTime>,
+    pub timezone: String,
+}
+
+impl RevenueBreakdown {
+    /// Breaks closed tickets down by every report dimension in one pass using
+    /// grouping sets. Revenue is what the ticket billed, so cash tickets count
+    /// as well. Tickets of the previous period, when requested, are only
+    /// counted in the `previous_*` columns.
+    pub async fn by_owner(query: &RevenueQuery, executor: impl PgExecutor<'_>) -> Result<Vec<RevenueBreakdown>> {
+        let data = sqlx::query_as!(
+            RevenueBreakdown,
+            r#"
+                with ticket as (
+                    select ph.parking_lot_id,
+                        pl.area_name,
+                        ph.keeper_id,
+                        ku."name" as keeper_name,
+                        ph.vehicle_type::text as vehicle_type,
+                        ph.payment::text as payment_type,
+                        case when ph.created_at >= $2
+                            then to_char((ph.created_at at time zone 'UTC') at time zone $5, 'YYYY-MM-DD')
+                        end as day,
+                        coalesce(
+                            ceil(extract(epoch from (ph.check_out_date - coalesce(ph.check_in_date, ph.created_at))) / 3600) * ph.amount,
+                            0
+                        ) + ph.penalty_amount as revenue,
+                        ph.created_at >= $2 as is_current
+                    from parking_history ph
+                    join parking_lot pl on pl.id = ph.parking_lot_id
+                    join "user" ku on ku.id = ph.keeper_id
+                    where ph.ticket_status = 'not_active' and
+                        ph.deleted_at is null and
+                        ph.owner_id = $1 and
+                        ph.created_at >= coalesce($4, $2) and
+                        ph.created_at <= $3
+                )
+                select
+                    case
+                        when grouping(parking_lot_id) = 0 then 'parking_lot'
+                        when grouping(day) = 0 then 'day'
+                        when grouping(vehicle_type) = 0 then 'vehicle_type'
+                        when grouping(payment_type) = 0 then 'payment_type'
+                        when grouping(keeper_id) = 0 then 'keeper'
+                        else 'total'
+                    end as "dimension!",
+                    coalesce(parking_lot_id::text, day, vehicle_type, payment_type, keeper_id::text) as key,
+                    coalesce(area_name, keeper_name, day, vehicle_type, payment_type) as label,
+                    coalesce(sum(revenue) filter (where is_current), 0) as revenue,
+                    count(*) filter (where is_current) as total_ticket,
+                    coalesce(sum(revenue) filter (where not is_current), 0) as previous_revenue,
+                    count(*) filter (where not is_current) as previous_total_ticket
+                from ticket
+                group by grouping sets (
+                    (parking_lot_id, area_name),
+                    (day),
+                    (vehicle_type),
+                    (payment_type),
+                    (keeper_id, keeper_name),
+                    ()
+                )
+                order by 1, 4 desc
+            "#,
+            query.owner_id,
+            query.created_at_start_filter,
+            query.created_at_end_filter,
+            query.previous_start_filter,
+            query.timezone
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+}
+
+/// Tickets of the period paid through the payment gateway, next to what the
+/// gateway actually settled for them.
+#[derive(Debug)]
+pub struct Settlement {
+    pub billed_amount: f64,
+    pub billed_ticket: i64,
+    pub settled_amount: f64,
+    pub settled_ticket: i64,
+}
+
+impl Settlement {
+    pub async fn by_owner(query: &RevenueQuery, executor: impl PgExecutor<'_>) -> Result<Settlement> {
+        let data = sqlx::query_as!(
+            Settlement,
+            r#"
+                with ticket as (
+                    select coalesce(
+                            ceil(extract(epoch from (ph.check_out_date - coalesce(ph.check_in_date, ph.created_at))) / 3600) * ph.amount,
+                            0
+                        ) + ph.penalty_amount as billed,
+                        cast(th.gross_amount as double precision) as settled
+                    from parking_history ph
+                    left join transaction_history th on th.id = ph.transaction_id and
+                        th.transaction_status in ('settlement', 'capture')
+                    where ph.ticket_status = 'not_active' and
+                        ph.payment <> 'cash' and
+                        ph.deleted_at is null and
+                        ph.owner_id = $1 and
+                        ph.created_at >= $2 and
+                        ph.created_at <= $3
+                )
+                select coalesce(sum(billed), 0) as "billed_amount!",
+                    count(*) as "billed_ticket!",
+                    coalesce(sum(settled), 0) as "settled_amount!",
+                    count(settled) as "settled_ticket!"
+                from ticket
+            "#,
+            query.owner_id,
+            query.created_at_start_filter,
+            query.created_at_end_filter
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(data)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl Granularity {
+    /// Unit understood by postgres `date_trunc` and `interval`.
+    pub fn as_unit(&self) -> &'static str {
+        match self {
+            Granularity::Hour => "hour",
+            Granularity::Day => "day",
+            Granularity::Week => "week",
+            Granularity::Month => "month",
+        }
+    }
+
+    /// Longest range a single request may cover, keeps the zero-filled series
+    /// at a few thousand buckets at most.
+    pub fn max_range(&self) -> chrono::Duration {
+        match self {
+            Granularity::Hour => chrono::Duration::days(93),
+            Granularity::Day => chrono::Duration::days(366 * 5),
+            Granularity::Week | Granularity::Month => chrono::Duration::days(366 * 20),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimeSeriesBucket {
+    pub bucket: Option<NaiveDateTime>,
+    pub total_ticket: Option<i64>,
+    pub closed_ticket: Option<i64>,
+    pub revenue: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct TimeSeriesQuery {
+    pub owner_id: Uuid,
+    pub parking_lot_id: Option<Uuid>,
+    pub created_at_start_filter: NaiveDateTime,
+    pub created_at_end_filter: NaiveDateTime,
+    pub granularity: Granularity,
+    pub timezone: String,
+}
+
+impl TimeSeriesBucket {
+    /// Buckets are the local start of every hour/day/week/month in `timezone`
+    /// between both filters, buckets without tickets are returned with zeros.
+    /// Revenue counts what cash tickets billed and what the gateway settled,
+    /// gateway orders that never settled add nothing.
+    pub async fn by_owner(query: &TimeSeriesQuery, executor: impl PgExecutor<'_>) -> Result<Vec<TimeSeriesBucket>> {
+        let data = sqlx::query_as!(
+            TimeSeriesBucket,
+            r#"
+                with series as (
+                    select generate_series(
+                        date_trunc($5, ($2::timestamp at time zone 'UTC') at time zone $6),
+                        date_trunc($5, ($3::timestamp at time zone 'UTC') at time zone $6),
+                        ('1 ' || $5)::interval
+                    ) as bucket
+                ),
+                ticket as (
+                    select date_trunc($5, (ph.created_at at time zone 'UTC') at time zone $6) as bucket,
+                        ph.ticket_status = 'not_active' as is_closed,
+                        case when ph.payment = 'cash'
+                            then coalesce(
+                                ceil(extract(epoch from (ph.check_out_date - coalesce(ph.check_in_date, ph.created_at))) / 3600) * ph.amount,
+                                0
+                            ) + ph.penalty_amount
+                            else cast(th.gross_amount as double precision)
+                        end as revenue
+                    from parking_history ph
+                    left join transaction_history th on th.id = ph.transaction_id and
+                        th.transaction_status in ('settlement', 'capture')
+                    where ph.owner_id = $1 and
+                        ph.deleted_at is null and
+                        ($4::uuid is null or ph.parking_lot_id = $4) and
+                        ph.created_at >= $2 and
+                        ph.created_at <= $3
+                )
+                select s.bucket,
+                    count(t.bucket) as total_ticket,
+                    count(t.bucket) filter (where t.is_closed) as closed_ticket,
+                    coalesce(sum(t.revenue) filter (where t.is_closed), 0) as revenue
+                from series s
+                left join ticket t on t.bucket = s.bucket
+                group by s.bucket
+                order by s.bucket
+            "#,
+            query.owner_id,
+            query.created_at_start_filter,
+            query.created_at_end_filter,
+            query.parking_lot_id,
+            query.granularity.as_unit(),
+            query.timezone
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(data)
+    }
+}
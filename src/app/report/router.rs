@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Query, State},
+    middleware,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    error::aggregate::{Error, Result},
+    extractor::app_json::AppSuccess,
+    middleware::base::print_request_body,
+};
+
+use super::{
+    validate_timezone, Granularity, RevenueBreakdown, RevenueQuery, Settlement, TimeSeriesBucket,
+    TimeSeriesQuery, DEFAULT_TIMEZONE,
+};
+
+pub fn build(pool: Pool<Postgres>) -> Router {
+    let router = Router::new()
+        .route("/revenue", get(revenue))
+        .route("/time-series", get(time_series))
+        .layer(middleware::from_fn(print_request_body))
+        .with_state(pool);
+
+    Router::new().nest("/report", router)
+}
+
+#[derive(Deserialize, Clone, Serialize)]
+pub struct RevenuePayload {
+    pub owner_id: Uuid,
+    pub created_at_start_filter: DateTime<Utc>,
+    pub created_at_end_filter: DateTime<Utc>,
+    pub compare_previous: Option<bool>,
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportPeriod {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Reconciliation {
+    pub billed_amount: f64,
+    pub billed_ticket: i64,
+    pub settled_amount: f64,
+    pub settled_ticket: i64,
+    pub reconciled: bool,
+    pub difference: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevenueReport {
+    pub timezone: String,
+    pub period: ReportPeriod,
+    pub previous_period: Option<ReportPeriod>,
+    pub total: Option<RevenueBreakdown>,
+    pub breakdown: BTreeMap<String, Vec<RevenueBreakdown>>,
+    pub reconciliation: Reconciliation,
+}
+
+async fn revenue(
+    State(pool): State<PgPool>,
+    Query(payload): Query<RevenuePayload>,
+) -> Result<AppSuccess<RevenueReport>> {
+    if payload.created_at_start_filter > payload.created_at_end_filter {
+        return Err(Error::BadRequest(String::from(
+            "created_at_start_filter must be before created_at_end_filter",
+        )));
+    }
+
+    let timezone = payload
+        .timezone
+        .unwrap_or_else(|| String::from(DEFAULT_TIMEZONE));
+    validate_timezone(&timezone, &pool).await?;
+
+    // The previous period has the same length and ends right where the
+    // requested one starts.
+    let previous_period = payload.compare_previous.unwrap_or(false).then(|| ReportPeriod {
+        start: payload.created_at_start_filter
+            - (payload.created_at_end_filter - payload.created_at_start_filter),
+        end: payload.created_at_start_filter,
+    });
+
+    let query = RevenueQuery {
+        owner_id: payload.owner_id,
+        created_at_start_filter: payload.created_at_start_filter.naive_utc(),
+        created_at_end_filter: payload.created_at_end_filter.naive_utc(),
+        previous_start_filter: previous_period
+            .as_ref()
+            .map(|period| period.start.naive_utc()),
+        timezone: timezone.clone(),
+    };
+
+    let rows = RevenueBreakdown::by_owner(&query, &pool).await?;
+    let settlement = Settlement::by_owner(&query, &pool).await?;
+
+    let mut total = None;
+    let mut breakdown: BTreeMap<String, Vec<RevenueBreakdown>> = BTreeMap::new();
+    for row in rows {
+        if row.dimension == "total" {
+            total = Some(row);
+        } else if row.key.is_none() {
+            // Tickets of the previous period have no day bucket, they are only
+            // part of the comparison columns.
+            continue;
+        } else {
+            breakdown.entry(row.dimension.clone()).or_default().push(row);
+        }
+    }
+
+    // What gateway-paid tickets billed has to match what the gateway settled
+    // for them, cash is counted by the keeper shift instead.
+    let Settlement {
+        billed_amount,
+        billed_ticket,
+        settled_amount,
+        settled_ticket,
+    } = settlement;
+    let difference = billed_amount - settled_amount;
+    let reconciled = difference.abs() < 0.01 && billed_ticket == settled_ticket;
+
+    Ok(AppSuccess(RevenueReport {
+        timezone,
+        period: ReportPeriod {
+            start: payload.created_at_start_filter,
+            end: payload.created_at_end_filter,
+        },
+        previous_period,
+        total,
+        breakdown,
+        reconciliation: Reconciliation {
+            billed_amount,
+            billed_ticket,
+            settled_amount,
+            settled_ticket,
+            reconciled,
+            difference,
+        },
+    }))
+}
+
+#[derive(Deserialize, Clone, Serialize)]
+pub struct TimeSeriesPayload {
+    pub owner_id: Uuid,
+    pub parking_lot_id: Option<Uuid>,
+    pub created_at_start_filter: DateTime<Utc>,
+    pub created_at_end_filter: DateTime<Utc>,
+    pub granularity: Option<Granularity>,
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimeSeries {
+    pub timezone: String,
+    pub granularity: Granularity,
+    pub buckets: Vec<TimeSeriesBucket>,
+}
+
+async fn time_series(
+    State(pool): State<PgPool>,
+    Query(payload): Query<TimeSeriesPayload>,
+) -> Result<AppSuccess<TimeSeries>> {
+    if payload.created_at_start_filter > payload.created_at_end_filter {
+        return Err(Error::BadRequest(String::from(
+            "created_at_start_filter must be before created_at_end_filter",
+        )));
+    }
+
+    let granularity = payload.granularity.unwrap_or(Granularity::Day);
+    if payload.created_at_end_filter - payload.created_at_start_filter > granularity.max_range() {
+        return Err(Error::BadRequest(format!(
+            "Date range is too long for {} granularity, use at most {} days",
+            granularity.as_unit(),
+            granularity.max_range().num_days()
+        )));
+    }
+
+    let timezone = payload
+        .timezone
+        .unwrap_or_else(|| String::from(DEFAULT_TIMEZONE));
+    validate_timezone(&timezone, &pool).await?;
+
+    let query = TimeSeriesQuery {
+        owner_id: payload.owner_id,
+        parking_lot_id: payload.parking_lot_id,
+        created_at_start_filter: payload.created_at_start_filter.naive_utc(),
+        created_at_end_filter: payload.created_at_end_filter.naive_utc(),
+        granularity,
+        timezone: timezone.clone(),
+    };
+    let buckets = TimeSeriesBucket::by_owner(&query, &pool).await?;
+
+    Ok(AppSuccess(TimeSeries {
+        timezone,
+        granularity,
+        buckets,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body as AxumBody},
+        http::{Request, StatusCode},
+    };
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use super::build;
+    use crate::app::fixture::{seed, EASYPARK_ID, KEEPER_ID, OWNER_ID, PARKING_LOT_ID};
+
+    /// A closed two hour car ticket at 5000 an hour, with the gateway order
+    /// in the given state.
+    async fn closed_ticket(
+        payment: &str,
+        transaction_status: Option<&str>,
+        gross_amount: Option<&str>,
+        pool: &PgPool,
+    ) -> Uuid {
+        let transaction_id = Uuid::new_v4();
+        sqlx::query(
+            "insert into transaction_history (id, transaction_status, gross_amount) values ($1, $2, $3)",
+        )
+        .bind(transaction_id)
+        .bind(transaction_status)
+        .bind(gross_amount)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        sqlx::query(
+            r#"
+                insert into parking_history (id, ticket_status, vehicle_type, payment, amount, parking_lot_id,
+                    easypark_id, keeper_id, owner_id, transaction_id, created_at, check_in_date, check_out_date)
+                values ($1, 'not_active', 'car', $2::payment_type, 5000, $3, $4, $5, $6, $7,
+                    '2024-06-01 08:00', '2024-06-01 08:00', '2024-06-01 09:30')
+            "#,
+        )
+        .bind(transaction_id)
+        .bind(payment)
+        .bind(PARKING_LOT_ID)
+        .bind(EASYPARK_ID)
+        .bind(KEEPER_ID)
+        .bind(OWNER_ID)
+        .bind(transaction_id)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        transaction_id
+    }
+
+    async fn fetch_reconciliation(pool: &PgPool) -> serde_json::Value {
+        let uri = format!(
+            "/report/revenue?owner_id={}&created_at_start_filter=2024-06-01T00:00:00Z&created_at_end_filter=2024-06-02T00:00:00Z",
+            OWNER_ID
+        );
+        let response = build(pool.clone())
+            .oneshot(Request::get(uri).body(AxumBody::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report["data"]["total"]["revenue"], 30000.0);
+
+        report["data"]["reconciliation"].take()
+    }
+
+    #[sqlx::test]
+    async fn revenue_reconciles_only_when_the_gateway_settled_every_ticket(pool: PgPool) {
+        seed(&pool).await;
+        closed_ticket("cash", None, None, &pool).await;
+        closed_ticket("qr", Some("settlement"), Some("10000.00"), &pool).await;
+        let pending = closed_ticket("qr", Some("pending"), Some("10000.00"), &pool).await;
+
+        let reconciliation = fetch_reconciliation(&pool).await;
+        assert_eq!(reconciliation["billed_amount"], 20000.0);
+        assert_eq!(reconciliation["billed_ticket"], 2);
+        assert_eq!(reconciliation["settled_amount"], 10000.0);
+        assert_eq!(reconciliation["settled_ticket"], 1);
+        assert_eq!(reconciliation["reconciled"], false);
+
+        sqlx::query("update transaction_history set transaction_status = 'settlement' where id = $1")
+            .bind(pending)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let reconciliation = fetch_reconciliation(&pool).await;
+        assert_eq!(reconciliation["settled_amount"], 20000.0);
+        assert_eq!(reconciliation["reconciled"], true);
+    }
+
+    #[sqlx::test]
+    async fn time_series_revenue_leaves_out_unsettled_gateway_orders(pool: PgPool) {
+        seed(&pool).await;
+        closed_ticket("cash", None, None, &pool).await;
+        closed_ticket("qr", Some("pending"), Some("10000.00"), &pool).await;
+
+        let uri = format!(
+            "/report/time-series?owner_id={}&created_at_start_filter=2024-06-01T00:00:00Z&created_at_end_filter=2024-06-01T12:00:00Z&timezone=UTC",
+            OWNER_ID
+        );
+        let response = build(pool.clone())
+            .oneshot(Request::get(uri).body(AxumBody::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let series: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let buckets = series["data"]["buckets"].as_array().unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0]["closed_ticket"], 2);
+        assert_eq!(buckets[0]["revenue"], 10000.0);
+    }
+}
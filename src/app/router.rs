@@ -3,12 +3,18 @@ use sqlx::{Pool, Postgres};
 
 use crate::error::aggregate::Error;
 
+use super::analytics::router::build as analytics_router;
 use super::auth::router::build as auth_router;
+use super::dashboard::router::build as dashboard_router;
 use super::file_upload::router::build as file_upload_router;
+use super::keeper_shift::router::build as keeper_shift_router;
 use super::parking_area::router::build as parking_lot_router;
 use super::parking_history::router::build as parking_history_router;
+use super::parking_pass::router::build as parking_pass_router;
 use super::payment::router::build as payment_router;
+use super::report::router::build as report_router;
 use super::user::router::build as user_router;
+use super::vehicle::router::build as vehicle_router;
 use super::whatsapp::router::build as wa_router;
 
 pub fn build(pool: Pool<Postgres>) -> Router {
@@ -21,7 +27,13 @@ pub fn build(pool: Pool<Postgres>) -> Router {
         .merge(payment_router(pool.clone()))
         .merge(parking_lot_router(pool.clone()))
         .merge(parking_history_router(pool.clone()))
-        .merge(file_upload_router())
+        .merge(parking_pass_router(pool.clone()))
+        .merge(vehicle_router(pool.clone()))
+        .merge(report_router(pool.clone()))
+        .merge(keeper_shift_router(pool.clone()))
+        .merge(analytics_router(pool.clone()))
+        .merge(dashboard_router(pool.clone()))
+        .merge(file_upload_router(pool.clone()))
 }
 
 async fn handler() {
@@ -0,0 +1,238 @@
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{Pool, Postgres};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info};
+
+use crate::{
+    app::{
+        analytics::{refresh_hourly_stat, ANALYTICS_LOCK_KEY},
+        parking_area::tariff::Tariff,
+        parking_history::{ParkingHistory, StaleTicket},
+        parking_pass::{ExpiringPass, ParkingPass},
+        whatsapp::send_message,
+    },
+    error::aggregate::Result,
+    unit_of_work::UnitOfWork,
+};
+
+/// Every server instance runs the scheduler, the advisory lock on this key makes
+/// sure only one of them does the work of a given tick.
+const SCHEDULER_LOCK_KEY: i64 = 0x6561_7379_7061_726b;
+
+pub struct SchedulerConfig {
+    pub interval: Duration,
+    pub ticket_expiry: chrono::Duration,
+    pub ticket_review: chrono::Duration,
+    pub analytics_refresh: Duration,
+    pub analytics_window: chrono::Duration,
+    pub pass_reminder: chrono::Duration,
+}
+
+/// What the scheduler remembers between ticks of this instance.
+#[derive(Default)]
+pub struct SchedulerState {
+    pub analytics_refreshed_at: Option<Instant>,
+}
+
+impl SchedulerConfig {
+    pub fn from_env() -> SchedulerConfig {
+        SchedulerConfig {
+            interval: Duration::from_secs(env_or("SCHEDULER_INTERVAL_SECONDS", 60)),
+            ticket_expiry: chrono::Duration::minutes(env_or("TICKET_EXPIRY_MINUTES", 30)),
+            ticket_review: chrono::Duration::hours(env_or("TICKET_REVIEW_HOURS", 12)),
+            analytics_refresh: Duration::from_secs(60 * env_or("ANALYTICS_REFRESH_MINUTES", 15)),
+            analytics_window: chrono::Duration::hours(env_or("ANALYTICS_REFRESH_WINDOW_HOURS", 24)),
+            pass_reminder: chrono::Duration::days(env_or("PASS_REMINDER_DAYS", 3)),
+        }
+    }
+}
+
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+pub fn spawn(pool: Pool<Postgres>) {
+    let config = SchedulerConfig::from_env();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut state = SchedulerState::default();
+
+        loop {
+            interval.tick().await;
+            if let Err(err) = run(&pool, &config, &mut state).await {
+                error!("Scheduler tick failed: {:?}", err);
+            }
+        }
+    });
+}
+
+async fn run(
+    pool: &Pool<Postgres>,
+    config: &SchedulerConfig,
+    state: &mut SchedulerState,
+) -> Result<()> {
+    let mut uow = UnitOfWork::begin(pool).await?;
+    if !uow.try_advisory_lock(SCHEDULER_LOCK_KEY).await? {
+        debug!("Scheduler tick is handled by another instance");
+        return Ok(());
+    }
+
+    let now = Utc::now().naive_utc();
+    let expired =
+        ParkingHistory::expire_unconfirmed(now - config.ticket_expiry, now, uow.connection())
+            .await?;
+    let flagged =
+        ParkingHistory::flag_for_review(now - config.ticket_review, now, uow.connection())
+            .await?;
+    // Scheduled tariffs reach the prices shown on the lot once they start.
+    let repriced = Tariff::sync_prices(None, now, uow.connection()).await?;
+    let expiring =
+        ParkingPass::find_expiring(now, now + config.pass_reminder, uow.connection()).await?;
+
+    uow.commit().await?;
+
+    if !expired.is_empty() || !flagged.is_empty() {
+        info!(
+            "Expired {} ticket(s), flagged {} ticket(s) for review",
+            expired.len(),
+            flagged.len()
+        );
+    }
+    if repriced > 0 {
+        info!("Applied scheduled tariffs to {} parking lot(s)", repriced);
+    }
+
+    // Notifications go out after the commit so a rolled back tick never tells
+    // anyone about a status change that did not happen.
+    for ticket in expired {
+        let driver_message = format!(
+            "Your parking ticket at {} was not checked in and has expired.",
+            ticket.area_name
+        );
+        let keeper_message = format!(
+            "Ticket {} at {} was not checked in and has expired.",
+            plate_number(&ticket),
+            ticket.area_name
+        );
+        notify(&ticket, driver_message, keeper_message).await;
+    }
+
+    for ticket in flagged {
+        let driver_message = format!(
+            "Your parking ticket at {} is still open, please complete the payment.",
+            ticket.area_name
+        );
+        let keeper_message = format!(
+            "Ticket {} at {} has been open for more than {} hours and needs review.",
+            plate_number(&ticket),
+            ticket.area_name,
+            config.ticket_review.num_hours()
+        );
+        notify(&ticket, driver_message, keeper_message).await;
+    }
+
+    // A pass is only marked as reminded once its message went out, a failed
+    // send is tried again on the next tick.
+    for pass in expiring {
+        if !remind(&pass).await {
+            continue;
+        }
+        if let Err(err) = ParkingPass::mark_reminded(pass.id, now, pool).await {
+            error!("Fail to mark pass {} as reminded: {:?}", pass.id, err);
+        }
+    }
+
+    let refresh_analytics = state
+        .analytics_refreshed_at
+        .is_none_or(|refreshed_at| refreshed_at.elapsed() >= config.analytics_refresh);
+    if refresh_analytics {
+        match refresh_analytics_window(pool, now - config.analytics_window).await {
+            Ok(()) => state.analytics_refreshed_at = Some(Instant::now()),
+            Err(err) => error!("Analytics refresh failed: {:?}", err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs after the ticket work is committed, a failing refresh is retried on
+/// the next tick without undoing anything else.
+async fn refresh_analytics_window(pool: &Pool<Postgres>, from: NaiveDateTime) -> Result<()> {
+    let mut uow = UnitOfWork::begin(pool).await?;
+    if !uow.try_advisory_lock(ANALYTICS_LOCK_KEY).await? {
+        debug!("Analytics refresh is handled by another instance");
+        return Ok(());
+    }
+
+    refresh_hourly_stat(from, None, uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(())
+}
+
+fn plate_number(ticket: &StaleTicket) -> &str {
+    ticket.plate_number.as_deref().unwrap_or("-")
+}
+
+async fn notify(ticket: &StaleTicket, driver_message: String, keeper_message: String) {
+    let messages = [
+        (&ticket.easypark_phone_number, driver_message),
+        (&ticket.keeper_phone_number, keeper_message),
+    ];
+
+    for (phone_number, message) in messages {
+        match send_message(phone_number, &message).await {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => error!(
+                "Fail to notify {} about ticket {}: {}",
+                phone_number,
+                ticket.id,
+                response.status()
+            ),
+            Err(err) => error!(
+                "Fail to notify {} about ticket {}: {:?}",
+                phone_number, ticket.id, err
+            ),
+        }
+    }
+}
+
+/// Returns whether the holder got the reminder.
+async fn remind(pass: &ExpiringPass) -> bool {
+    let message = format!(
+        "Your parking pass for {} at {} ends on {}, renew it to keep parking with your pass.",
+        pass.plate_number,
+        pass.area_name,
+        pass.ends_at.format("%d %b %Y %H:%M")
+    );
+
+    match send_message(&pass.phone_number, &message).await {
+        Ok(response) if response.status().is_success() => true,
+        Ok(response) => {
+            error!(
+                "Fail to remind {} about pass {}: {}",
+                pass.phone_number,
+                pass.id,
+                response.status()
+            );
+            false
+        }
+        Err(err) => {
+            error!(
+                "Fail to remind {} about pass {}: {:?}",
+                pass.phone_number, pass.id, err
+            );
+            false
+        }
+    }
+}
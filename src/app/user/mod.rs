@@ -1,14 +1,32 @@
+pub mod permit;
 pub mod router;
 
 use chrono::NaiveDateTime;
-use router::UserAggregatePayload;
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Postgres};
+use sqlx::{PgConnection, PgExecutor, Postgres, QueryBuilder};
 use uuid::Uuid;
 
-use crate::{error::aggregate::Result, types::count::SqlxCount};
+use crate::{
+    error::aggregate::{Error, Result},
+    query_filter::QueryFilter,
+    types::{
+        count::SqlxCount,
+        pagination::{push_page, Cursor, Sort},
+    },
+};
 
-#[derive(Debug, Serialize)]
+const PHONE_NUMBER_CONSTRAINT: &str = "user_phone_number_key";
+
+fn map_phone_number_violation(error: sqlx::Error) -> Error {
+    match &error {
+        sqlx::Error::Database(dbx) if dbx.constraint() == Some(PHONE_NUMBER_CONSTRAINT) => {
+            Error::Conflict("Phone number is already used by another user".to_string())
+        }
+        _ => Error::Sqlx(error),
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
 pub struct User {
     pub id: Uuid,
     pub phone_number: String,
@@ -30,6 +48,7 @@ pub enum Role {
     Easypark,
     ParkKeeper,
     ParkOwner,
+    Admin,
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd, sqlx::Type, Deserialize, Serialize)]
@@ -41,7 +60,7 @@ pub enum UserStatus {
 }
 
 impl User {
-    pub async fn save(self, pool: &Pool<Postgres>) -> Result<User> {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<User> {
         let user = sqlx::query_as!(
             User, 
             r#"insert into "user" values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) returning id, phone_number, name, nik, role as "role!: Role", status as "status!: UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id"#,  
@@ -57,164 +76,358 @@ impl User {
             self.parking_lot_id,
             self.owner_id
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
     }
     
-    pub async fn find_one(phone: String, pool: &Pool<Postgres>) -> Result<User> {
+    pub async fn find_one(phone: String, executor: impl PgExecutor<'_>) -> Result<User> {
         let user = sqlx::query_as!(
             User, 
-            r#"select id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id from "user" where phone_number = $1"#,
+            r#"select id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id from "user" where phone_number = $1 and deleted_at is null"#,
             phone
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
     }
     
-    pub async fn find_one_by_id(id: Uuid, pool: &Pool<Postgres>) -> Result<User> {
+    /// Locks the user row until the transaction ends, tickets and shifts of
+    /// a keeper wait on it through their foreign key.
+    pub async fn find_one_for_update(phone: String, executor: impl PgExecutor<'_>) -> Result<User> {
         let user = sqlx::query_as!(
             User, 
-            r#"select id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id  from "user" where id = $1"#,
+            r#"select id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id from "user" where phone_number = $1 and deleted_at is null for update"#,
+            phone
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(user)
+    }
+    
+    pub async fn find_one_by_id(id: Uuid, executor: impl PgExecutor<'_>) -> Result<User> {
+        let user = sqlx::query_as!(
+            User, 
+            r#"select id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id  from "user" where id = $1 and deleted_at is null"#,
             id
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
     }
     
-    pub async fn update_otp(self, otp: i32, pool: &Pool<Postgres>) -> Result<User> {
+    /// Also finds archived users, for showing who took part in an old ticket.
+    pub async fn find_one_by_id_with_archived(id: Uuid, executor: impl PgExecutor<'_>) -> Result<User> {
         let user = sqlx::query_as!(
             User, 
-            r#"update "user" set otp = $1 where phone_number = $2 returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id"#,
+            r#"select id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id  from "user" where id = $1"#,  
+            id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(user)
+    }
+
+    pub async fn update_otp(self, otp: i32, executor: impl PgExecutor<'_>) -> Result<User> {
+        let user = sqlx::query_as!(
+            User, 
+            r#"update "user" set otp = $1 where phone_number = $2 and deleted_at is null returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id"#,
             otp,
             self.phone_number
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
     }
 
-    pub async fn update_status(self, status: UserStatus, pool: &Pool<Postgres>) -> Result<User> {
+    pub async fn update_status(self, status: UserStatus, executor: impl PgExecutor<'_>) -> Result<User> {
         let user = sqlx::query_as!(
             User, 
-            r#"update "user" set status = $1 where phone_number = $2 returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id"#,
+            r#"update "user" set status = $1 where phone_number = $2 and deleted_at is null returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id"#,
             status as UserStatus,
             self.phone_number
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
     }
     
-    pub async fn invalidate_otp(self, pool: &Pool<Postgres>) -> Result<User> {
+    pub async fn invalidate_otp(self, executor: impl PgExecutor<'_>) -> Result<User> {
         let otp: Option<i32> = None;
         let user = sqlx::query_as!(
             User, 
-            r#"update "user" set otp = $1 where phone_number = $2 returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id"#,
+            r#"update "user" set otp = $1 where phone_number = $2 and deleted_at is null returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id"#,
             otp,
             self.phone_number
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
     }
     
-    pub async fn aggregate(pool: &Pool<Postgres>, payload: UserAggregatePayload) -> Result<Vec<User>> {
+    pub async fn aggregate(executor: impl PgExecutor<'_>, payload: UserAggregateQuery) -> Result<Vec<User>> {
+        let mut query = payload.select(
+            r#"
+                u.id,
+                u.phone_number,
+                u.name,
+                u.nik,
+                u.role,
+                u.status,
+                u.otp,
+                u.created_at,
+                u.updated_at,
+                u.parking_lot_id,
+                u.owner_id
+            "#,
+        );
+        push_page(&mut query, "u", payload.cursor, payload.sort, payload.take, payload.skip);
+
+        let user = query
+            .build_query_as::<User>()
+            .fetch_all(executor)
+            .await?;
+
+        Ok(user)
+    }
+    
+    pub async fn count(executor: impl PgExecutor<'_>, payload: UserAggregateQuery) -> Result<SqlxCount> {
+        let data = payload
+            .count()
+            .build_query_scalar::<i64>()
+            .fetch_one(executor)
+            .await?;
+
+        Ok(SqlxCount { data: Some(data) })
+    }
+    
+    /// Live users among `ids`, in no particular order. Missing ids are left out.
+    /// Locks the users until the transaction ends, in id order so two requests
+    /// over the same keepers cannot deadlock.
+    pub async fn find_many_for_update(ids: &[Uuid], executor: impl PgExecutor<'_>) -> Result<Vec<User>> {
         let user = sqlx::query_as!(
             User, 
             r#"
-                select u.id,
-                    u.phone_number,
-                    u.name,
-                    u.nik,
-                    u.role as "role!: Role",
-                    u.status as "status!:UserStatus",
-                    u.otp,
-                    u.created_at,
-                    u.updated_at,
-                    u.parking_lot_id,
-                    u.owner_id
-                from "user" u
-                left join "parking_lot" pl on u.parking_lot_id = pl.id
-                where ($3::Uuid is null or u.parking_lot_id = $3)
-                    and ($4::Uuid is null or u.owner_id = $4)
-                    and ($5::bool is null or ($5 = false and u.parking_lot_id is null) or ($5 = true and u.parking_lot_id is not null))
-                limit $1
-                offset $2
+                select id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id
+                from "user"
+                where id = any($1) and deleted_at is null
+                order by id
+                for update
             "#,
-            payload.take,
-            payload.skip,
-            payload.belong_to_parking_lot_id,
-            payload.owner_id,
-            payload.already_assigned            
+            ids
         )
-            .fetch_all(pool)
+            .fetch_all(executor)
             .await?;
 
         Ok(user)
     }
-    
-    pub async fn count(pool: &Pool<Postgres>, payload: UserAggregatePayload) -> Result<SqlxCount> {
-        let count = sqlx::query_as!(
-            SqlxCount, 
+
+    pub async fn update_parking_lot(parking_lot_id: Uuid, keeper_ids: &[Uuid], updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Vec<User>> {
+        let user = sqlx::query_as!(
+            User, 
             r#"
-                select count(*) as data from "user" as u
-                join "parking_lot" pl on u.parking_lot_id = pl.id
-                where ($1::Uuid is null or parking_lot_id = $1) and 
-                    ($2::Uuid is null or pl.owner_id = $2)
+                update "user"
+                set parking_lot_id = $1,
+                    updated_at = $3
+                where id = any($2) and deleted_at is null
+                returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id
             "#,
-            payload.belong_to_parking_lot_id,
-            payload.owner_id
+            parking_lot_id,
+            keeper_ids,
+            updated_at
         )
-            .fetch_one(pool)
+            .fetch_all(executor)
             .await?;
 
-        Ok(count)
+        Ok(user)
     }
-    
-    pub async fn update_parking_lot(parking_lot_id: Uuid, keeper_ids: Vec<Uuid>, pool: &Pool<Postgres>) -> Result<User> {
-        let count = sqlx::query_as!(
+
+    /// Takes `keeper_ids` off the lot, keepers assigned elsewhere are left alone.
+    pub async fn remove_parking_lot(parking_lot_id: Uuid, keeper_ids: &[Uuid], updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<Vec<User>> {
+        let user = sqlx::query_as!(
             User, 
             r#"
                 update "user"
-                set parking_lot_id = $1
-                where id in (SELECT unnest($2::Uuid[]))
+                set parking_lot_id = null,
+                    updated_at = $3
+                where parking_lot_id = $1 and id = any($2)
                 returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id
             "#,
             parking_lot_id,
-            keeper_ids as Vec<Uuid>
+            keeper_ids,
+            updated_at
         )
-            .fetch_one(pool)
+            .fetch_all(executor)
             .await?;
 
-        Ok(count)
+        Ok(user)
     }
 
-    pub async fn remove_parking_lot(parking_lot_id: Uuid, pool: &Pool<Postgres>) -> Result<User> {
-        let count = sqlx::query_as!(
+    /// Archives a keeper of `owner_id` and takes them off their lot, restoring
+    /// them later does not assign the lot again.
+    pub async fn archive(phone_number: String, owner_id: Uuid, deleted_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<User> {
+        let user = sqlx::query_as!(
             User, 
             r#"
                 update "user"
-                set parking_lot_id = null
-                where parking_lot_id = $1
+                set deleted_at = $3,
+                    parking_lot_id = null,
+                    updated_at = $3
+                where phone_number = $1 and owner_id = $2 and deleted_at is null
                 returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id
             "#,
-            parking_lot_id,
+            phone_number,
+            owner_id,
+            deleted_at
         )
-            .fetch_one(pool)
+            .fetch_optional(executor)
             .await?;
 
-        Ok(count)
+        user.ok_or(Error::NotFoundRejection("Keeper is not found".to_string()))
     }
 
+    /// The number may have been archived more than once, only the latest one
+    /// comes back and only while nobody else uses the number.
+    pub async fn restore(phone_number: String, owner_id: Uuid, updated_at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<User> {
+        let user = sqlx::query_as!(
+            User, 
+            r#"
+                update "user"
+                set deleted_at = null,
+                    updated_at = $3
+                where id = (
+                    select id from "user"
+                    where phone_number = $1 and owner_id = $2 and deleted_at is not null
+                    order by deleted_at desc
+                    limit 1
+                )
+                returning id, phone_number, name, nik, role as "role!: Role", status as "status!:UserStatus", otp, created_at, updated_at, parking_lot_id, owner_id
+            "#,
+            phone_number,
+            owner_id,
+            updated_at
+        )
+            .fetch_optional(executor)
+            .await
+            .map_err(map_phone_number_violation)?;
+
+        user.ok_or(Error::NotFoundRejection("Archived keeper is not found".to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UserAggregateQuery {
+    pub belong_to_parking_lot_id: Option<Uuid>,
+    pub owner_id: Option<Uuid>,
+    pub already_assigned: Option<bool>,
+    pub take: Option<i64>,
+    pub skip: Option<i64>,
+    pub cursor: Option<Cursor>,
+    pub sort: Sort,
+}
+
+impl QueryFilter for UserAggregateQuery {
+    const FROM: &'static str = r#"from "user" u"#;
+
+    fn push_filters(&self, builder: &mut QueryBuilder<'static, Postgres>) {
+        builder.push(" and u.deleted_at is null");
+        if let Some(parking_lot_id) = self.belong_to_parking_lot_id {
+            builder.push(" and u.parking_lot_id = ").push_bind(parking_lot_id);
+        }
+        if let Some(owner_id) = self.owner_id {
+            builder.push(" and u.owner_id = ").push_bind(owner_id);
+        }
+        match self.already_assigned {
+            Some(true) => {
+                builder.push(" and u.parking_lot_id is not null");
+            }
+            Some(false) => {
+                builder.push(" and u.parking_lot_id is null");
+            }
+            None => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserSearchQuery {
+    pub search: String,
+    pub owner_id: Option<Uuid>,
+    pub role: Option<Role>,
+    pub take: Option<i64>,
+    pub skip: Option<i64>,
+}
+
+impl QueryFilter for UserSearchQuery {
+    const FROM: &'static str = r#"from "user" u"#;
+
+    fn push_filters(&self, builder: &mut QueryBuilder<'static, Postgres>) {
+        builder
+            .push(" and u.deleted_at is null and (")
+            .push_bind(self.search.clone())
+            .push(" <% u.name or ")
+            .push_bind(self.search.clone())
+            .push(" <% u.phone_number)");
+        if let Some(owner_id) = self.owner_id {
+            builder.push(" and u.owner_id = ").push_bind(owner_id);
+        }
+        if let Some(role) = self.role.clone() {
+            builder.push(" and u.role = ").push_bind(role);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SearchedUser {
+    pub id: Uuid,
+    pub phone_number: String,
+    pub name: String,
+    pub role: Role,
+    pub parking_lot_id: Option<Uuid>,
+    pub owner_id: Option<Uuid>,
+    pub rank: f32,
+}
+
+impl SearchedUser {
+    /// Ranked by the best word similarity of name or phone number. Expects the
+    /// connection to run `tolerate_typos` first.
+    pub async fn search(payload: &UserSearchQuery, connection: &mut PgConnection) -> Result<(i64, Vec<SearchedUser>)> {
+        let total = payload
+            .count()
+            .build_query_scalar::<i64>()
+            .fetch_one(&mut *connection)
+            .await?;
+
+        let mut query = QueryBuilder::new(
+            "select u.id, u.phone_number, u.name, u.role, u.parking_lot_id, u.owner_id, greatest(word_similarity(",
+        );
+        query
+            .push_bind(payload.search.clone())
+            .push(", u.name), word_similarity(")
+            .push_bind(payload.search.clone())
+            .push(", u.phone_number)) as rank");
+        payload.push_from(&mut query);
+        query
+            .push(" order by rank desc, u.id limit ")
+            .push_bind(payload.take)
+            .push(" offset ")
+            .push_bind(payload.skip);
+
+        let user = query
+            .build_query_as::<SearchedUser>()
+            .fetch_all(&mut *connection)
+            .await?;
+
+        Ok((total, user))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -228,12 +441,11 @@ pub struct UpdateUser {
     pub otp: Option<i32>,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
-    pub parking_lot_id: Option<Uuid>,
     pub owner_id: Option<Uuid>,
 }
 
 impl UpdateUser {
-    pub async fn update(self, phone_number: String, pool: &Pool<Postgres>) -> Result<User> {
+    pub async fn update(self, phone_number: String, executor: impl PgExecutor<'_>) -> Result<User> {
         let user = sqlx::query_as!(
             User, 
             r#"
@@ -246,9 +458,8 @@ impl UpdateUser {
                         updated_at = coalesce($6, "user".updated_at),
                         status = coalesce($7, "user".status),
                         role = coalesce($8, "user".role),
-                        parking_lot_id = coalesce($9, "user".parking_lot_id),
-                        owner_id = $10
-                    where phone_number = $11
+                        owner_id = $9
+                    where phone_number = $10 and deleted_at is null
                     returning 
                         id, 
                         phone_number, 
@@ -270,11 +481,10 @@ impl UpdateUser {
             self.updated_at,
             self.status.unwrap_or(UserStatus::Default) as UserStatus,
             self.role.unwrap_or(Role::Default) as Role,
-            self.parking_lot_id,
             self.owner_id,
             phone_number
         )
-            .fetch_one(pool)
+            .fetch_one(executor)
             .await?;
 
         Ok(user)
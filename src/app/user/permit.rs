@@ -0,0 +1,58 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::error::aggregate::Result;
+
+/// A disability parking permit checked by an admin. Reserved spots trust this
+/// record, never a flag sent along with the ticket.
+#[derive(Debug, Serialize)]
+pub struct DisabledPermit {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub permit_number: String,
+    pub valid_until: NaiveDateTime,
+    pub verified_by: Option<Uuid>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl DisabledPermit {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<DisabledPermit> {
+        let permit = sqlx::query_as!(
+            DisabledPermit,
+            r#"
+                insert into "disabled_permit" (id, user_id, permit_number, valid_until, verified_by, created_at)
+                values ($1, $2, $3, $4, $5, $6)
+                returning id, user_id, permit_number, valid_until, verified_by, created_at
+            "#,
+            self.id,
+            self.user_id,
+            self.permit_number,
+            self.valid_until,
+            self.verified_by,
+            self.created_at
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(permit)
+    }
+
+    pub async fn is_valid(user_id: Uuid, at: NaiveDateTime, executor: impl PgExecutor<'_>) -> Result<bool> {
+        let valid = sqlx::query_scalar!(
+            r#"
+                select exists (
+                    select 1 from "disabled_permit"
+                    where user_id = $1 and valid_until >= $2
+                ) as "valid!"
+            "#,
+            user_id,
+            at
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(valid)
+    }
+}
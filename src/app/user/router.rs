@@ -4,25 +4,40 @@ use axum::{
     routing::{get, patch, post},
     Router,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Pool, Postgres};
 use tracing::debug;
 use uuid::Uuid;
 
 use crate::{
-    error::aggregate::Result,
-    extractor::{app_body::Body, app_json::AppSuccess},
+    app::{
+        keeper_shift::{KeeperShift, ShiftQuery, ShiftStatus},
+        parking_area::assignment::KeeperAssignment,
+        parking_history::ParkingHistory,
+    },
+    error::aggregate::{Error, Result},
+    extractor::{app_body::Body, app_json::AppSuccess, current_user::CurrentUser},
     middleware::base::print_request_body,
+    query_filter::tolerate_typos,
+    types::pagination::{next_cursor, resolve_page, Cursor, Sort},
+    unit_of_work::UnitOfWork,
 };
 
-use super::{Role, UpdateUser, User, UserStatus};
+use super::{
+    permit::DisabledPermit,
+    Role, SearchedUser, UpdateUser, User, UserAggregateQuery, UserSearchQuery, UserStatus,
+};
 
 pub fn build(pool: Pool<Postgres>) -> Router {
     let router = Router::new()
         .route("/", post(create).get(aggregate))
         .route("/aggregate", get(aggregate))
+        .route("/search", get(search))
         .route("/:phone_number", patch(update).get(get_by_phone_number))
+        .route("/:phone_number/archive", post(archive))
+        .route("/:phone_number/restore", post(restore))
+        .route("/:phone_number/disabled-permit", post(add_disabled_permit))
         .layer(middleware::from_fn(print_request_body))
         .with_state(pool);
 
@@ -37,6 +52,8 @@ async fn get_by_phone_number(
     Ok(AppSuccess(user))
 }
 
+/// Keepers join a lot through `/parking-lot/:id/keepers`, which also records
+/// the assignment, so users are created and updated without one.
 #[derive(Debug, Serialize, Deserialize)]
 struct CreateUserPayload {
     phone_number: String,
@@ -45,7 +62,6 @@ struct CreateUserPayload {
     role: Role,
     status: UserStatus,
     otp: Option<i32>,
-    belong_to_parking_lot_id: Option<Uuid>,
     owner_id: Option<Uuid>,
 }
 
@@ -61,7 +77,7 @@ impl CreateUserPayload {
             otp: None,
             created_at: Some(Utc::now().naive_utc()),
             updated_at: None,
-            parking_lot_id: self.belong_to_parking_lot_id,
+            parking_lot_id: None,
             owner_id: self.owner_id,
         }
     }
@@ -71,6 +87,12 @@ async fn create(
     State(pool): State<PgPool>,
     Body(payload): Body<CreateUserPayload>,
 ) -> Result<AppSuccess<User>> {
+    if payload.role == Role::Admin {
+        return Err(Error::BadRequest(
+            "Admin users cannot be created through the API".to_string(),
+        ));
+    }
+
     let user = payload.into_user();
     let user = user.save(&pool).await?;
     Ok(AppSuccess(user))
@@ -84,7 +106,6 @@ struct UpdateUserPayload {
     role: Option<Role>,
     status: Option<UserStatus>,
     otp: Option<i32>,
-    belong_to_parking_lot_id: Option<Uuid>,
     owner_id: Option<Uuid>,
 }
 
@@ -100,7 +121,6 @@ impl UpdateUserPayload {
             otp: self.otp,
             created_at: None,
             updated_at: Some(Utc::now().naive_utc()),
-            parking_lot_id: self.belong_to_parking_lot_id,
             owner_id: self.owner_id,
         }
     }
@@ -111,18 +131,42 @@ async fn update(
     Path(phone_number): Path<String>,
     Body(payload): Body<UpdateUserPayload>,
 ) -> Result<AppSuccess<User>> {
+    if payload.role == Some(Role::Admin) {
+        return Err(Error::BadRequest(
+            "Admin role cannot be given through the API".to_string(),
+        ));
+    }
+
     let user = payload.into_update_user();
     let user = user.update(phone_number, &pool).await?;
     Ok(AppSuccess(user))
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserAggregatePayload {
     pub belong_to_parking_lot_id: Option<Uuid>,
     pub owner_id: Option<Uuid>,
     pub already_assigned: Option<bool>,
     pub take: Option<i64>,
     pub skip: Option<i64>,
+    pub cursor: Option<String>,
+    pub sort: Option<Sort>,
+}
+
+impl UserAggregatePayload {
+    fn into_user_aggregate_query(self) -> Result<UserAggregateQuery> {
+        let (cursor, skip) = resolve_page(self.cursor.as_deref(), self.skip)?;
+
+        Ok(UserAggregateQuery {
+            belong_to_parking_lot_id: self.belong_to_parking_lot_id,
+            owner_id: self.owner_id,
+            already_assigned: self.already_assigned,
+            take: self.take,
+            skip,
+            cursor,
+            sort: self.sort.unwrap_or_default(),
+        })
+    }
 }
 
 #[derive(Serialize)]
@@ -134,6 +178,7 @@ struct Aggregate {
 #[derive(Serialize)]
 struct MetaAggregate {
     total_data: i64,
+    next_cursor: Option<String>,
     query: UserAggregatePayload,
 }
 
@@ -142,13 +187,211 @@ async fn aggregate(
     Query(payload): Query<UserAggregatePayload>,
 ) -> Result<AppSuccess<Aggregate>> {
     debug!("{:#?}", payload);
-    let count = User::count(&pool, payload).await?;
-    let user = User::aggregate(&pool, payload).await?;
+    let query = payload.clone().into_user_aggregate_query()?;
+    let count = User::count(&pool, query).await?;
+    let mut user = User::aggregate(
+        &pool,
+        UserAggregateQuery {
+            take: query.take.map(|take| take + 1),
+            ..query
+        },
+    )
+    .await?;
+    let next_cursor = next_cursor(&mut user, query.take, |user| {
+        user.created_at.map(|created_at| Cursor {
+            created_at,
+            id: user.id,
+        })
+    });
+
     Ok(AppSuccess(Aggregate {
         meta: MetaAggregate {
             total_data: count.data.unwrap_or(0),
+            next_cursor,
+            query: payload,
+        },
+        user,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserSearchPayload {
+    pub q: String,
+    pub role: Option<Role>,
+    pub take: Option<i64>,
+    pub skip: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct SearchAggregate {
+    meta: SearchMeta,
+    user: Vec<SearchedUser>,
+}
+
+#[derive(Serialize)]
+struct SearchMeta {
+    total_data: i64,
+    query: UserSearchPayload,
+}
+
+/// Owners search their own keepers by name or phone number, other roles have
+/// no users to look for.
+async fn search(
+    State(pool): State<PgPool>,
+    current_user: CurrentUser,
+    Query(payload): Query<UserSearchPayload>,
+) -> Result<AppSuccess<SearchAggregate>> {
+    let search = payload.q.trim().to_string();
+    if search.chars().count() < 2 {
+        return Err(Error::BadRequest(
+            "Search must be at least 2 characters".to_string(),
+        ));
+    }
+
+    let caller = User::find_one(current_user.sub, &pool).await?;
+    if caller.role != Role::ParkOwner {
+        return Err(Error::Unauthorize(
+            "Only ParkOwner can search users".to_string(),
+        ));
+    }
+
+    let query = UserSearchQuery {
+        search,
+        owner_id: Some(caller.id),
+        role: payload.role.clone(),
+        take: Some(payload.take.unwrap_or(20).clamp(1, 100)),
+        skip: payload.skip,
+    };
+
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    tolerate_typos(uow.connection()).await?;
+    let (total_data, user) = SearchedUser::search(&query, uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(SearchAggregate {
+        meta: SearchMeta {
+            total_data,
             query: payload,
         },
         user,
     }))
 }
+
+#[derive(Deserialize)]
+struct ArchivePayload {
+    owner_id: Uuid,
+}
+
+/// Owners archive their own keepers, once the keeper has no ticket left to
+/// check out and is off shift.
+async fn archive(
+    State(pool): State<PgPool>,
+    Path(phone_number): Path<String>,
+    Body(payload): Body<ArchivePayload>,
+) -> Result<AppSuccess<User>> {
+    let owner = User::find_one_by_id(payload.owner_id, &pool).await?;
+    if owner.role != Role::ParkOwner {
+        return Err(Error::BadRequest(
+            "Provided owner id is not having ParkOwner role".to_string(),
+        ));
+    }
+
+    let now = Utc::now().naive_utc();
+    let mut uow = UnitOfWork::begin(&pool).await?;
+    let keeper = User::find_one_for_update(phone_number, uow.connection()).await?;
+    if keeper.role != Role::ParkKeeper || keeper.owner_id != Some(owner.id) {
+        return Err(Error::BadRequest(
+            "Only a keeper of the provided owner id can be archived".to_string(),
+        ));
+    }
+
+    if ParkingHistory::count_active(None, Some(keeper.id), uow.connection()).await? > 0 {
+        return Err(Error::Conflict("Keeper still has active tickets".to_string()));
+    }
+
+    let open_shift = KeeperShift::aggregate(
+        ShiftQuery {
+            owner_id: Some(owner.id),
+            keeper_id: Some(keeper.id),
+            parking_lot_id: None,
+            shift_status: Some(ShiftStatus::Open),
+        },
+        uow.connection(),
+    )
+    .await?;
+    if !open_shift.is_empty() {
+        return Err(Error::Conflict("Keeper is still on shift".to_string()));
+    }
+
+    KeeperAssignment::end(&[keeper.id], now, uow.connection()).await?;
+    let keeper = User::archive(keeper.phone_number, owner.id, now, uow.connection()).await?;
+    uow.commit().await?;
+
+    Ok(AppSuccess(keeper))
+}
+
+async fn restore(
+    State(pool): State<PgPool>,
+    Path(phone_number): Path<String>,
+    Body(payload): Body<ArchivePayload>,
+) -> Result<AppSuccess<User>> {
+    let owner = User::find_one_by_id(payload.owner_id, &pool).await?;
+    if owner.role != Role::ParkOwner {
+        return Err(Error::BadRequest(
+            "Provided owner id is not having ParkOwner role".to_string(),
+        ));
+    }
+
+    let keeper = User::restore(phone_number, owner.id, Utc::now().naive_utc(), &pool).await?;
+
+    Ok(AppSuccess(keeper))
+}
+
+#[derive(Deserialize)]
+struct DisabledPermitPayload {
+    permit_number: String,
+    valid_until: DateTime<Utc>,
+}
+
+/// Records a permit an admin has seen, the driver can then be given reserved
+/// spots until it runs out.
+async fn add_disabled_permit(
+    State(pool): State<PgPool>,
+    current_user: CurrentUser,
+    Path(phone_number): Path<String>,
+    Body(payload): Body<DisabledPermitPayload>,
+) -> Result<AppSuccess<DisabledPermit>> {
+    let admin = User::find_one(current_user.sub, &pool).await?;
+    if admin.role != Role::Admin {
+        return Err(Error::Unauthorize(
+            "Only an admin can verify disabled permits".to_string(),
+        ));
+    }
+
+    let easypark = User::find_one(phone_number, &pool).await?;
+    if easypark.role != Role::Easypark {
+        return Err(Error::BadRequest(
+            "Only an easypark can hold a disabled permit".to_string(),
+        ));
+    }
+    if payload.permit_number.trim().is_empty() {
+        return Err(Error::BadRequest("permit_number is required".to_string()));
+    }
+
+    let now = Utc::now().naive_utc();
+    if payload.valid_until.naive_utc() <= now {
+        return Err(Error::BadRequest("Permit has already expired".to_string()));
+    }
+
+    let permit = DisabledPermit {
+        id: Uuid::new_v4(),
+        user_id: easypark.id,
+        permit_number: payload.permit_number.trim().to_string(),
+        valid_until: payload.valid_until.naive_utc(),
+        verified_by: Some(admin.id),
+        created_at: Some(now),
+    };
+    let permit = permit.save(&pool).await?;
+
+    Ok(AppSuccess(permit))
+}
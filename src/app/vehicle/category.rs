@@ -0,0 +1,146 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::{
+    app::parking_history::VehicleType,
+    error::aggregate::{Error, Result},
+};
+
+use super::Vehicle;
+
+/// A kind of vehicle lots can price, e.g. `truck` or `bicycle`. Reports still
+/// group by the coarse `vehicle_type` of the category.
+#[derive(Debug, Serialize)]
+pub struct VehicleCategory {
+    pub id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub vehicle_type: VehicleType,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+/// Lowercase letters, digits and underscores, e.g. `electric_bus`.
+pub fn normalize_category_code(raw: &str) -> Option<String> {
+    let code = raw.trim().to_ascii_lowercase();
+    if code.is_empty()
+        || code.len() > 32
+        || !code.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return None;
+    }
+
+    Some(code)
+}
+
+impl VehicleCategory {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<VehicleCategory> {
+        let category = sqlx::query_as!(
+            VehicleCategory,
+            r#"
+                insert into "vehicle_category" (id, code, name, vehicle_type, created_at)
+                values ($1, $2, $3, $4, $5)
+                returning id, code, name, vehicle_type as "vehicle_type!: VehicleType", created_at
+            "#,
+            self.id,
+            self.code,
+            self.name,
+            self.vehicle_type as VehicleType,
+            self.created_at
+        )
+            .fetch_one(executor)
+            .await
+            .map_err(|err| match &err {
+                sqlx::Error::Database(dbx) if dbx.is_unique_violation() => {
+                    Error::Conflict("Vehicle category already exists".to_string())
+                }
+                _ => Error::from(err),
+            })?;
+
+        Ok(category)
+    }
+
+    pub async fn find_all(executor: impl PgExecutor<'_>) -> Result<Vec<VehicleCategory>> {
+        let category = sqlx::query_as!(
+            VehicleCategory,
+            r#"
+                select id, code, name, vehicle_type as "vehicle_type!: VehicleType", created_at
+                from "vehicle_category"
+                order by name
+            "#
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(category)
+    }
+
+    pub async fn find_one(id: Uuid, executor: impl PgExecutor<'_>) -> Result<VehicleCategory> {
+        let category = sqlx::query_as!(
+            VehicleCategory,
+            r#"
+                select id, code, name, vehicle_type as "vehicle_type!: VehicleType", created_at
+                from "vehicle_category"
+                where id = $1
+            "#,
+            id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(category)
+    }
+
+    /// Returned in the order of `codes`, every code has to exist.
+    pub async fn find_by_codes(codes: &[String], executor: impl PgExecutor<'_>) -> Result<Vec<VehicleCategory>> {
+        let category = sqlx::query_as!(
+            VehicleCategory,
+            r#"
+                select c.id, c.code, c.name, c.vehicle_type as "vehicle_type!: VehicleType", c.created_at
+                from unnest($1::text[]) with ordinality as requested(code, position)
+                join "vehicle_category" c on c.code = requested.code
+                order by requested.position
+            "#,
+            codes
+        )
+            .fetch_all(executor)
+            .await?;
+
+        if let Some(code) = codes
+            .iter()
+            .find(|code| !category.iter().any(|category| &category.code == *code))
+        {
+            return Err(Error::NotFoundRejection(format!(
+                "Vehicle category {} is not found",
+                code
+            )));
+        }
+
+        Ok(category)
+    }
+
+    pub async fn find_by_code(code: &str, executor: impl PgExecutor<'_>) -> Result<VehicleCategory> {
+        let mut category = Self::find_by_codes(&[code.to_string()], executor).await?;
+        Ok(category.remove(0))
+    }
+
+    pub async fn find_by_vehicle(vehicle: &Vehicle, executor: impl PgExecutor<'_>) -> Result<VehicleCategory> {
+        match vehicle.vehicle_category_id {
+            Some(id) => Self::find_one(id, executor).await,
+            None => Self::find_by_vehicle_type(&vehicle.vehicle_type, executor).await,
+        }
+    }
+
+    /// The category of a vehicle registered before categories existed, only
+    /// cars and motorcycles have one.
+    pub async fn find_by_vehicle_type(vehicle_type: &VehicleType, executor: impl PgExecutor<'_>) -> Result<VehicleCategory> {
+        match vehicle_type {
+            VehicleType::Default => Err(Error::BadRequest(
+                "Vehicle type is required".to_string(),
+            )),
+            VehicleType::Car => Self::find_by_code("car", executor).await,
+            VehicleType::Motor => Self::find_by_code("motor", executor).await,
+        }
+    }
+}
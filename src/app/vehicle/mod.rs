@@ -0,0 +1,160 @@
+pub mod category;
+pub mod router;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::{app::parking_history::VehicleType, error::aggregate::Result};
+
+#[derive(Debug, Serialize)]
+pub struct Vehicle {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub plate_number: String,
+    pub vehicle_type: VehicleType,
+    pub vehicle_category_id: Option<Uuid>,
+    pub color: String,
+    pub image_url: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+/// Normalizes an Indonesian license plate (`b1234abc`, `B-1234-ABC`, `b 1234 abc`)
+/// into the canonical `B 1234 ABC` form: a 1-2 letter region code, a 1-4 digit
+/// number and an optional 1-3 letter suffix. Returns `None` when the input does
+/// not follow that format.
+pub fn normalize_plate_number(raw: &str) -> Option<String> {
+    if !raw
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '.')
+    {
+        return None;
+    }
+
+    let compact: String = raw
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    let region_len = compact.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+    let (region, rest) = compact.split_at(region_len);
+    let number_len = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    let (number, suffix) = rest.split_at(number_len);
+
+    if !(1..=2).contains(&region.len())
+        || !(1..=4).contains(&number.len())
+        || number.starts_with('0')
+        || suffix.len() > 3
+        || !suffix.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+
+    if suffix.is_empty() {
+        Some(format!("{} {}", region, number))
+    } else {
+        Some(format!("{} {} {}", region, number, suffix))
+    }
+}
+
+impl Vehicle {
+    pub async fn save(self, executor: impl PgExecutor<'_>) -> Result<Vehicle> {
+        let vehicle = sqlx::query_as!(
+            Vehicle,
+            r#"
+                insert into "vehicle" (id, user_id, plate_number, vehicle_type, vehicle_category_id, color, image_url, created_at, updated_at)
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                returning id, user_id, plate_number, vehicle_type as "vehicle_type!: VehicleType", vehicle_category_id, color, image_url, created_at, updated_at
+            "#,
+            self.id,
+            self.user_id,
+            self.plate_number,
+            self.vehicle_type as VehicleType,
+            self.vehicle_category_id,
+            self.color,
+            self.image_url,
+            self.created_at,
+            self.updated_at
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(vehicle)
+    }
+
+    pub async fn find_one(id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vehicle> {
+        let vehicle = sqlx::query_as!(
+            Vehicle,
+            r#"
+                select id, user_id, plate_number, vehicle_type as "vehicle_type!: VehicleType", vehicle_category_id, color, image_url, created_at, updated_at
+                from "vehicle"
+                where id = $1
+            "#,
+            id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(vehicle)
+    }
+
+    pub async fn find_by_user(user_id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vec<Vehicle>> {
+        let vehicle = sqlx::query_as!(
+            Vehicle,
+            r#"
+                select id, user_id, plate_number, vehicle_type as "vehicle_type!: VehicleType", vehicle_category_id, color, image_url, created_at, updated_at
+                from "vehicle"
+                where user_id = $1
+                order by created_at
+            "#,
+            user_id
+        )
+            .fetch_all(executor)
+            .await?;
+
+        Ok(vehicle)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateVehicle {
+    pub plate_number: Option<String>,
+    pub vehicle_type: Option<VehicleType>,
+    pub vehicle_category_id: Option<Uuid>,
+    pub color: Option<String>,
+    pub image_url: Option<String>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl UpdateVehicle {
+    pub async fn update(self, id: Uuid, executor: impl PgExecutor<'_>) -> Result<Vehicle> {
+        let vehicle = sqlx::query_as!(
+            Vehicle,
+            r#"
+                update "vehicle"
+                    set plate_number = coalesce($1, "vehicle".plate_number),
+                        vehicle_type = coalesce($2, "vehicle".vehicle_type),
+                        color = coalesce($3, "vehicle".color),
+                        image_url = coalesce($4, "vehicle".image_url),
+                        updated_at = coalesce($5, "vehicle".updated_at),
+                        vehicle_category_id = coalesce($6, "vehicle".vehicle_category_id)
+                    where id = $7
+                    returning id, user_id, plate_number, vehicle_type as "vehicle_type!: VehicleType", vehicle_category_id, color, image_url, created_at, updated_at
+            "#,
+            self.plate_number,
+            self.vehicle_type as Option<VehicleType>,
+            self.color,
+            self.image_url,
+            self.updated_at,
+            self.vehicle_category_id,
+            id
+        )
+            .fetch_one(executor)
+            .await?;
+
+        Ok(vehicle)
+    }
+}
@@ -0,0 +1,235 @@
+use std::path::Path as StdPath;
+
+use axum::{
+    extract::{Path, State},
+    middleware,
+    routing::{get, patch, post},
+    Router,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        parking_history::VehicleType,
+        user::{Role, User},
+    },
+    error::aggregate::{Error, Result},
+    extractor::{app_body::Body, app_json::AppSuccess, current_user::CurrentUser},
+    middleware::base::print_request_body,
+};
+
+use super::{
+    category::{normalize_category_code, VehicleCategory},
+    normalize_plate_number, UpdateVehicle, Vehicle,
+};
+
+pub fn build(pool: Pool<Postgres>) -> Router {
+    let router = Router::new()
+        .route("/", post(create))
+        .route("/:id", patch(update).get(detail))
+        .route("/user/:id", get(get_by_user))
+        .route("/category", get(categories).post(create_category))
+        .layer(middleware::from_fn(print_request_body))
+        .with_state(pool);
+
+    Router::new().nest("/vehicle", router)
+}
+
+fn validate_plate_number(plate_number: &str) -> Result<String> {
+    normalize_plate_number(plate_number)
+        .ok_or_else(|| Error::BadRequest("Plate number is not valid".to_string()))
+}
+
+fn validate_file_name(file_name: &str) -> Result<()> {
+    let dir = format!("./public/files/{}", file_name);
+    let path = StdPath::new(&dir);
+    if !path.exists() {
+        return Err(Error::BadRequest("Image not found".to_string()));
+    }
+    Ok(())
+}
+
+/// The category of a vehicle, named by its code or, for clients that only
+/// know car and motor, taken from the vehicle type.
+async fn resolve_category(
+    vehicle_category: Option<&str>,
+    vehicle_type: Option<&VehicleType>,
+    pool: &PgPool,
+) -> Result<Option<VehicleCategory>> {
+    match (vehicle_category, vehicle_type) {
+        (Some(code), _) => Ok(Some(VehicleCategory::find_by_code(code, pool).await?)),
+        (None, Some(vehicle_type)) => Ok(Some(
+            VehicleCategory::find_by_vehicle_type(vehicle_type, pool).await?,
+        )),
+        (None, None) => Ok(None),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CreateVehiclePayload {
+    user_id: Uuid,
+    plate_number: String,
+    vehicle_type: Option<VehicleType>,
+    vehicle_category: Option<String>,
+    color: String,
+    file_name: Option<String>,
+}
+
+impl CreateVehiclePayload {
+    fn into_vehicle(self, plate_number: String, category: VehicleCategory) -> Vehicle {
+        Vehicle {
+            id: Uuid::new_v4(),
+            user_id: self.user_id,
+            plate_number,
+            vehicle_type: category.vehicle_type,
+            vehicle_category_id: Some(category.id),
+            color: self.color,
+            image_url: self.file_name,
+            created_at: Some(Utc::now().naive_utc()),
+            updated_at: None,
+        }
+    }
+}
+
+async fn create(
+    State(pool): State<PgPool>,
+    Body(payload): Body<CreateVehiclePayload>,
+) -> Result<AppSuccess<Vehicle>> {
+    let user = User::find_one_by_id(payload.user_id, &pool).await?;
+    if user.role != Role::Easypark {
+        return Err(Error::BadRequest(
+            "Related user is not having Easypark role".to_string(),
+        ));
+    }
+
+    let category = resolve_category(
+        payload.vehicle_category.as_deref(),
+        payload.vehicle_type.as_ref(),
+        &pool,
+    )
+    .await?
+    .ok_or_else(|| Error::BadRequest("Vehicle type is required".to_string()))?;
+
+    if let Some(file_name) = &payload.file_name {
+        validate_file_name(file_name)?;
+    }
+
+    let plate_number = validate_plate_number(&payload.plate_number)?;
+    let vehicle = payload.into_vehicle(plate_number, category);
+    let vehicle = vehicle.save(&pool).await?;
+    Ok(AppSuccess(vehicle))
+}
+
+#[derive(Serialize, Deserialize)]
+struct UpdateVehiclePayload {
+    plate_number: Option<String>,
+    vehicle_type: Option<VehicleType>,
+    vehicle_category: Option<String>,
+    color: Option<String>,
+    file_name: Option<String>,
+}
+
+impl UpdateVehiclePayload {
+    fn into_update_vehicle(self, plate_number: Option<String>, category: Option<VehicleCategory>) -> UpdateVehicle {
+        UpdateVehicle {
+            plate_number,
+            vehicle_type: category.as_ref().map(|category| category.vehicle_type.clone()),
+            vehicle_category_id: category.map(|category| category.id),
+            color: self.color,
+            image_url: self.file_name,
+            updated_at: Some(Utc::now().naive_utc()),
+        }
+    }
+}
+
+async fn update(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Body(payload): Body<UpdateVehiclePayload>,
+) -> Result<AppSuccess<Vehicle>> {
+    let category = resolve_category(
+        payload.vehicle_category.as_deref(),
+        payload.vehicle_type.as_ref(),
+        &pool,
+    )
+    .await?;
+
+    if let Some(file_name) = &payload.file_name {
+        validate_file_name(file_name)?;
+    }
+
+    let plate_number = match &payload.plate_number {
+        Some(plate_number) => Some(validate_plate_number(plate_number)?),
+        None => None,
+    };
+
+    let vehicle = payload.into_update_vehicle(plate_number, category);
+    let vehicle = vehicle.update(id, &pool).await?;
+    Ok(AppSuccess(vehicle))
+}
+
+async fn detail(State(pool): State<PgPool>, Path(id): Path<Uuid>) -> Result<AppSuccess<Vehicle>> {
+    let vehicle = Vehicle::find_one(id, &pool).await?;
+    Ok(AppSuccess(vehicle))
+}
+
+async fn get_by_user(
+    State(pool): State<PgPool>,
+    Path(user_id): Path<Uuid>,
+) -> Result<AppSuccess<Vec<Vehicle>>> {
+    let vehicle = Vehicle::find_by_user(user_id, &pool).await?;
+    Ok(AppSuccess(vehicle))
+}
+
+async fn categories(State(pool): State<PgPool>) -> Result<AppSuccess<Vec<VehicleCategory>>> {
+    let category = VehicleCategory::find_all(&pool).await?;
+    Ok(AppSuccess(category))
+}
+
+#[derive(Deserialize)]
+struct CreateCategoryPayload {
+    code: String,
+    name: String,
+    vehicle_type: VehicleType,
+}
+
+/// Adds a category every lot can then price, e.g. `truck` or `bicycle`.
+/// Categories are shared by all owners, so only an admin adds them.
+async fn create_category(
+    State(pool): State<PgPool>,
+    current_user: CurrentUser,
+    Body(payload): Body<CreateCategoryPayload>,
+) -> Result<AppSuccess<VehicleCategory>> {
+    let caller = User::find_one(current_user.sub, &pool).await?;
+    if caller.role != Role::Admin {
+        return Err(Error::Unauthorize(
+            "Only an admin can add vehicle categories".to_string(),
+        ));
+    }
+
+    let code = normalize_category_code(&payload.code).ok_or_else(|| {
+        Error::BadRequest(
+            "Category code may only have lowercase letters, digits and underscores".to_string(),
+        )
+    })?;
+    if payload.name.trim().is_empty() {
+        return Err(Error::BadRequest("Category name is required".to_string()));
+    }
+    if payload.vehicle_type == VehicleType::Default {
+        return Err(Error::BadRequest("Vehicle type is required".to_string()));
+    }
+
+    let category = VehicleCategory {
+        id: Uuid::new_v4(),
+        code,
+        name: payload.name.trim().to_string(),
+        vehicle_type: payload.vehicle_type,
+        created_at: Some(Utc::now().naive_utc()),
+    };
+    let category = category.save(&pool).await?;
+
+    Ok(AppSuccess(category))
+}
@@ -1 +1,36 @@
-pub mod router;
\ No newline at end of file
+pub mod router;
+
+use std::collections::HashMap;
+
+use reqwest::{Client, Response};
+
+use crate::error::aggregate::{Error, Result};
+
+pub async fn send_message(phone_number: &str, body: &str) -> Result<Response> {
+    let account_sid = std::env::var("TWILIO_ACCOUNT_SID")
+        .map_err(|_| Error::InternalServerError("TWILIO credential must be set".to_string()))?;
+    let auth_token = std::env::var("TWILIO_AUTH_TOKEN")
+        .map_err(|_| Error::InternalServerError("TWILIO credential must be set".to_string()))?;
+
+    let url = format!(
+        "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
+        account_sid
+    );
+
+    let to_number = format!("whatsapp:+{}", phone_number);
+
+    let mut params = HashMap::new();
+    params.insert("To", to_number.as_str());
+    params.insert("From", "whatsapp:+14155238886");
+    params.insert("Body", body);
+
+    let client = Client::new();
+    let response = client
+        .post(&url)
+        .basic_auth(account_sid, Some(auth_token))
+        .form(&params)
+        .send()
+        .await?;
+
+    Ok(response)
+}
@@ -1,9 +1,6 @@
-use std::collections::HashMap;
-
 use axum::{extract::State, middleware, routing::post, Router};
 use chrono::{DateTime, Datelike, Duration, Utc};
 use rand::{rngs::OsRng, Rng};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Pool, Postgres};
 
@@ -14,6 +11,8 @@ use crate::{
     middleware::base::print_request_body,
 };
 
+use super::send_message;
+
 pub fn build(pool: Pool<Postgres>) -> Router {
     let router = Router::new()
         .route("/send", post(send))
@@ -44,29 +43,8 @@ async fn send(
     let mut rng = OsRng;
     let otp: i32 = rng.gen_range(100000..1000000);
 
-    let account_sid = std::env::var("TWILIO_ACCOUNT_SID").expect("MIDTRANS credential must be set");
-    let auth_token = std::env::var("TWILIO_AUTH_TOKEN").expect("MIDTRANS credential must be set");
-
-    let url = format!(
-        "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
-        account_sid
-    );
-
-    let to_number = format!("whatsapp:+{}", user.phone_number);
     let to_body = format!("Your OTP: {}", otp);
-
-    let mut params = HashMap::new();
-    params.insert("To", to_number.as_str());
-    params.insert("From", "whatsapp:+14155238886");
-    params.insert("Body", to_body.as_str());
-
-    let client = Client::new();
-    let response = client
-        .post(&url)
-        .basic_auth(account_sid, Some(auth_token))
-        .form(&params)
-        .send()
-        .await?;
+    let response = send_message(&user.phone_number, &to_body).await?;
 
     if response.status().is_success() {
         println!("Message sent successfully!");
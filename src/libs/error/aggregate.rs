@@ -13,6 +13,7 @@ pub enum Error {
     BadRequest(String),
     NotFoundRejection(String),
     Unauthorize(String),
+    Conflict(String),
     InternalServerError(String),
     JsonRejection(JsonRejection),
     Sqlx(sqlx::Error),
@@ -27,6 +28,7 @@ impl IntoResponse for Error {
             Error::InternalServerError(message) => (StatusCode::INTERNAL_SERVER_ERROR, message),
             Error::NotFoundRejection(message) => (StatusCode::NOT_FOUND, message),
             Error::Unauthorize(message) => (StatusCode::UNAUTHORIZED, message),
+            Error::Conflict(message) => (StatusCode::CONFLICT, message),
             Error::JsonRejection(rejection) => (rejection.status(), rejection.body_text()),
             Error::Sqlx(err) => {
                 let (status, message) = match err {
@@ -5,4 +5,6 @@ pub mod trace;
 pub mod types;
 pub mod error;
 pub mod jwt;
+pub mod query_filter;
+pub mod unit_of_work;
 
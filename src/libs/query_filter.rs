@@ -0,0 +1,48 @@
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+
+use crate::error::aggregate::Result;
+
+/// The `where` clause of a list endpoint, written once and shared by the query
+/// that counts the rows and the query that returns a page of them.
+///
+/// Implementors append ` and <predicate>` for every filter that is set; the
+/// clause starts with `where true` so the order of the filters never matters.
+pub trait QueryFilter {
+    /// Tables and joins every query of this list reads from.
+    const FROM: &'static str;
+
+    fn push_filters(&self, builder: &mut QueryBuilder<'static, Postgres>);
+
+    /// Appends ` <FROM> where <filters>`, for selects whose columns need binds.
+    fn push_from(&self, builder: &mut QueryBuilder<'static, Postgres>) {
+        builder.push(" ").push(Self::FROM).push(" where true");
+        self.push_filters(builder);
+    }
+
+    /// `select <columns> <FROM> where <filters>`, ready for ordering and paging.
+    fn select(&self, columns: &str) -> QueryBuilder<'static, Postgres> {
+        let mut builder = QueryBuilder::new("select ");
+        builder.push(columns);
+        self.push_from(&mut builder);
+        builder
+    }
+
+    fn count(&self) -> QueryBuilder<'static, Postgres> {
+        self.select("count(*)")
+    }
+}
+
+/// How loosely `<%` matches in the rest of the transaction. The default of 0.6
+/// rejects most single-letter typos in short names.
+pub const SEARCH_SIMILARITY_THRESHOLD: &str = "0.3";
+
+pub async fn tolerate_typos(connection: &mut PgConnection) -> Result<()> {
+    sqlx::query!(
+        "select set_config('pg_trgm.word_similarity_threshold', $1, true)",
+        SEARCH_SIMILARITY_THRESHOLD
+    )
+    .fetch_one(connection)
+    .await?;
+
+    Ok(())
+}
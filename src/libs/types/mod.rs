@@ -1,3 +1,4 @@
 pub mod date;
 pub mod timestampz;
-pub mod count;
\ No newline at end of file
+pub mod count;
+pub mod pagination;
\ No newline at end of file
@@ -0,0 +1,107 @@
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::error::aggregate::{Error, Result};
+
+/// Keyset position of the last row of a page. Clients only ever see it as an
+/// opaque url-safe string and hand it back unchanged to get the next page.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Cursor {
+    pub created_at: NaiveDateTime,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        BASE64_URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(raw: &str) -> Result<Cursor> {
+        BASE64_URL_SAFE_NO_PAD
+            .decode(raw)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or(Error::BadRequest("Invalid cursor".to_string()))
+    }
+}
+
+/// Accepted values of the `sort` query parameter. Every list is ordered by
+/// `(created_at, id)` so rows with the same timestamp still have a stable order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Sort {
+    #[serde(rename = "created_at")]
+    CreatedAtAsc,
+    #[default]
+    #[serde(rename = "-created_at")]
+    CreatedAtDesc,
+}
+
+impl Sort {
+    pub fn is_descending(&self) -> bool {
+        *self == Sort::CreatedAtDesc
+    }
+}
+
+/// Resolves `cursor` and `skip` into what the list queries expect. A cursor
+/// always wins, `skip` is only the offset fallback for clients without one.
+pub fn resolve_page(cursor: Option<&str>, skip: Option<i64>) -> Result<(Option<Cursor>, Option<i64>)> {
+    match cursor {
+        Some(_) if skip.is_some() => Err(Error::BadRequest(
+            "cursor and skip cannot be used together".to_string(),
+        )),
+        Some(raw) => Ok((Some(Cursor::decode(raw)?), None)),
+        None => Ok((None, skip)),
+    }
+}
+
+/// Lists are queried with one extra row (`take + 1`); when it comes back there is
+/// a next page. Drops that row and returns the cursor of the last kept one.
+pub fn next_cursor<T>(
+    rows: &mut Vec<T>,
+    take: Option<i64>,
+    cursor_of: impl Fn(&T) -> Option<Cursor>,
+) -> Option<String> {
+    let take = usize::try_from(take?).ok()?;
+    if rows.len() <= take {
+        return None;
+    }
+
+    rows.truncate(take);
+    rows.last().and_then(cursor_of).map(|cursor| cursor.encode())
+}
+
+/// Appends the keyset condition, `order by` and `limit`/`offset` of a page to a
+/// filtered query. `alias` is the table that owns `created_at` and `id`.
+pub fn push_page(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    alias: &str,
+    cursor: Option<Cursor>,
+    sort: Sort,
+    take: Option<i64>,
+    skip: Option<i64>,
+) {
+    let (comparison, direction) = if sort.is_descending() {
+        ("<", "desc")
+    } else {
+        (">", "asc")
+    };
+
+    if let Some(cursor) = cursor {
+        builder
+            .push(format!(" and ({alias}.created_at, {alias}.id) {comparison} ("))
+            .push_bind(cursor.created_at)
+            .push(", ")
+            .push_bind(cursor.id)
+            .push(")");
+    }
+
+    builder.push(format!(
+        " order by {alias}.created_at {direction}, {alias}.id {direction}"
+    ));
+    builder.push(" limit ").push_bind(take);
+    builder.push(" offset ").push_bind(skip);
+}
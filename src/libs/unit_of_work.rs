@@ -0,0 +1,39 @@
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+
+use crate::error::aggregate::Result;
+
+/// Groups the writes of a single request into one database transaction.
+///
+/// Pass `uow.connection()` to the model methods instead of the pool and call
+/// `commit` once every step succeeded. Returning early (for example through `?`)
+/// drops the unit of work, which rolls every write back.
+pub struct UnitOfWork {
+    transaction: Transaction<'static, Postgres>,
+}
+
+impl UnitOfWork {
+    pub async fn begin(pool: &PgPool) -> Result<UnitOfWork> {
+        let transaction = pool.begin().await?;
+        Ok(UnitOfWork { transaction })
+    }
+
+    pub fn connection(&mut self) -> &mut PgConnection {
+        &mut self.transaction
+    }
+
+    /// Takes a transaction-scoped advisory lock without waiting. Returns `false`
+    /// when another connection already holds it; the lock is released on commit
+    /// or rollback.
+    pub async fn try_advisory_lock(&mut self, key: i64) -> Result<bool> {
+        let locked = sqlx::query_scalar!("select pg_try_advisory_xact_lock($1)", key)
+            .fetch_one(self.connection())
+            .await?;
+
+        Ok(locked.unwrap_or(false))
+    }
+
+    pub async fn commit(self) -> Result<()> {
+        self.transaction.commit().await?;
+        Ok(())
+    }
+}
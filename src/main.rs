@@ -22,9 +22,11 @@ async fn main() {
 
     let pool = libs::database::build().await;
 
+    app::scheduler::spawn(pool.clone());
+
     let app = Router::new()
         .nest("/api", app::router::build(pool))
-        .nest_service("/asset", ServeDir::new("./public/files"))
+        .nest_service("/asset", ServeDir::new(&app::file_upload::STORAGE.root))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|request: &Request<_>| {